@@ -34,13 +34,36 @@ pub fn object(
     code.function(&func);
     module.section(&code);
 
+    // Add our custom section
+    module.section(&custom_section(
+        resolve, world, world_name, encoding, suffix,
+    )?);
+
+    // Append the linking section, so that lld knows the custom section's symbol name
+    let mut linking = LinkingSection::new();
+    let mut symbols = SymbolTable::new();
+    symbols.function(0, 0, Some(&linking_symbol(world_name)));
+    linking.symbol_table(&symbols);
+    module.section(&linking);
+
+    Ok(module.finish())
+}
+
+/// Returns the `component-type` custom section describing `world`.
+pub fn custom_section(
+    resolve: &Resolve,
+    world: WorldId,
+    world_name: &str,
+    encoding: StringEncoding,
+    suffix: Option<&str>,
+) -> Result<CustomSection<'static>> {
     let mut producers = wasm_metadata::Producers::empty();
     producers.add(
         "processed-by",
         env!("CARGO_PKG_NAME"),
         env!("CARGO_PKG_VERSION"),
     );
-    let data = wit_component::metadata::encode(resolve, world, encoding, Some(&producers)).unwrap();
+    let data = wit_component::metadata::encode(resolve, world, encoding, Some(&producers))?;
 
     // The custom section name here must start with "component-type" but
     // otherwise is attempted to be unique here to ensure that this doesn't get
@@ -48,18 +71,8 @@ pub fn object(
     // concatenate custom sections of the same name.
     let section_name = format!("component-type:{world_name}{}", suffix.unwrap_or(""));
 
-    // Add our custom section
-    module.section(&CustomSection {
-        name: std::borrow::Cow::Borrowed(&section_name),
-        data: std::borrow::Cow::Borrowed(data.as_slice()),
-    });
-
-    // Append the linking section, so that lld knows the custom section's symbol name
-    let mut linking = LinkingSection::new();
-    let mut symbols = SymbolTable::new();
-    symbols.function(0, 0, Some(&linking_symbol(world_name)));
-    linking.symbol_table(&symbols);
-    module.section(&linking);
-
-    Ok(module.finish())
+    Ok(CustomSection {
+        name: std::borrow::Cow::Owned(section_name),
+        data: std::borrow::Cow::Owned(data),
+    })
 }
@@ -1,4 +1,4 @@
-mod component_type_object;
+pub mod component_type_object;
 
 use anyhow::Result;
 use heck::*;
@@ -7,8 +7,8 @@ use std::fmt::Write;
 use std::mem;
 use wit_bindgen_core::abi::{self, AbiVariant, Bindgen, Bitcast, Instruction, LiftLower, WasmType};
 use wit_bindgen_core::{
-    dealias, uwrite, uwriteln, wit_parser::*, AnonymousTypeGenerator, Direction, Files,
-    InterfaceGenerator as _, Ns, WorldGenerator,
+    dealias, deprecation, uwrite, uwriteln, wit_parser::*, AnonymousTypeGenerator, Direction,
+    Files, InterfaceGenerator as _, Ns, WorldGenerator,
 };
 use wit_component::StringEncoding;
 
@@ -26,6 +26,8 @@ struct C {
     needs_union_float_int32: bool,
     needs_union_int64_double: bool,
     needs_union_double_int64: bool,
+    needs_layout_asserts: bool,
+    needs_deprecation_pragmas: bool,
     prim_names: HashSet<String>,
     world: String,
     sizes: SizeAlign,
@@ -33,6 +35,10 @@ struct C {
 
     world_id: Option<WorldId>,
     dtor_funcs: HashMap<TypeId, String>,
+    /// Names of the types with `*_clone` and `*_eq` functions, which are
+    /// tracked by name as anonymous types of primitives are shared.
+    clone_funcs: HashSet<String>,
+    eq_funcs: HashSet<String>,
     type_names: HashMap<TypeId, String>,
     resources: HashMap<TypeId, ResourceInfo>,
 }
@@ -99,6 +105,13 @@ pub struct Opts {
     /// Configure the autodropping of borrows in exported functions.
     #[cfg_attr(feature = "clap", arg(long, default_value_t = Enabled::default()))]
     pub autodrop_borrows: Enabled,
+
+    /// Route all allocation in the generated C source, including
+    /// `cabi_realloc`, through the `{WORLD}_MALLOC`, `{WORLD}_REALLOC` and
+    /// `{WORLD}_FREE` macros, which default to the C standard library and may
+    /// be defined when compiling the bindings to use a custom allocator.
+    #[cfg_attr(feature = "clap", arg(long, default_value_t = false))]
+    pub allocator_macros: bool,
 }
 
 #[cfg(feature = "clap")]
@@ -303,74 +316,10 @@ impl WorldGenerator for C {
 
         if self.needs_string {
             self.c_include("<string.h>");
-            let (strlen, size) = match self.opts.string_encoding {
-                StringEncoding::UTF8 => (format!("strlen(s)"), 1),
-                StringEncoding::UTF16 => {
-                    self.h_include("<uchar.h>");
-                    uwrite!(
-                        self.src.h_helpers,
-                        "
-                            size_t {snake}_string_len(const char16_t* s);
-                        ",
-                    );
-                    uwrite!(
-                        self.src.c_helpers,
-                        "
-                            size_t {snake}_string_len(const char16_t* s) {{
-                                char16_t* c = (char16_t*)s;
-                                for (; *c; ++c);
-                                return c-s;
-                            }}
-                        ",
-                    );
-                    (format!("{snake}_string_len(s)"), 2)
-                }
-                StringEncoding::CompactUTF16 => unimplemented!(),
-            };
-            let ty = self.char_type();
-            let c_string_ty = match self.opts.string_encoding {
-                StringEncoding::UTF8 => "char",
-                StringEncoding::UTF16 => "char16_t",
-                StringEncoding::CompactUTF16 => panic!("Compact UTF16 unsupported"),
-            };
-            uwrite!(
-                self.src.h_helpers,
-                "
-                   // Transfers ownership of `s` into the string `ret`
-                   void {snake}_string_set({snake}_string_t *ret, const {c_string_ty} *s);
-
-                   // Creates a copy of the input nul-terminate string `s` and
-                   // stores it into the component model string `ret`.
-                   void {snake}_string_dup({snake}_string_t *ret, const {c_string_ty} *s);
-
-                   // Deallocates the string pointed to by `ret`, deallocating
-                   // the memory behind the string.
-                   void {snake}_string_free({snake}_string_t *ret);\
-               ",
-            );
-            uwrite!(
-                self.src.c_helpers,
-                "
-                   void {snake}_string_set({snake}_string_t *ret, const {c_string_ty} *s) {{
-                       ret->ptr = ({ty}*) s;
-                       ret->len = {strlen};
-                   }}
-
-                   void {snake}_string_dup({snake}_string_t *ret, const {c_string_ty} *s) {{
-                       ret->len = {strlen};
-                       ret->ptr = ({ty}*) cabi_realloc(NULL, 0, {size}, ret->len * {size});
-                       memcpy(ret->ptr, s, ret->len * {size});
-                   }}
-
-                   void {snake}_string_free({snake}_string_t *ret) {{
-                       if (ret->len > 0) {{
-                           free(ret->ptr);
-                       }}
-                       ret->ptr = NULL;
-                       ret->len = 0;
-                   }}
-               ",
-            );
+            match self.opts.string_encoding {
+                StringEncoding::UTF8 | StringEncoding::UTF16 => self.print_string_helpers(),
+                StringEncoding::CompactUTF16 => self.print_compact_utf16_string_helpers(),
+            }
         }
         if self.needs_union_int32_float {
             uwriteln!(
@@ -414,6 +363,31 @@ impl WorldGenerator for C {
         h_str.deindent(1);
         uwriteln!(h_str, "\n#endif\n");
 
+        // Deprecated types are still used by the bindings themselves.
+        if self.needs_deprecation_pragmas {
+            uwriteln!(
+                h_str,
+                "#pragma GCC diagnostic push
+                #pragma GCC diagnostic ignored \"-Wdeprecated-declarations\"
+                "
+            );
+        }
+
+        let string_layout_asserts = if self.needs_string {
+            // The length follows the pointer, which is aligned to its size.
+            let size = self.sizes.size(&Type::String);
+            let align = self.sizes.align(&Type::String);
+            let name = format!("{snake}_string_t");
+            self.layout_asserts(&name, size, align, &[("ptr", 0), ("len", align)])
+        } else {
+            String::new()
+        };
+        if self.needs_layout_asserts {
+            self.h_include("<assert.h>");
+            self.h_include("<stdalign.h>");
+            self.h_include("<stddef.h>");
+        }
+
         uwriteln!(h_str, "#include <stdint.h>");
         uwriteln!(h_str, "#include <stdbool.h>");
         for include in self.h_includes.iter() {
@@ -426,6 +400,15 @@ impl WorldGenerator for C {
         for include in self.c_includes.iter() {
             uwriteln!(c_str, "#include {include}");
         }
+        if self.needs_deprecation_pragmas {
+            uwriteln!(
+                c_str,
+                "#pragma GCC diagnostic ignored \"-Wdeprecated-declarations\""
+            );
+        }
+        if self.opts.allocator_macros {
+            self.print_allocator_macros(&mut c_str);
+        }
         c_str.push_str(&self.src.c_defs);
         c_str.push_str(&self.src.c_fns);
 
@@ -439,6 +422,17 @@ impl WorldGenerator for C {
                 }} {snake}_string_t;",
                 ty = self.char_type(),
             );
+            h_str.push_str(&string_layout_asserts);
+            if self.opts.string_encoding == StringEncoding::CompactUTF16 {
+                uwriteln!(
+                    h_str,
+                    "
+                    // Set in the `len` of a string whose code units are UTF-16
+                    // rather than Latin-1.
+                    #define {}_STRING_UTF16_TAG ((size_t) 1 << 31)",
+                    self.world.to_shouty_snake_case(),
+                );
+            }
         }
         if self.src.h_defs.len() > 0 {
             h_str.push_str(&self.src.h_defs);
@@ -476,6 +470,9 @@ impl WorldGenerator for C {
         }
         c_str.push_str(&self.src.c_adapters);
 
+        if self.needs_deprecation_pragmas {
+            uwriteln!(h_str, "\n#pragma GCC diagnostic pop");
+        }
         uwriteln!(
             h_str,
             "
@@ -528,6 +525,36 @@ impl C {
         }
     }
 
+    /// Returns compile-time checks that the layout of the C struct `name`
+    /// matches the canonical ABI, as lists of it are passed directly to and
+    /// from the component. These use `static_assert` and `alignof` from
+    /// `<assert.h>` and `<stdalign.h>` so the header also works from C++.
+    fn layout_asserts(
+        &mut self,
+        name: &str,
+        size: usize,
+        align: usize,
+        fields: &[(&str, usize)],
+    ) -> String {
+        self.needs_layout_asserts = true;
+        let mut ret = String::new();
+        uwriteln!(
+            ret,
+            "static_assert(sizeof({name}) == {size}, \"size of {name}\");"
+        );
+        uwriteln!(
+            ret,
+            "static_assert(alignof({name}) == {align}, \"alignment of {name}\");"
+        );
+        for (field, offset) in fields {
+            uwriteln!(
+                ret,
+                "static_assert(offsetof({name}, {field}) == {offset}, \"offset of {name}.{field}\");"
+            );
+        }
+        ret
+    }
+
     fn h_include(&mut self, s: &str) {
         self.h_includes.push(s.to_string());
     }
@@ -540,10 +567,235 @@ impl C {
         match self.opts.string_encoding {
             StringEncoding::UTF8 => "uint8_t",
             StringEncoding::UTF16 => "uint16_t",
-            StringEncoding::CompactUTF16 => panic!("Compact UTF16 unsupported"),
+            // Code units are either Latin-1 bytes or UTF-16, as indicated by
+            // the tag in the string's length.
+            StringEncoding::CompactUTF16 => "uint8_t",
         }
     }
 
+    fn print_string_helpers(&mut self) {
+        let snake = self.world.to_snake_case();
+        let free = self.alloc_fn("free");
+        let (strlen, size) = match self.opts.string_encoding {
+            StringEncoding::UTF8 => (format!("strlen(s)"), 1),
+            StringEncoding::UTF16 => {
+                self.h_include("<uchar.h>");
+                uwrite!(
+                    self.src.h_helpers,
+                    "
+                        size_t {snake}_string_len(const char16_t* s);
+                    ",
+                );
+                uwrite!(
+                    self.src.c_helpers,
+                    "
+                        size_t {snake}_string_len(const char16_t* s) {{
+                            char16_t* c = (char16_t*)s;
+                            for (; *c; ++c);
+                            return c-s;
+                        }}
+                    ",
+                );
+                (format!("{snake}_string_len(s)"), 2)
+            }
+            StringEncoding::CompactUTF16 => unreachable!(),
+        };
+        let ty = self.char_type();
+        let c_string_ty = match self.opts.string_encoding {
+            StringEncoding::UTF8 => "char",
+            StringEncoding::UTF16 => "char16_t",
+            StringEncoding::CompactUTF16 => unreachable!(),
+        };
+        uwrite!(
+            self.src.h_helpers,
+            "
+               // Transfers ownership of `s` into the string `ret`
+               void {snake}_string_set({snake}_string_t *ret, const {c_string_ty} *s);
+
+               // Creates a copy of the input nul-terminate string `s` and
+               // stores it into the component model string `ret`.
+               void {snake}_string_dup({snake}_string_t *ret, const {c_string_ty} *s);
+
+               // Creates a copy of the string `src` and stores it into `dst`.
+               void {snake}_string_clone({snake}_string_t *dst, const {snake}_string_t *src);
+
+               // Returns whether the strings `a` and `b` are equal.
+               bool {snake}_string_eq(const {snake}_string_t *a, const {snake}_string_t *b);
+
+               // Deallocates the string pointed to by `ret`, deallocating
+               // the memory behind the string.
+               void {snake}_string_free({snake}_string_t *ret);\
+           ",
+        );
+        uwrite!(
+            self.src.c_helpers,
+            "
+               void {snake}_string_set({snake}_string_t *ret, const {c_string_ty} *s) {{
+                   ret->ptr = ({ty}*) s;
+                   ret->len = {strlen};
+               }}
+
+               void {snake}_string_dup({snake}_string_t *ret, const {c_string_ty} *s) {{
+                   ret->len = {strlen};
+                   ret->ptr = ({ty}*) cabi_realloc(NULL, 0, {size}, ret->len * {size});
+                   memcpy(ret->ptr, s, ret->len * {size});
+               }}
+
+               void {snake}_string_clone({snake}_string_t *dst, const {snake}_string_t *src) {{
+                   dst->len = src->len;
+                   dst->ptr = ({ty}*) cabi_realloc(NULL, 0, {size}, src->len * {size});
+                   memcpy(dst->ptr, src->ptr, src->len * {size});
+               }}
+
+               bool {snake}_string_eq(const {snake}_string_t *a, const {snake}_string_t *b) {{
+                   return a->len == b->len
+                       && (a->len == 0 || memcmp(a->ptr, b->ptr, a->len * {size}) == 0);
+               }}
+
+               void {snake}_string_free({snake}_string_t *ret) {{
+                   if (ret->len > 0) {{
+                       {free}(ret->ptr);
+                   }}
+                   ret->ptr = NULL;
+                   ret->len = 0;
+               }}
+           ",
+        );
+    }
+
+    /// Helpers for `latin1+utf16` strings, whose `len` is the number of code
+    /// units tagged with `*_STRING_UTF16_TAG` if they're UTF-16 rather than
+    /// Latin-1.
+    fn print_compact_utf16_string_helpers(&mut self) {
+        let snake = self.world.to_snake_case();
+        let free = self.alloc_fn("free");
+        let shouty = self.world.to_shouty_snake_case();
+        self.h_include("<uchar.h>");
+        uwrite!(
+            self.src.h_helpers,
+            "
+               // Returns whether the code units of `s` are UTF-16 rather than
+               // Latin-1.
+               bool {snake}_string_is_utf16(const {snake}_string_t *s);
+
+               // Returns the number of code units in `s`, without the UTF-16 tag.
+               size_t {snake}_string_code_units(const {snake}_string_t *s);
+
+               // Transfers ownership of the Latin-1 string `s` into the string
+               // `ret`. Note that `s` must be 2-byte aligned.
+               void {snake}_string_set({snake}_string_t *ret, const char *s);
+
+               // Creates a copy of the input nul-terminated Latin-1 string `s`
+               // and stores it into the component model string `ret`.
+               void {snake}_string_dup({snake}_string_t *ret, const char *s);
+
+               // Transfers ownership of the UTF-16 string `s` into the string
+               // `ret`.
+               void {snake}_string_set_utf16({snake}_string_t *ret, const char16_t *s);
+
+               // Creates a copy of the input nul-terminated UTF-16 string `s`
+               // and stores it into the component model string `ret`.
+               void {snake}_string_dup_utf16({snake}_string_t *ret, const char16_t *s);
+
+               // Creates a copy of the string `src` and stores it into `dst`,
+               // keeping its encoding.
+               void {snake}_string_clone({snake}_string_t *dst, const {snake}_string_t *src);
+
+               // Returns whether the strings `a` and `b` have the same code
+               // units, regardless of their encoding.
+               bool {snake}_string_eq(const {snake}_string_t *a, const {snake}_string_t *b);
+
+               // Deallocates the string pointed to by `ret`, deallocating
+               // the memory behind the string.
+               void {snake}_string_free({snake}_string_t *ret);\
+           ",
+        );
+        uwrite!(
+            self.src.c_helpers,
+            "
+               static size_t {snake}_utf16_len(const char16_t *s) {{
+                   const char16_t *c = s;
+                   for (; *c; ++c);
+                   return c - s;
+               }}
+
+               bool {snake}_string_is_utf16(const {snake}_string_t *s) {{
+                   return (s->len & {shouty}_STRING_UTF16_TAG) != 0;
+               }}
+
+               size_t {snake}_string_code_units(const {snake}_string_t *s) {{
+                   return s->len & ~{shouty}_STRING_UTF16_TAG;
+               }}
+
+               void {snake}_string_set({snake}_string_t *ret, const char *s) {{
+                   ret->ptr = (uint8_t*) s;
+                   ret->len = strlen(s);
+               }}
+
+               void {snake}_string_dup({snake}_string_t *ret, const char *s) {{
+                   size_t len = strlen(s);
+                   ret->ptr = (uint8_t*) cabi_realloc(NULL, 0, 2, len);
+                   memcpy(ret->ptr, s, len);
+                   ret->len = len;
+               }}
+
+               void {snake}_string_set_utf16({snake}_string_t *ret, const char16_t *s) {{
+                   ret->ptr = (uint8_t*) s;
+                   ret->len = {snake}_utf16_len(s) | {shouty}_STRING_UTF16_TAG;
+               }}
+
+               void {snake}_string_dup_utf16({snake}_string_t *ret, const char16_t *s) {{
+                   size_t len = {snake}_utf16_len(s);
+                   ret->ptr = (uint8_t*) cabi_realloc(NULL, 0, 2, len * 2);
+                   memcpy(ret->ptr, s, len * 2);
+                   ret->len = len | {shouty}_STRING_UTF16_TAG;
+               }}
+
+               void {snake}_string_clone({snake}_string_t *dst, const {snake}_string_t *src) {{
+                   size_t units = {snake}_string_code_units(src);
+                   size_t size = {snake}_string_is_utf16(src) ? units * 2 : units;
+                   dst->ptr = (uint8_t*) cabi_realloc(NULL, 0, 2, size);
+                   memcpy(dst->ptr, src->ptr, size);
+                   dst->len = src->len;
+               }}
+
+               static char16_t {snake}_string_code_unit(const {snake}_string_t *s, size_t i) {{
+                   if ({snake}_string_is_utf16(s)) {{
+                       return ((const char16_t*) s->ptr)[i];
+                   }}
+                   return s->ptr[i];
+               }}
+
+               bool {snake}_string_eq(const {snake}_string_t *a, const {snake}_string_t *b) {{
+                   size_t units = {snake}_string_code_units(a);
+                   if (units != {snake}_string_code_units(b)) {{
+                       return false;
+                   }}
+                   if (units > 0 && {snake}_string_is_utf16(a) == {snake}_string_is_utf16(b)) {{
+                       size_t size = {snake}_string_is_utf16(a) ? units * 2 : units;
+                       return memcmp(a->ptr, b->ptr, size) == 0;
+                   }}
+                   // Latin-1 code units are the same as the first 256 UTF-16
+                   // code units.
+                   for (size_t i = 0; i < units; i++) {{
+                       if ({snake}_string_code_unit(a, i) != {snake}_string_code_unit(b, i)) {{
+                           return false;
+                       }}
+                   }}
+                   return true;
+               }}
+
+               void {snake}_string_free({snake}_string_t *ret) {{
+                   if ({snake}_string_code_units(ret) > 0) {{
+                       {free}(ret->ptr);
+                   }}
+                   ret->ptr = NULL;
+                   ret->len = 0;
+               }}
+           ",
+        );
+    }
+
     fn type_name(&mut self, ty: &Type) -> String {
         let mut name = String::new();
         self.push_type_name(ty, &mut name);
@@ -778,8 +1030,25 @@ pub fn push_ty_name(resolve: &Resolve, ty: &Type, src: &mut String) {
                     src.push_str("list_");
                     push_ty_name(resolve, ty, src);
                 }
-                TypeDefKind::Future(_) => unimplemented!(),
-                TypeDefKind::Stream(_) => unimplemented!(),
+                TypeDefKind::Future(ty) => {
+                    src.push_str("future_");
+                    match ty {
+                        Some(ty) => push_ty_name(resolve, ty, src),
+                        None => src.push_str("void"),
+                    }
+                }
+                TypeDefKind::Stream(s) => {
+                    src.push_str("stream_");
+                    match &s.element {
+                        Some(ty) => push_ty_name(resolve, ty, src),
+                        None => src.push_str("void"),
+                    }
+                    src.push('_');
+                    match &s.end {
+                        Some(ty) => push_ty_name(resolve, ty, src),
+                        None => src.push_str("void"),
+                    }
+                }
                 TypeDefKind::Handle(Handle::Own(resource)) => {
                     src.push_str("own_");
                     push_ty_name(resolve, &Type::Id(*resource), src);
@@ -914,18 +1183,53 @@ impl C {
         // overridden from some other symbol.
         self.src.c_fns("\n// Canonical ABI intrinsics");
         self.src.c_fns("\n");
-        self.src.c_fns(
+        let realloc = self.alloc_fn("realloc");
+        self.src.c_fns(&format!(
             r#"
                 __attribute__((__weak__, __export_name__("cabi_realloc")))
-                void *cabi_realloc(void *ptr, size_t old_size, size_t align, size_t new_size) {
+                void *cabi_realloc(void *ptr, size_t old_size, size_t align, size_t new_size) {{
                     (void) old_size;
                     if (new_size == 0) return (void*) align;
-                    void *ret = realloc(ptr, new_size);
+                    void *ret = {realloc}(ptr, new_size);
                     if (!ret) abort();
                     return ret;
-                }
+                }}
             "#,
-        );
+        ));
+    }
+
+    /// Returns how the generated C source calls the C standard library
+    /// allocation function `name`, which is through an overridable macro if
+    /// `allocator_macros` is enabled.
+    fn alloc_fn(&self, name: &str) -> String {
+        if self.opts.allocator_macros {
+            format!(
+                "{}_{}",
+                self.world.to_shouty_snake_case(),
+                name.to_shouty_snake_case()
+            )
+        } else {
+            name.to_string()
+        }
+    }
+
+    fn print_allocator_macros(&self, src: &mut wit_bindgen_core::Source) {
+        let shouty = self.world.to_shouty_snake_case();
+        src.push_str(&format!(
+            "
+            // Allocation functions used by these bindings, which may be defined
+            // when compiling them to use a custom allocator.
+            #ifndef {shouty}_MALLOC
+            #define {shouty}_MALLOC(size) malloc(size)
+            #endif
+            #ifndef {shouty}_REALLOC
+            #define {shouty}_REALLOC(ptr, size) realloc(ptr, size)
+            #endif
+            #ifndef {shouty}_FREE
+            #define {shouty}_FREE(ptr) free(ptr)
+            #endif
+            "
+        ));
     }
 }
 
@@ -951,8 +1255,13 @@ impl Return {
         match &resolve.types[id].kind {
             TypeDefKind::Type(t) => return self.return_single(resolve, t, orig_ty, sig_flattening),
 
-            // Flags are returned as their bare values, and enums and handles are scalars
-            TypeDefKind::Flags(_) | TypeDefKind::Enum(_) | TypeDefKind::Handle(_) => {
+            // Flags are returned as their bare values, and enums, handles,
+            // futures and streams are scalars
+            TypeDefKind::Flags(_)
+            | TypeDefKind::Enum(_)
+            | TypeDefKind::Handle(_)
+            | TypeDefKind::Future(_)
+            | TypeDefKind::Stream(_) => {
                 self.scalar = Some(Scalar::Type(*orig_ty));
                 return;
             }
@@ -989,8 +1298,6 @@ impl Return {
             | TypeDefKind::List(_)
             | TypeDefKind::Variant(_) => {}
 
-            TypeDefKind::Future(_) => todo!("return_single for future"),
-            TypeDefKind::Stream(_) => todo!("return_single for stream"),
             TypeDefKind::Resource => todo!("return_single for resource"),
             TypeDefKind::Unknown => unreachable!(),
         }
@@ -1016,6 +1323,7 @@ impl<'a> wit_bindgen_core::InterfaceGenerator<'a> for InterfaceGenerator<'a> {
             self.src.h_defs(";\n");
         }
         self.finish_typedef_struct(id);
+        self.print_layout_asserts(id);
     }
 
     fn type_resource(&mut self, id: TypeId, name: &str, _docs: &Docs) {
@@ -1044,7 +1352,9 @@ extern void {ns}_{snake}_drop_own({own} handle);
         } else {
             let module = match self.interface {
                 Some((_, key)) => self.resolve.name_world_key(key),
-                None => unimplemented!("resource exports from worlds"),
+                // Worlds can't export types, so resources defined in a world
+                // are always imported, even when used by exported functions.
+                None => unreachable!("worlds can't export resources"),
             };
             format!("[export]{module}")
         };
@@ -1067,6 +1377,8 @@ void {ns}_{snake}_drop_own({own} handle) {{
         self.src.h_defs(&format!(
             "\ntypedef struct {own} {{\nint32_t __handle;\n}} {own};\n"
         ));
+        let asserts = self.gen.layout_asserts(&own, 4, 4, &[("__handle", 0)]);
+        self.src.h_defs(&asserts);
 
         if self.in_import {
             // For imported resources borrowed handles are represented the same
@@ -1075,6 +1387,8 @@ void {ns}_{snake}_drop_own({own} handle) {{
             self.src.h_defs(&format!(
                 "\ntypedef struct {borrow} {{\nint32_t __handle;\n}} {borrow};\n"
             ));
+            let asserts = self.gen.layout_asserts(&borrow, 4, 4, &[("__handle", 0)]);
+            self.src.h_defs(&asserts);
 
             if self.autodrop_enabled() {
                 // As we have two different types for owned vs borrowed resources,
@@ -1192,6 +1506,7 @@ void __wasm_export_{ns}_{snake}_dtor({ns}_{snake}_t* arg) {{
             uwriteln!(self.src.h_defs, " f{i};");
         }
         self.finish_typedef_struct(id);
+        self.print_layout_asserts(id);
     }
 
     fn type_flags(&mut self, id: TypeId, name: &str, flags: &Flags, docs: &Docs) {
@@ -1201,17 +1516,26 @@ void __wasm_export_{ns}_{snake}_dtor({ns}_{snake}_t* arg) {{
         let repr = flags_repr(flags);
         self.src.h_defs(int_repr(repr));
         self.src.h_defs(" ");
+        // Flags with more than 32 members are stored as two 32-bit words,
+        // so they're only 4-byte aligned in the canonical ABI.
+        if let Int::U64 = repr {
+            self.src.h_defs("__attribute__((__aligned__(4))) ");
+        }
         self.print_typedef_target(id);
 
         if flags.flags.len() > 0 {
             self.src.h_defs("\n");
         }
         let ns = self.owner_namespace(id).to_shouty_snake_case();
+        let one = match repr {
+            Int::U64 => "1ULL",
+            _ => "1",
+        };
         for (i, flag) in flags.flags.iter().enumerate() {
             self.docs(&flag.docs, SourceType::HDefs);
             uwriteln!(
                 self.src.h_defs,
-                "#define {ns}_{}_{} (1 << {i})",
+                "#define {ns}_{}_{} ({one} << {i})",
                 name.to_shouty_snake_case(),
                 flag.name.to_shouty_snake_case(),
             );
@@ -1243,6 +1567,7 @@ void __wasm_export_{ns}_{snake}_dtor({ns}_{snake}_t* arg) {{
             self.src.h_defs("} val;\n");
         }
         self.finish_typedef_struct(id);
+        self.print_layout_asserts(id);
 
         if variant.cases.len() > 0 {
             self.src.h_defs("\n");
@@ -1267,6 +1592,7 @@ void __wasm_export_{ns}_{snake}_dtor({ns}_{snake}_t* arg) {{
         self.print_ty(SourceType::HDefs, payload);
         self.src.h_defs(" val;\n");
         self.finish_typedef_struct(id);
+        self.print_layout_asserts(id);
     }
 
     fn type_result(&mut self, id: TypeId, _name: &str, result: &Result_, docs: &Docs) {
@@ -1287,6 +1613,7 @@ void __wasm_export_{ns}_{snake}_dtor({ns}_{snake}_t* arg) {{
             self.src.h_defs("} val;\n");
         }
         self.finish_typedef_struct(id);
+        self.print_layout_asserts(id);
     }
 
     fn type_enum(&mut self, id: TypeId, name: &str, enum_: &Enum, docs: &Docs) {
@@ -1336,6 +1663,7 @@ void __wasm_export_{ns}_{snake}_dtor({ns}_{snake}_t* arg) {{
         self.src.h_defs(" *ptr;\n");
         self.src.h_defs("size_t len;\n");
         self.finish_typedef_struct(id);
+        self.print_layout_asserts(id);
     }
 
     fn type_builtin(&mut self, id: TypeId, name: &str, ty: &Type, docs: &Docs) {
@@ -1372,6 +1700,7 @@ impl<'a> wit_bindgen_core::AnonymousTypeGenerator<'a> for InterfaceGenerator<'a>
         self.src.h_defs("}");
         self.src.h_defs(" ");
         self.print_typedef_target(id);
+        self.print_layout_asserts(id);
     }
 
     fn anonymous_type_option(&mut self, id: TypeId, ty: &Type, _docs: &Docs) {
@@ -1383,6 +1712,7 @@ impl<'a> wit_bindgen_core::AnonymousTypeGenerator<'a> for InterfaceGenerator<'a>
         self.src.h_defs("}");
         self.src.h_defs(" ");
         self.print_typedef_target(id);
+        self.print_layout_asserts(id);
     }
 
     fn anonymous_type_result(&mut self, id: TypeId, ty: &Result_, _docs: &Docs) {
@@ -1409,6 +1739,7 @@ impl<'a> wit_bindgen_core::AnonymousTypeGenerator<'a> for InterfaceGenerator<'a>
         self.src.h_defs("}");
         self.src.h_defs(" ");
         self.print_typedef_target(id);
+        self.print_layout_asserts(id);
     }
 
     fn anonymous_type_list(&mut self, id: TypeId, ty: &Type, _docs: &Docs) {
@@ -1420,14 +1751,19 @@ impl<'a> wit_bindgen_core::AnonymousTypeGenerator<'a> for InterfaceGenerator<'a>
         self.src.h_defs("}");
         self.src.h_defs(" ");
         self.print_typedef_target(id);
+        self.print_layout_asserts(id);
     }
 
-    fn anonymous_type_future(&mut self, _id: TypeId, _ty: &Option<Type>, _docs: &Docs) {
-        todo!("print_anonymous_type for future");
+    fn anonymous_type_future(&mut self, id: TypeId, _ty: &Option<Type>, _docs: &Docs) {
+        // Futures are passed around as opaque handles owned by the host.
+        self.src.h_defs("\ntypedef uint32_t ");
+        self.print_typedef_target(id);
     }
 
-    fn anonymous_type_stream(&mut self, _id: TypeId, _ty: &Stream, _docs: &Docs) {
-        todo!("print_anonymous_type for stream");
+    fn anonymous_type_stream(&mut self, id: TypeId, _ty: &Stream, _docs: &Docs) {
+        // Streams, like futures, are opaque handles owned by the host.
+        self.src.h_defs("\ntypedef uint32_t ");
+        self.print_typedef_target(id);
     }
 
     fn anonymous_typ_type(&mut self, _id: TypeId, _ty: &Type, _docs: &Docs) {
@@ -1523,6 +1859,8 @@ impl InterfaceGenerator<'_> {
             }
 
             self.define_dtor(ty);
+            self.define_clone(ty);
+            self.define_eq(ty);
         }
     }
 
@@ -1567,7 +1905,8 @@ impl InterfaceGenerator<'_> {
                     .c_helpers("for (size_t i = 0; i < list_len; i++) {\n");
                 self.free(t, "&list_ptr[i]");
                 self.src.c_helpers("}\n");
-                uwriteln!(self.src.c_helpers, "free(list_ptr);");
+                let free = self.gen.alloc_fn("free");
+                uwriteln!(self.src.c_helpers, "{free}(list_ptr);");
                 uwriteln!(self.src.c_helpers, "}}");
             }
 
@@ -1602,9 +1941,7 @@ impl InterfaceGenerator<'_> {
                 }
                 self.src.c_helpers("}\n");
             }
-            TypeDefKind::Future(_) => todo!("print_dtor for future"),
-            TypeDefKind::Stream(_) => todo!("print_dtor for stream"),
-            TypeDefKind::Resource => {}
+            TypeDefKind::Future(_) | TypeDefKind::Stream(_) | TypeDefKind::Resource => {}
             TypeDefKind::Handle(Handle::Borrow(id) | Handle::Own(id)) => {
                 self.free(&Type::Id(*id), "*ptr");
             }
@@ -1646,6 +1983,321 @@ impl InterfaceGenerator<'_> {
         }
     }
 
+    /// Defines a `*_clone` function performing a deep copy of the aggregate
+    /// type `id`.
+    ///
+    /// Owned handles can't be duplicated so no function is defined for types
+    /// containing them, while borrowed handles are copied as-is and are only
+    /// valid for as long as the handle they were copied from.
+    fn define_clone(&mut self, id: TypeId) {
+        match &self.resolve.types[id].kind {
+            TypeDefKind::Type(Type::Id(t)) if self.clone_func(*t).is_none() => return,
+            TypeDefKind::Type(Type::String)
+            | TypeDefKind::Type(Type::Id(_))
+            | TypeDefKind::Record(_)
+            | TypeDefKind::Tuple(_)
+            | TypeDefKind::Variant(_)
+            | TypeDefKind::List(_)
+            | TypeDefKind::Option(_)
+            | TypeDefKind::Result(_) => {}
+            _ => return,
+        }
+        if contains_own_handle(self.resolve, &Type::Id(id)) {
+            return;
+        }
+
+        let name = self.gen.type_names[&id].clone();
+        let prefix = name.strip_suffix("_t").unwrap();
+        self.src.h_helpers(&format!(
+            "\nvoid {prefix}_clone({name} *dst, const {name} *src);\n"
+        ));
+        self.src.c_helpers(&format!(
+            "\nvoid {prefix}_clone({name} *dst, const {name} *src) {{\n"
+        ));
+        match &self.resolve.types[id].kind {
+            TypeDefKind::Type(t) => self.clone(t, "*dst", "*src"),
+
+            TypeDefKind::Record(r) => {
+                for field in r.fields.iter() {
+                    let ident = to_c_ident(&field.name);
+                    self.clone(
+                        &field.ty,
+                        &format!("dst->{ident}"),
+                        &format!("src->{ident}"),
+                    );
+                }
+            }
+
+            TypeDefKind::Tuple(t) => {
+                for (i, ty) in t.types.iter().enumerate() {
+                    self.clone(ty, &format!("dst->f{i}"), &format!("src->f{i}"));
+                }
+            }
+
+            TypeDefKind::List(t) => {
+                let t_name = self.gen.type_name(t);
+                uwriteln!(self.src.c_helpers, "dst->len = src->len;");
+                uwriteln!(self.src.c_helpers, "dst->ptr = NULL;");
+                uwriteln!(self.src.c_helpers, "if (src->len > 0) {{");
+                let malloc = self.gen.alloc_fn("malloc");
+                uwriteln!(
+                    self.src.c_helpers,
+                    "dst->ptr = ({t_name}*) {malloc}(src->len * sizeof({t_name}));"
+                );
+                self.src
+                    .c_helpers("for (size_t i = 0; i < src->len; i++) {\n");
+                self.clone(t, "dst->ptr[i]", "src->ptr[i]");
+                self.src.c_helpers("}\n");
+                uwriteln!(self.src.c_helpers, "}}");
+            }
+
+            TypeDefKind::Variant(v) => {
+                self.src.c_helpers("dst->tag = src->tag;\n");
+                if v.cases.iter().any(|case| case.ty.is_some()) {
+                    self.src.c_helpers("switch ((int32_t) src->tag) {\n");
+                    for (i, case) in v.cases.iter().enumerate() {
+                        if let Some(ty) = &case.ty {
+                            uwriteln!(self.src.c_helpers, "case {}: {{", i);
+                            let case = to_c_ident(&case.name);
+                            self.clone(
+                                ty,
+                                &format!("dst->val.{case}"),
+                                &format!("src->val.{case}"),
+                            );
+                            self.src.c_helpers("break;\n");
+                            self.src.c_helpers("}\n");
+                        }
+                    }
+                    self.src.c_helpers("}\n");
+                }
+            }
+
+            TypeDefKind::Option(t) => {
+                self.src.c_helpers("dst->is_some = src->is_some;\n");
+                self.src.c_helpers("if (src->is_some) {\n");
+                self.clone(t, "dst->val", "src->val");
+                self.src.c_helpers("}\n");
+            }
+
+            TypeDefKind::Result(r) => {
+                self.src.c_helpers("dst->is_err = src->is_err;\n");
+                if let Some(ok) = &r.ok {
+                    self.src.c_helpers("if (!src->is_err) {\n");
+                    self.clone(ok, "dst->val.ok", "src->val.ok");
+                    self.src.c_helpers("}\n");
+                }
+                if let Some(err) = &r.err {
+                    self.src.c_helpers("if (src->is_err) {\n");
+                    self.clone(err, "dst->val.err", "src->val.err");
+                    self.src.c_helpers("}\n");
+                }
+            }
+
+            _ => unreachable!(),
+        }
+        self.src.c_helpers("}\n");
+        self.gen.clone_funcs.insert(name);
+    }
+
+    /// Returns the `*_clone` function of the type `id`, if it has one.
+    fn clone_func(&self, id: TypeId) -> Option<String> {
+        let name = self.gen.type_names.get(&id)?;
+        let prefix = name.strip_suffix("_t").unwrap();
+        self.gen
+            .clone_funcs
+            .contains(name)
+            .then(|| format!("{prefix}_clone"))
+    }
+
+    /// Copies the value `src` of type `ty` into `dst`, where both are lvalues.
+    fn clone(&mut self, ty: &Type, dst: &str, src: &str) {
+        match ty {
+            Type::Id(id) => {
+                if let Some(clone) = self.clone_func(*id) {
+                    uwriteln!(self.src.c_helpers, "{clone}(&{dst}, &{src});");
+                } else if let TypeDefKind::Type(t) = &self.resolve.types[*id].kind {
+                    self.clone(t, dst, src);
+                } else {
+                    uwriteln!(self.src.c_helpers, "{dst} = {src};");
+                }
+            }
+            Type::String => {
+                let snake = self.gen.world.to_snake_case();
+                uwriteln!(self.src.c_helpers, "{snake}_string_clone(&{dst}, &{src});");
+            }
+            Type::Bool
+            | Type::U8
+            | Type::S8
+            | Type::U16
+            | Type::S16
+            | Type::U32
+            | Type::S32
+            | Type::U64
+            | Type::S64
+            | Type::F32
+            | Type::F64
+            | Type::Char => uwriteln!(self.src.c_helpers, "{dst} = {src};"),
+        }
+    }
+
+    /// Defines an `*_eq` function comparing two values of the aggregate type
+    /// `id` structurally, where handles are equal if they're the same handle.
+    fn define_eq(&mut self, id: TypeId) {
+        match &self.resolve.types[id].kind {
+            TypeDefKind::Type(Type::Id(t)) if self.eq_func(*t).is_none() => return,
+            TypeDefKind::Type(Type::String)
+            | TypeDefKind::Type(Type::Id(_))
+            | TypeDefKind::Record(_)
+            | TypeDefKind::Tuple(_)
+            | TypeDefKind::Variant(_)
+            | TypeDefKind::List(_)
+            | TypeDefKind::Option(_)
+            | TypeDefKind::Result(_) => {}
+            _ => return,
+        }
+
+        let name = self.gen.type_names[&id].clone();
+        let prefix = name.strip_suffix("_t").unwrap();
+        self.src.h_helpers(&format!(
+            "\nbool {prefix}_eq(const {name} *a, const {name} *b);\n"
+        ));
+        self.src.c_helpers(&format!(
+            "\nbool {prefix}_eq(const {name} *a, const {name} *b) {{\n"
+        ));
+        match &self.resolve.types[id].kind {
+            TypeDefKind::Type(t) => {
+                let eq = self.eq(t, "*a", "*b");
+                uwriteln!(self.src.c_helpers, "return {eq};");
+            }
+
+            TypeDefKind::Record(r) => {
+                for field in r.fields.iter() {
+                    let ident = to_c_ident(&field.name);
+                    let eq = self.eq(&field.ty, &format!("a->{ident}"), &format!("b->{ident}"));
+                    uwriteln!(self.src.c_helpers, "if (!({eq})) return false;");
+                }
+                uwriteln!(self.src.c_helpers, "return true;");
+            }
+
+            TypeDefKind::Tuple(t) => {
+                for (i, ty) in t.types.iter().enumerate() {
+                    let eq = self.eq(ty, &format!("a->f{i}"), &format!("b->f{i}"));
+                    uwriteln!(self.src.c_helpers, "if (!({eq})) return false;");
+                }
+                uwriteln!(self.src.c_helpers, "return true;");
+            }
+
+            TypeDefKind::List(t) => {
+                uwriteln!(self.src.c_helpers, "if (a->len != b->len) return false;");
+                self.src
+                    .c_helpers("for (size_t i = 0; i < a->len; i++) {\n");
+                let eq = self.eq(t, "a->ptr[i]", "b->ptr[i]");
+                uwriteln!(self.src.c_helpers, "if (!({eq})) return false;");
+                self.src.c_helpers("}\n");
+                uwriteln!(self.src.c_helpers, "return true;");
+            }
+
+            TypeDefKind::Variant(v) => {
+                uwriteln!(self.src.c_helpers, "if (a->tag != b->tag) return false;");
+                if v.cases.iter().any(|case| case.ty.is_some()) {
+                    self.src.c_helpers("switch ((int32_t) a->tag) {\n");
+                    for (i, case) in v.cases.iter().enumerate() {
+                        if let Some(ty) = &case.ty {
+                            let case = to_c_ident(&case.name);
+                            let eq =
+                                self.eq(ty, &format!("a->val.{case}"), &format!("b->val.{case}"));
+                            uwriteln!(self.src.c_helpers, "case {i}: return {eq};");
+                        }
+                    }
+                    self.src.c_helpers("}\n");
+                }
+                uwriteln!(self.src.c_helpers, "return true;");
+            }
+
+            TypeDefKind::Option(t) => {
+                uwriteln!(
+                    self.src.c_helpers,
+                    "if (a->is_some != b->is_some) return false;"
+                );
+                let eq = self.eq(t, "a->val", "b->val");
+                uwriteln!(self.src.c_helpers, "return !a->is_some || ({eq});");
+            }
+
+            TypeDefKind::Result(r) => {
+                uwriteln!(
+                    self.src.c_helpers,
+                    "if (a->is_err != b->is_err) return false;"
+                );
+                if let Some(ok) = &r.ok {
+                    let eq = self.eq(ok, "a->val.ok", "b->val.ok");
+                    uwriteln!(self.src.c_helpers, "if (!a->is_err) return {eq};");
+                }
+                if let Some(err) = &r.err {
+                    let eq = self.eq(err, "a->val.err", "b->val.err");
+                    uwriteln!(self.src.c_helpers, "if (a->is_err) return {eq};");
+                }
+                uwriteln!(self.src.c_helpers, "return true;");
+            }
+
+            _ => unreachable!(),
+        }
+        self.src.c_helpers("}\n");
+        self.gen.eq_funcs.insert(name);
+    }
+
+    /// Returns the `*_eq` function of the type `id`, if it has one.
+    fn eq_func(&self, id: TypeId) -> Option<String> {
+        let name = self.gen.type_names.get(&id)?;
+        let prefix = name.strip_suffix("_t").unwrap();
+        self.gen
+            .eq_funcs
+            .contains(name)
+            .then(|| format!("{prefix}_eq"))
+    }
+
+    /// Returns an expression comparing the values `a` and `b` of type `ty`,
+    /// where both are lvalues.
+    fn eq(&self, ty: &Type, a: &str, b: &str) -> String {
+        match ty {
+            Type::Id(id) => {
+                if let Some(eq) = self.eq_func(*id) {
+                    return format!("{eq}(&{a}, &{b})");
+                }
+                match &self.resolve.types[*id].kind {
+                    TypeDefKind::Type(t) => self.eq(t, a, b),
+                    TypeDefKind::Handle(Handle::Own(_)) => {
+                        format!("{a}.__handle == {b}.__handle")
+                    }
+                    TypeDefKind::Handle(Handle::Borrow(resource)) => {
+                        match self.gen.resources[&dealias(self.resolve, *resource)].direction {
+                            Direction::Import => format!("{a}.__handle == {b}.__handle"),
+                            // Borrows of exported resources are pointers to
+                            // their representation.
+                            Direction::Export => format!("{a} == {b}"),
+                        }
+                    }
+                    _ => format!("{a} == {b}"),
+                }
+            }
+            Type::String => {
+                let snake = self.gen.world.to_snake_case();
+                format!("{snake}_string_eq(&{a}, &{b})")
+            }
+            Type::Bool
+            | Type::U8
+            | Type::S8
+            | Type::U16
+            | Type::S16
+            | Type::U32
+            | Type::S32
+            | Type::U64
+            | Type::S64
+            | Type::F32
+            | Type::F64
+            | Type::Char => format!("{a} == {b}"),
+        }
+    }
+
     fn c_func_name(&self, interface_id: Option<&WorldKey>, func: &Function) -> String {
         c_func_name(
             self.in_import,
@@ -1867,6 +2519,18 @@ impl InterfaceGenerator<'_> {
         let name = self.c_func_name(interface_name, func);
         self.gen.names.insert(&name).expect("duplicate symbols");
 
+        // Exports are implemented and only called by bindings, so only
+        // imports are marked as deprecated.
+        if self.in_import {
+            match deprecation(&func.docs) {
+                Some("") => self.src.h_fns("__attribute__((__deprecated__))\n"),
+                Some(note) => {
+                    uwriteln!(self.src.h_fns, "__attribute__((__deprecated__({note:?})))")
+                }
+                None => {}
+            }
+        }
+
         let start = self.src.h_fns.len();
         let mut result_rets = false;
         let mut result_rets_has_ok_type = false;
@@ -1980,6 +2644,16 @@ impl InterfaceGenerator<'_> {
     fn print_typedef_target(&mut self, id: TypeId) {
         let name = &self.gen.type_names[&id];
         self.src.h_defs(&name);
+        let deprecation = deprecation(&self.resolve.types[id].docs);
+        match deprecation {
+            Some("") => self.src.h_defs(" __attribute__((__deprecated__))"),
+            Some(note) => uwrite!(
+                self.src.h_defs,
+                " __attribute__((__deprecated__({note:?})))"
+            ),
+            None => {}
+        }
+        self.gen.needs_deprecation_pragmas |= deprecation.is_some();
         self.src.h_defs(";\n");
     }
 
@@ -1995,6 +2669,62 @@ impl InterfaceGenerator<'_> {
         self.print_typedef_target(id);
     }
 
+    fn print_layout_asserts(&mut self, id: TypeId) {
+        let sizes = &self.gen.sizes;
+        let ty = Type::Id(id);
+        let (size, align) = (sizes.size(&ty), sizes.align(&ty));
+        // Zero-sized types can't be expressed in standard C.
+        if size == 0 {
+            return;
+        }
+        let mut fields = Vec::new();
+        match &self.resolve.types[id].kind {
+            TypeDefKind::Record(r) => {
+                let offsets = sizes.field_offsets(r.fields.iter().map(|f| &f.ty));
+                for (field, (offset, _)) in r.fields.iter().zip(offsets) {
+                    fields.push((to_c_ident(&field.name), offset));
+                }
+            }
+            TypeDefKind::Tuple(t) => {
+                for (i, (offset, _)) in sizes.field_offsets(&t.types).into_iter().enumerate() {
+                    fields.push((format!("f{i}"), offset));
+                }
+            }
+            TypeDefKind::Variant(v) => {
+                fields.push(("tag".to_string(), 0));
+                if v.cases.iter().any(|c| c.ty.is_some()) {
+                    let cases = v.cases.iter().map(|c| c.ty.as_ref());
+                    fields.push(("val".to_string(), sizes.payload_offset(v.tag(), cases)));
+                }
+            }
+            TypeDefKind::Option(t) => {
+                fields.push(("is_some".to_string(), 0));
+                let offset = sizes.payload_offset(Int::U8, [None, Some(t)]);
+                fields.push(("val".to_string(), offset));
+            }
+            TypeDefKind::Result(r) => {
+                fields.push(("is_err".to_string(), 0));
+                if r.ok.is_some() || r.err.is_some() {
+                    let offset = sizes.payload_offset(Int::U8, [r.ok.as_ref(), r.err.as_ref()]);
+                    fields.push(("val".to_string(), offset));
+                }
+            }
+            TypeDefKind::List(_) => {
+                // The length follows the pointer, which is aligned to its size.
+                fields.push(("ptr".to_string(), 0));
+                fields.push(("len".to_string(), align));
+            }
+            _ => unreachable!(),
+        }
+        let name = self.gen.type_names[&id].clone();
+        let fields = fields
+            .iter()
+            .map(|(field, offset)| (field.as_str(), *offset))
+            .collect::<Vec<_>>();
+        let asserts = self.gen.layout_asserts(&name, size, align, &fields);
+        self.src.h_defs(&asserts);
+    }
+
     fn owner_namespace(&self, id: TypeId) -> String {
         owner_namespace(
             self.interface,
@@ -2352,6 +3082,17 @@ impl Bindgen for FunctionBindgen<'_, '_> {
                 results.push(format!("({op}).__handle"))
             }
 
+            Instruction::FutureLower { .. } | Instruction::StreamLower { .. } => {
+                let op = &operands[0];
+                results.push(format!("(int32_t) ({op})"))
+            }
+
+            Instruction::FutureLift { ty, .. } | Instruction::StreamLift { ty, .. } => {
+                let op = &operands[0];
+                let name = self.gen.gen.type_name(&Type::Id(*ty));
+                results.push(format!("({name}) ({op})"))
+            }
+
             Instruction::HandleLift { handle, ty, .. } => match handle {
                 Handle::Borrow(resource)
                     if matches!(
@@ -2995,11 +3736,13 @@ impl Bindgen for FunctionBindgen<'_, '_> {
             }
 
             Instruction::GuestDeallocate { .. } => {
-                uwriteln!(self.src, "free({});", operands[0]);
+                let free = self.gen.gen.alloc_fn("free");
+                uwriteln!(self.src, "{free}({});", operands[0]);
             }
             Instruction::GuestDeallocateString => {
+                let free = self.gen.gen.alloc_fn("free");
                 uwriteln!(self.src, "if (({}) > 0) {{", operands[1]);
-                uwriteln!(self.src, "free({});", operands[0]);
+                uwriteln!(self.src, "{free}({});", operands[0]);
                 uwriteln!(self.src, "}}");
             }
             Instruction::GuestDeallocateVariant { blocks } => {
@@ -3032,7 +3775,8 @@ impl Bindgen for FunctionBindgen<'_, '_> {
                 uwriteln!(self.src, "(void) base;");
                 uwrite!(self.src, "{body}");
                 uwriteln!(self.src, "}}");
-                uwriteln!(self.src, "free({ptr});");
+                let free = self.gen.gen.alloc_fn("free");
+                uwriteln!(self.src, "{free}({ptr});");
                 uwriteln!(self.src, "}}");
             }
 
@@ -3141,9 +3885,8 @@ pub fn is_arg_by_pointer(resolve: &Resolve, ty: &Type) -> bool {
             TypeDefKind::Enum(_) => false,
             TypeDefKind::Flags(_) => false,
             TypeDefKind::Handle(_) => false,
+            TypeDefKind::Future(_) | TypeDefKind::Stream(_) => false,
             TypeDefKind::Tuple(_) | TypeDefKind::Record(_) | TypeDefKind::List(_) => true,
-            TypeDefKind::Future(_) => todo!("is_arg_by_pointer for future"),
-            TypeDefKind::Stream(_) => todo!("is_arg_by_pointer for stream"),
             TypeDefKind::Resource => todo!("is_arg_by_pointer for resource"),
             TypeDefKind::Unknown => unreachable!(),
         },
@@ -3152,6 +3895,38 @@ pub fn is_arg_by_pointer(resolve: &Resolve, ty: &Type) -> bool {
     }
 }
 
+/// Returns whether values of `ty` own a handle to a resource.
+pub fn contains_own_handle(resolve: &Resolve, ty: &Type) -> bool {
+    let id = match ty {
+        Type::Id(id) => *id,
+        _ => return false,
+    };
+    match &resolve.types[id].kind {
+        TypeDefKind::Type(t) | TypeDefKind::Option(t) | TypeDefKind::List(t) => {
+            contains_own_handle(resolve, t)
+        }
+        TypeDefKind::Handle(Handle::Own(_)) => true,
+        TypeDefKind::Record(r) => r.fields.iter().any(|f| contains_own_handle(resolve, &f.ty)),
+        TypeDefKind::Tuple(t) => t.types.iter().any(|t| contains_own_handle(resolve, t)),
+        TypeDefKind::Variant(v) => v
+            .cases
+            .iter()
+            .filter_map(|c| c.ty.as_ref())
+            .any(|t| contains_own_handle(resolve, t)),
+        TypeDefKind::Result(r) => [&r.ok, &r.err]
+            .iter()
+            .filter_map(|t| t.as_ref())
+            .any(|t| contains_own_handle(resolve, t)),
+        TypeDefKind::Handle(Handle::Borrow(_))
+        | TypeDefKind::Resource
+        | TypeDefKind::Flags(_)
+        | TypeDefKind::Enum(_)
+        | TypeDefKind::Future(_)
+        | TypeDefKind::Stream(_) => false,
+        TypeDefKind::Unknown => unreachable!(),
+    }
+}
+
 pub fn to_c_ident(name: &str) -> String {
     match name {
         // Escape C and C++ keywords.
@@ -40,6 +40,26 @@ macro_rules! codegen_test {
                 },
                 verify,
             );
+            test_helpers::run_world_codegen_test(
+                "guest-c-compact-utf16",
+                $test.as_ref(),
+                |resolve, world, files| {
+                    let mut opts = wit_bindgen_c::Opts::default();
+                    opts.string_encoding = wit_component::StringEncoding::CompactUTF16;
+                    opts.build().generate(resolve, world, files).unwrap()
+                },
+                verify,
+            );
+            test_helpers::run_world_codegen_test(
+                "guest-c-allocator-macros",
+                $test.as_ref(),
+                |resolve, world, files| {
+                    let mut opts = wit_bindgen_c::Opts::default();
+                    opts.allocator_macros = true;
+                    opts.build().generate(resolve, world, files).unwrap()
+                },
+                verify,
+            );
         }
     };
 }
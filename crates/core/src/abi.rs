@@ -1,7 +1,7 @@
 pub use wit_parser::abi::{AbiVariant, WasmSignature, WasmType};
 use wit_parser::{
     Enum, Flags, FlagsRepr, Function, Handle, Int, Record, Resolve, Result_, Results, SizeAlign,
-    Tuple, Type, TypeDefKind, TypeId, Variant,
+    Stream, Tuple, Type, TypeDefKind, TypeId, Variant,
 };
 
 // Helper macro for defining instructions without having to have tons of
@@ -350,6 +350,35 @@ def_instruction! {
             ty: TypeId,
         } : [1] => [1],
 
+        /// Create an `i32` from a future.
+        ///
+        /// The future is transferred by its handle; the payload type is
+        /// provided so bindings can name the type of the value being lowered.
+        FutureLower {
+            payload: &'a Option<Type>,
+            ty: TypeId,
+        } : [1] => [1],
+
+        /// Create a future from an `i32`.
+        FutureLift {
+            payload: &'a Option<Type>,
+            ty: TypeId,
+        } : [1] => [1],
+
+        /// Create an `i32` from a stream.
+        ///
+        /// Like futures, streams are transferred by their handle.
+        StreamLower {
+            payload: &'a Stream,
+            ty: TypeId,
+        } : [1] => [1],
+
+        /// Create a stream from an `i32`.
+        StreamLift {
+            payload: &'a Stream,
+            ty: TypeId,
+        } : [1] => [1],
+
         /// Pops a tuple value off the stack, decomposes the tuple to all of
         /// its fields, and then pushes the fields onto the stack.
         TupleLower {
@@ -522,7 +551,7 @@ def_instruction! {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Bitcast {
     // Upcasts
     F32ToI32,
@@ -734,7 +763,7 @@ fn needs_post_return(resolve: &Resolve, ty: &Type) -> bool {
                 .filter_map(|t| t.as_ref())
                 .any(|t| needs_post_return(resolve, t)),
             TypeDefKind::Flags(_) | TypeDefKind::Enum(_) => false,
-            TypeDefKind::Future(_) | TypeDefKind::Stream(_) => unimplemented!(),
+            TypeDefKind::Future(_) | TypeDefKind::Stream(_) => false,
             TypeDefKind::Unknown => unreachable!(),
         },
 
@@ -1168,8 +1197,12 @@ impl<'a, B: Bindgen> Generator<'a, B> {
                         results: &results,
                     });
                 }
-                TypeDefKind::Future(_) => todo!("lower future"),
-                TypeDefKind::Stream(_) => todo!("lower stream"),
+                TypeDefKind::Future(payload) => {
+                    self.emit(&FutureLower { payload, ty: id });
+                }
+                TypeDefKind::Stream(payload) => {
+                    self.emit(&StreamLower { payload, ty: id });
+                }
                 TypeDefKind::Unknown => unreachable!(),
             },
         }
@@ -1353,8 +1386,12 @@ impl<'a, B: Bindgen> Generator<'a, B> {
                     self.emit(&ResultLift { result: r, ty: id });
                 }
 
-                TypeDefKind::Future(_) => todo!("lift future"),
-                TypeDefKind::Stream(_) => todo!("lift stream"),
+                TypeDefKind::Future(payload) => {
+                    self.emit(&FutureLift { payload, ty: id });
+                }
+                TypeDefKind::Stream(payload) => {
+                    self.emit(&StreamLift { payload, ty: id });
+                }
                 TypeDefKind::Unknown => unreachable!(),
             },
         }
@@ -1512,8 +1549,9 @@ impl<'a, B: Bindgen> Generator<'a, B> {
                     self.store_intrepr(offset, e.tag());
                 }
 
-                TypeDefKind::Future(_) => todo!("write future to memory"),
-                TypeDefKind::Stream(_) => todo!("write stream to memory"),
+                TypeDefKind::Future(_) | TypeDefKind::Stream(_) => {
+                    self.lower_and_emit(ty, addr, &I32Store { offset })
+                }
                 TypeDefKind::Unknown => unreachable!(),
             },
         }
@@ -1695,8 +1733,9 @@ impl<'a, B: Bindgen> Generator<'a, B> {
                     self.lift(ty);
                 }
 
-                TypeDefKind::Future(_) => todo!("read future from memory"),
-                TypeDefKind::Stream(_) => todo!("read stream from memory"),
+                TypeDefKind::Future(_) | TypeDefKind::Stream(_) => {
+                    self.emit_and_lift(ty, addr, &I32Load { offset })
+                }
                 TypeDefKind::Unknown => unreachable!(),
             },
         }
@@ -1866,8 +1905,9 @@ impl<'a, B: Bindgen> Generator<'a, B> {
 
                 TypeDefKind::Enum(_) => {}
 
-                TypeDefKind::Future(_) => todo!("read future from memory"),
-                TypeDefKind::Stream(_) => todo!("read stream from memory"),
+                // Futures and streams are plain handles with nothing to free
+                // in linear memory.
+                TypeDefKind::Future(_) | TypeDefKind::Stream(_) => {}
                 TypeDefKind::Unknown => unreachable!(),
             },
         }
@@ -1959,3 +1999,85 @@ fn cast(from: WasmType, to: WasmType) -> Bitcast {
 fn align_to(val: usize, align: usize) -> usize {
     (val + align - 1) & !(align - 1)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A `Bindgen` which only records the names of the instructions emitted.
+    struct Recorder {
+        sizes: SizeAlign,
+        insts: Vec<String>,
+    }
+
+    impl Bindgen for Recorder {
+        type Operand = ();
+
+        fn emit(
+            &mut self,
+            _resolve: &Resolve,
+            inst: &Instruction<'_>,
+            _operands: &mut Vec<()>,
+            results: &mut Vec<()>,
+        ) {
+            let name = format!("{inst:?}");
+            let name = name.split([' ', '(']).next().unwrap().to_string();
+            self.insts.push(name);
+            results.extend((0..inst.results_len()).map(|_| ()));
+        }
+
+        fn return_pointer(&mut self, _size: usize, _align: usize) {}
+
+        fn push_block(&mut self) {}
+
+        fn finish_block(&mut self, _operands: &mut Vec<()>) {}
+
+        fn sizes(&self) -> &SizeAlign {
+            &self.sizes
+        }
+
+        fn is_list_canonical(&self, _resolve: &Resolve, _element: &Type) -> bool {
+            false
+        }
+    }
+
+    fn record(variant: AbiVariant, lift_lower: LiftLower) -> Vec<String> {
+        let mut resolve = Resolve::default();
+        let pkgs = resolve
+            .push_str(
+                "test.wit",
+                r#"
+                    package a:b;
+
+                    interface x {
+                        f: func(a: future<u32>) -> stream<u8>;
+                    }
+                "#,
+            )
+            .unwrap();
+        let iface = resolve.packages[pkgs[0]].interfaces["x"];
+        let func = &resolve.interfaces[iface].functions["f"];
+        let mut sizes = SizeAlign::default();
+        sizes.fill(&resolve);
+        let mut recorder = Recorder {
+            sizes,
+            insts: Vec::new(),
+        };
+        call(&resolve, variant, lift_lower, func, &mut recorder);
+        recorder.insts
+    }
+
+    #[test]
+    fn future_and_stream_import() {
+        let insts = record(AbiVariant::GuestImport, LiftLower::LowerArgsLiftResults);
+        assert!(insts.contains(&"FutureLower".to_string()), "{insts:?}");
+        assert!(insts.contains(&"StreamLift".to_string()), "{insts:?}");
+    }
+
+    #[test]
+    fn future_and_stream_export() {
+        let insts = record(AbiVariant::GuestExport, LiftLower::LiftArgsLowerResults);
+        assert!(insts.contains(&"FutureLift".to_string()), "{insts:?}");
+        assert!(insts.contains(&"StreamLower".to_string()), "{insts:?}");
+    }
+}
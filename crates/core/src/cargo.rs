@@ -0,0 +1,612 @@
+//! Resolution of WIT dependencies from the metadata in a `Cargo.toml`, in the
+//! same format as `cargo-component`.
+//!
+//! ```toml
+//! [package.metadata.component.target]
+//! path = "wit"
+//! world = "my-world"
+//!
+//! [package.metadata.component.target.dependencies]
+//! "wasi:io" = "0.2"
+//! "my:local" = { path = "../local/wit" }
+//!
+//! [package.metadata.wit-bindgen]
+//! vendor-dir = "vendor/wit"
+//! ```
+//!
+//! The target package at `path`, which defaults to `wit`, is parsed along with
+//! every package it transitively depends on. Dependencies with a `path` are
+//! read from there and all others are searched for in the `deps` directory of
+//! the target package and then in the optional `vendor-dir`, the same way as
+//! entries of a `deps` directory are, with version requirements checked
+//! against the versions the WIT documents refer to.
+//!
+//! The digests of the packages used can be recorded in a `wit-bindgen.lock`
+//! file next to the `Cargo.toml`, with [`Lock::Update`], so that their
+//! contents can't silently change: resolving with [`Lock::Verify`] fails if
+//! they no longer match the lockfile.
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use wit_parser::{PackageId, PackageName, Resolve, UnresolvedPackageGroup};
+
+/// The name of the lockfile written next to a `Cargo.toml`.
+pub const LOCKFILE: &str = "wit-bindgen.lock";
+
+/// What [`push_manifest`] does with the lockfile next to a `Cargo.toml`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Lock {
+    /// The packages used must match the ones recorded in the lockfile, if
+    /// there is one, which is never written.
+    Verify,
+    /// The lockfile is written to record the packages used, replacing what it
+    /// recorded before.
+    Update,
+}
+
+/// The target package of a `Cargo.toml`, pushed into a [`Resolve`] by
+/// [`push_manifest`].
+pub struct CargoTarget {
+    /// The packages of the target, for use with [`Resolve::select_world`].
+    pub packages: Vec<PackageId>,
+    /// The world named by the metadata, if any.
+    pub world: Option<String>,
+    /// All files which were read, including the `Cargo.toml` and the
+    /// lockfile if there is one.
+    pub files: Vec<PathBuf>,
+}
+
+#[derive(Default, Deserialize)]
+struct Manifest {
+    #[serde(default)]
+    package: Package,
+}
+
+#[derive(Default, Deserialize)]
+struct Package {
+    #[serde(default)]
+    metadata: Metadata,
+}
+
+#[derive(Default, Deserialize)]
+struct Metadata {
+    component: Option<Component>,
+    #[serde(rename = "wit-bindgen", default)]
+    wit_bindgen: WitBindgen,
+}
+
+#[derive(Deserialize)]
+struct Component {
+    target: Option<Target>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Target {
+    /// A world in a registry package, such as `wasi:http/proxy@0.2.0`.
+    Registry(String),
+    Local(LocalTarget),
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct LocalTarget {
+    path: Option<PathBuf>,
+    world: Option<String>,
+    #[serde(default)]
+    dependencies: BTreeMap<String, Dependency>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Dependency {
+    Version(String),
+    Detailed {
+        version: Option<String>,
+        path: Option<PathBuf>,
+    },
+}
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct WitBindgen {
+    vendor_dir: Option<PathBuf>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Lockfile {
+    version: u32,
+    #[serde(default, rename = "package")]
+    packages: Vec<LockedPackage>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct LockedPackage {
+    name: String,
+    source: String,
+    digest: String,
+}
+
+/// A package which dependencies may be resolved to.
+struct Candidate {
+    path: PathBuf,
+    names: Vec<PackageName>,
+    group: Option<UnresolvedPackageGroup>,
+}
+
+/// Returns whether the `Cargo.toml` at `manifest` has a
+/// `[package.metadata.component.target]` table describing a local package,
+/// which [`push_manifest`] supports, rather than naming a registry package.
+pub fn has_local_target(manifest: &Path) -> Result<bool> {
+    Ok(matches!(
+        read_manifest(manifest)?
+            .package
+            .metadata
+            .component
+            .and_then(|c| c.target),
+        Some(Target::Local(_))
+    ))
+}
+
+/// Pushes the target package described by the `Cargo.toml` at `manifest`,
+/// along with its dependencies, into `resolve`, verifying or updating the
+/// lockfile next to it according to `lock`.
+///
+/// Returns an error if the manifest has no local target, if a dependency
+/// can't be found, or if the packages used don't match the lockfile.
+pub fn push_manifest(resolve: &mut Resolve, manifest: &Path, lock: Lock) -> Result<CargoTarget> {
+    let root = manifest.parent().unwrap_or(Path::new(""));
+    let metadata = read_manifest(manifest)?.package.metadata;
+    let target = match metadata.component.and_then(|c| c.target) {
+        Some(Target::Local(target)) => target,
+        Some(Target::Registry(name)) => bail!(
+            "the registry target `{name}` in {manifest:?} isn't supported, \
+             only local targets with a `path`"
+        ),
+        None => bail!("no `[package.metadata.component.target]` in {manifest:?}"),
+    };
+    let target_path = root.join(target.path.as_deref().unwrap_or(Path::new("wit")));
+    let main = UnresolvedPackageGroup::parse_path(&target_path)
+        .with_context(|| format!("failed to parse package: {}", target_path.display()))?;
+
+    let mut search_dirs = vec![target_path.join("deps")];
+    search_dirs.extend(metadata.wit_bindgen.vendor_dir.map(|dir| root.join(dir)));
+    let mut candidates = Vec::new();
+    for dir in search_dirs.iter() {
+        candidates.extend(read_candidates(dir)?);
+    }
+    // Packages in the search directories come first, followed by the
+    // dependencies with a `path`.
+    let searched = candidates.len();
+    let mut path_deps = HashMap::new();
+    for (name, dep) in target.dependencies.iter() {
+        if let Dependency::Detailed {
+            path: Some(path), ..
+        } = dep
+        {
+            let path = root.join(path);
+            let group = UnresolvedPackageGroup::parse_path(&path)
+                .with_context(|| format!("failed to parse package: {}", path.display()))?;
+            path_deps.insert(name.as_str(), candidates.len());
+            candidates.push(Candidate {
+                names: group.packages.iter().map(|p| p.name.clone()).collect(),
+                path,
+                group: Some(group),
+            });
+        }
+    }
+
+    // Resolve the packages referred to by the target, and then the packages
+    // those refer to, to candidates.
+    let provided = main
+        .packages
+        .iter()
+        .map(|p| p.name.clone())
+        .collect::<HashSet<_>>();
+    let mut worklist = main
+        .packages
+        .iter()
+        .flat_map(|p| p.foreign_deps.keys().cloned())
+        .collect::<Vec<_>>();
+    let mut seen = HashSet::new();
+    let mut used = Vec::new();
+    while let Some(name) = worklist.pop() {
+        if provided.contains(&name) || !seen.insert(name.clone()) {
+            continue;
+        }
+        let key = format!("{}:{}", name.namespace, name.name);
+        let dep = target.dependencies.get(&key);
+        let index = match path_deps.get(key.as_str()) {
+            Some(i) if candidates[*i].names.contains(&name) => *i,
+            Some(i) => bail!("package `{name}` not found at {:?}", candidates[*i].path),
+            None => candidates[..searched]
+                .iter()
+                .position(|c| c.names.contains(&name))
+                .with_context(|| {
+                    format!(
+                        "package `{name}` not found in {search_dirs:?}; \
+                         add it there or give it a `path` in \
+                         `[package.metadata.component.target.dependencies]`"
+                    )
+                })?,
+        };
+        let requirement = match dep {
+            Some(Dependency::Version(v)) => Some(v),
+            Some(Dependency::Detailed { version, .. }) => version.as_ref(),
+            None => None,
+        };
+        if let (Some(requirement), Some(version)) = (requirement, &name.version) {
+            let req = semver::VersionReq::parse(requirement)
+                .with_context(|| format!("invalid version requirement for `{key}`"))?;
+            if !req.matches(version) {
+                bail!("package `{name}` doesn't match the version requirement `{requirement}` in {manifest:?}");
+            }
+        }
+        if used.contains(&index) {
+            continue;
+        }
+        used.push(index);
+        let group = candidates[index].group.as_ref().unwrap();
+        worklist.extend(
+            group
+                .packages
+                .iter()
+                .flat_map(|p| p.foreign_deps.keys().cloned()),
+        );
+    }
+
+    // Push dependencies before the packages which refer to them.
+    let mut order = Vec::new();
+    let mut visiting = HashSet::new();
+    for i in used.iter() {
+        visit(*i, &candidates, &used, &mut visiting, &mut order)?;
+    }
+    let mut files = vec![manifest.to_path_buf()];
+    let mut locked = Vec::new();
+    for i in order {
+        let candidate = &mut candidates[i];
+        let group = candidate.group.take().unwrap();
+        let sources = group
+            .source_map
+            .source_files()
+            .map(|p| p.to_path_buf())
+            .collect::<Vec<_>>();
+        let mut names = candidate
+            .names
+            .iter()
+            .map(|n| n.to_string())
+            .collect::<Vec<_>>();
+        names.sort();
+        locked.push(LockedPackage {
+            name: names.join(", "),
+            source: relative(root, &candidate.path),
+            digest: digest(&candidate.path, &sources)?,
+        });
+        files.extend(sources);
+        resolve.push_group(group)?;
+    }
+    files.extend(main.source_map.source_files().map(|p| p.to_path_buf()));
+    let packages = resolve.push_group(main)?;
+
+    let lockfile = root.join(LOCKFILE);
+    let exists = match lock {
+        Lock::Verify => verify_lockfile(&lockfile, locked)?,
+        Lock::Update => {
+            write_lockfile(&lockfile, locked)?;
+            true
+        }
+    };
+    if exists {
+        files.push(lockfile);
+    }
+
+    Ok(CargoTarget {
+        packages,
+        world: target.world,
+        files,
+    })
+}
+
+fn read_manifest(manifest: &Path) -> Result<Manifest> {
+    let contents = std::fs::read_to_string(manifest)
+        .with_context(|| format!("failed to read {manifest:?}"))?;
+    toml::from_str(&contents).with_context(|| format!("failed to parse {manifest:?}"))
+}
+
+/// Parses every package in `dir`, which is laid out like a `deps` directory.
+fn read_candidates(dir: &Path) -> Result<Vec<Candidate>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut entries = dir
+        .read_dir()
+        .and_then(|i| i.collect::<std::io::Result<Vec<_>>>())
+        .with_context(|| format!("failed to read directory {dir:?}"))?;
+    entries.sort_by_key(|e| e.file_name());
+    let mut ret = Vec::new();
+    for entry in entries {
+        let path = entry.path();
+        // Other files are ignored, as in `deps` directories.
+        if !path.is_dir() && path.extension().and_then(|s| s.to_str()) != Some("wit") {
+            continue;
+        }
+        let group = UnresolvedPackageGroup::parse_path(&path)
+            .with_context(|| format!("failed to parse package: {}", path.display()))?;
+        ret.push(Candidate {
+            names: group.packages.iter().map(|p| p.name.clone()).collect(),
+            path,
+            group: Some(group),
+        });
+    }
+    Ok(ret)
+}
+
+/// Adds the candidate `i` to `order` after the used candidates it depends on.
+fn visit(
+    i: usize,
+    candidates: &[Candidate],
+    used: &[usize],
+    visiting: &mut HashSet<usize>,
+    order: &mut Vec<usize>,
+) -> Result<()> {
+    if order.contains(&i) {
+        return Ok(());
+    }
+    if !visiting.insert(i) {
+        bail!("dependency cycle involving {:?}", candidates[i].path);
+    }
+    let group = candidates[i].group.as_ref().unwrap();
+    for name in group.packages.iter().flat_map(|p| p.foreign_deps.keys()) {
+        if let Some(dep) = used
+            .iter()
+            .find(|j| **j != i && candidates[**j].names.contains(name))
+        {
+            visit(*dep, candidates, used, visiting, order)?;
+        }
+    }
+    visiting.remove(&i);
+    order.push(i);
+    Ok(())
+}
+
+/// Returns `path` relative to `root` with `/` separators, for the lockfile.
+fn relative(root: &Path, path: &Path) -> String {
+    let path = path.strip_prefix(root).unwrap_or(path);
+    path.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Returns the digest of the `sources` of the package at `path`.
+///
+/// Files are hashed in order of their path relative to the package, along
+/// with that path, so the digest doesn't depend on where the package is.
+fn digest(path: &Path, sources: &[PathBuf]) -> Result<String> {
+    let mut sources = sources
+        .iter()
+        .map(|file| (relative(path, file), file))
+        .collect::<Vec<_>>();
+    sources.sort();
+    let mut hasher = Sha256::new();
+    for (name, file) in sources {
+        let contents = std::fs::read(file).with_context(|| format!("failed to read {file:?}"))?;
+        hasher.update(name.as_bytes());
+        hasher.update([0]);
+        hasher.update((contents.len() as u64).to_le_bytes());
+        hasher.update(&contents);
+    }
+    let hash = hasher.finalize();
+    let mut ret = String::from("sha256:");
+    for byte in hash {
+        ret.push_str(&format!("{byte:02x}"));
+    }
+    Ok(ret)
+}
+
+/// Checks `packages` against the lockfile at `path`, returning whether
+/// there is one.
+fn verify_lockfile(path: &Path, mut packages: Vec<LockedPackage>) -> Result<bool> {
+    packages.sort_by(|a, b| a.name.cmp(&b.name));
+    let prev = match std::fs::read_to_string(path) {
+        Ok(contents) => toml::from_str::<Lockfile>(&contents)
+            .with_context(|| format!("failed to parse {path:?}"))?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e).with_context(|| format!("failed to read {path:?}")),
+    };
+    for package in packages.iter() {
+        let Some(locked) = prev.packages.iter().find(|p| p.name == package.name) else {
+            bail!(
+                "package `{}` at `{}` isn't recorded in {path:?}; \
+                 run `wit-bindgen` with `--update-lockfile` to add it",
+                package.name,
+                package.source
+            );
+        };
+        if locked.digest != package.digest {
+            bail!(
+                "the contents of package `{}` at `{}` don't match its digest in {path:?}; \
+                 run `wit-bindgen` with `--update-lockfile` if the change is intended",
+                package.name,
+                package.source
+            );
+        }
+        if locked.source != package.source {
+            bail!(
+                "package `{}` is resolved to `{}` rather than `{}` recorded in {path:?}; \
+                 run `wit-bindgen` with `--update-lockfile` if the change is intended",
+                package.name,
+                package.source,
+                locked.source
+            );
+        }
+    }
+    if let Some(unused) = prev
+        .packages
+        .iter()
+        .find(|p| !packages.iter().any(|q| q.name == p.name))
+    {
+        bail!(
+            "package `{}` recorded in {path:?} is no longer used; \
+             run `wit-bindgen` with `--update-lockfile` to remove it",
+            unused.name
+        );
+    }
+    Ok(true)
+}
+
+/// Writes the lockfile at `path` to record `packages`, unless it already does.
+fn write_lockfile(path: &Path, mut packages: Vec<LockedPackage>) -> Result<()> {
+    packages.sort_by(|a, b| a.name.cmp(&b.name));
+    let lockfile = Lockfile {
+        version: 1,
+        packages,
+    };
+    let contents = format!(
+        "# This file is automatically generated by wit-bindgen.\n\
+         # It is not intended for manual editing.\n\
+         {}",
+        toml::to_string(&lockfile)?
+    );
+    if std::fs::read_to_string(path).is_ok_and(|prev| prev == contents) {
+        return Ok(());
+    }
+    std::fs::write(path, contents).with_context(|| format!("failed to write {path:?}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(path: &Path, contents: &str) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, contents).unwrap();
+    }
+
+    #[test]
+    fn resolves_and_locks_dependencies() {
+        let root = std::env::temp_dir().join(format!("wit-bindgen-cargo-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let manifest = root.join("Cargo.toml");
+        write(
+            &manifest,
+            r#"
+                [package]
+                name = "component"
+
+                [package.metadata.component.target]
+                world = "w"
+
+                [package.metadata.component.target.dependencies]
+                "a:io" = "0.2"
+                "a:local" = { path = "local" }
+
+                [package.metadata.wit-bindgen]
+                vendor-dir = "vendor"
+            "#,
+        );
+        write(
+            &root.join("wit/world.wit"),
+            "package a:component; world w { import a:http/handler@0.2.1; import a:local/l; }",
+        );
+        write(
+            &root.join("wit/deps/http.wit"),
+            "package a:http@0.2.1; interface handler { use a:io/streams@0.2.0.{s}; }",
+        );
+        write(
+            &root.join("vendor/io/streams.wit"),
+            "package a:io@0.2.0; interface streams { type s = u32; }",
+        );
+        write(&root.join("vendor/unused.wit"), "package a:unused;");
+        write(
+            &root.join("local/l.wit"),
+            "package a:local; interface l { use a:io/streams@0.2.0.{s}; }",
+        );
+
+        assert!(has_local_target(&manifest).unwrap());
+        let mut resolve = Resolve::default();
+        let target = push_manifest(&mut resolve, &manifest, Lock::Verify).unwrap();
+        assert_eq!(target.world.as_deref(), Some("w"));
+        resolve.select_world(&target.packages, Some("w")).unwrap();
+        assert_eq!(resolve.packages.len(), 4);
+
+        // The lockfile is only written when asked to.
+        assert!(!root.join(LOCKFILE).exists());
+        assert!(!target.files.contains(&root.join(LOCKFILE)));
+        let target = push_manifest(&mut Resolve::default(), &manifest, Lock::Update).unwrap();
+        assert!(target.files.contains(&root.join(LOCKFILE)));
+
+        let lockfile = std::fs::read_to_string(root.join(LOCKFILE)).unwrap();
+        let lockfile = toml::from_str::<Lockfile>(&lockfile).unwrap();
+        let sources = lockfile
+            .packages
+            .iter()
+            .map(|p| (p.name.as_str(), p.source.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            sources,
+            [
+                ("a:http@0.2.1", "wit/deps/http.wit"),
+                ("a:io@0.2.0", "vendor/io"),
+                ("a:local", "local"),
+            ]
+        );
+
+        // Resolving again with the same contents succeeds, but changing a
+        // locked package is an error until the lockfile is updated.
+        push_manifest(&mut Resolve::default(), &manifest, Lock::Verify).unwrap();
+        write(
+            &root.join("vendor/io/streams.wit"),
+            "package a:io@0.2.0; interface streams { type s = u64; }",
+        );
+        let err = push_manifest(&mut Resolve::default(), &manifest, Lock::Verify)
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("don't match its digest"), "{err}");
+        push_manifest(&mut Resolve::default(), &manifest, Lock::Update).unwrap();
+        push_manifest(&mut Resolve::default(), &manifest, Lock::Verify).unwrap();
+
+        // Packages which aren't locked are an error as well.
+        write(
+            &root.join("wit/world.wit"),
+            "package a:component; world w { import a:http/handler@0.2.1; }",
+        );
+        let err = push_manifest(&mut Resolve::default(), &manifest, Lock::Verify)
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("no longer used"), "{err}");
+        std::fs::remove_file(root.join(LOCKFILE)).unwrap();
+
+        // Version requirements are checked against the versions referred to.
+        write(
+            &manifest,
+            r#"
+                [package.metadata.component.target.dependencies]
+                "a:io" = "0.3"
+                "a:local" = { path = "local" }
+
+                [package.metadata.wit-bindgen]
+                vendor-dir = "vendor"
+            "#,
+        );
+        let err = push_manifest(&mut Resolve::default(), &manifest, Lock::Verify)
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("version requirement"), "{err}");
+
+        // Registry targets aren't local.
+        write(
+            &manifest,
+            r#"
+                [package.metadata.component]
+                target = "a:http/proxy@0.2.1"
+            "#,
+        );
+        assert!(!has_local_target(&manifest).unwrap());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,1275 @@
+//! A reference interpreter for the canonical ABI.
+//!
+//! Language generators translate the instruction stream produced by
+//! [`abi::call`] into source code, which makes their ABI logic hard to test
+//! in isolation. The [`Interpreter`] here instead records that instruction
+//! stream and executes it directly against an in-memory linear memory. A
+//! function is called by lowering its arguments as a guest import, lifting
+//! them again as a guest export, and then doing the same for its results in
+//! the opposite direction. Comparing the values which come out with the
+//! values which went in gives a language-independent oracle for the
+//! instructions emitted by [`abi`].
+
+use crate::abi::{self, AbiVariant, Bindgen, Bitcast, Instruction, LiftLower, WasmType};
+use anyhow::{anyhow, bail, Result};
+use std::collections::BTreeMap;
+use wit_parser::*;
+
+/// A value manipulated by the interpreter.
+///
+/// The first few cases are core wasm values, which are what the lowered
+/// representation of a function consists of. The remaining cases are
+/// component model values, which are what arguments and results of
+/// [`Interpreter::call`] consist of. Component model `f32` and `f64` values
+/// share their representation with core wasm values.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    I32(i32),
+    I64(i64),
+    F32(f32),
+    F64(f64),
+
+    Bool(bool),
+    U8(u8),
+    S8(i8),
+    U16(u16),
+    S16(i16),
+    U32(u32),
+    S32(i32),
+    U64(u64),
+    S64(i64),
+    Char(char),
+    String(String),
+    List(Vec<Value>),
+    Record(Vec<Value>),
+    Tuple(Vec<Value>),
+    /// One entry for each flag, in declaration order.
+    Flags(Vec<bool>),
+    /// The index of the case along with its payload, if any.
+    Variant(u32, Option<Box<Value>>),
+    /// The index of the case.
+    Enum(u32),
+    Option(Option<Box<Value>>),
+    Result(Result<Option<Box<Value>>, Option<Box<Value>>>),
+    Handle(u32),
+    Future(u32),
+    Stream(u32),
+}
+
+/// A linear memory along with the allocations made within it.
+///
+/// Memory is never reused, so reads of freed memory are not detected, but
+/// frees of memory which isn't allocated, or was allocated with a different
+/// size or alignment, are reported as errors.
+#[derive(Debug, Default)]
+pub struct Memory {
+    bytes: Vec<u8>,
+    allocations: BTreeMap<u32, Allocation>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Allocation {
+    size: usize,
+    align: usize,
+    /// Whether this memory is owned by the callee it was passed to, as
+    /// opposed to being temporary space of the caller.
+    owned: bool,
+}
+
+impl Memory {
+    /// Allocates `size` bytes aligned to `align`.
+    ///
+    /// Owned allocations are expected to be freed by whoever receives them,
+    /// while other allocations model stack space or static return areas and
+    /// are never expected to be freed.
+    pub fn alloc(&mut self, size: usize, align: usize, owned: bool) -> u32 {
+        // Keep zero out of the address space and give every allocation at
+        // least one byte so each has a unique address.
+        let ptr = align_to(self.bytes.len().max(8), align.max(1));
+        self.bytes.resize(ptr + size.max(1), 0);
+        let ptr = u32::try_from(ptr).unwrap();
+        self.allocations
+            .insert(ptr, Allocation { size, align, owned });
+        ptr
+    }
+
+    /// Frees the allocation at `ptr`, which must have been allocated with
+    /// the same `size` and `align`.
+    pub fn free(&mut self, ptr: u32, size: usize, align: usize) -> Result<()> {
+        match self.allocations.remove(&ptr) {
+            Some(a) if a.size == size && a.align == align => Ok(()),
+            Some(a) => bail!(
+                "freeing {ptr:#x} with size {size} and alignment {align}, but it \
+                 was allocated with size {} and alignment {}",
+                a.size,
+                a.align
+            ),
+            None => bail!("freeing {ptr:#x} which is not allocated"),
+        }
+    }
+
+    /// Returns the number of owned allocations which haven't been freed yet.
+    pub fn live_allocations(&self) -> usize {
+        self.allocations.values().filter(|a| a.owned).count()
+    }
+
+    /// Returns the `len` bytes at `ptr`.
+    pub fn read(&self, ptr: u32, len: usize) -> Result<&[u8]> {
+        let start = ptr as usize;
+        self.bytes
+            .get(start..start + len)
+            .ok_or_else(|| anyhow!("out-of-bounds read of {len} bytes at {ptr:#x}"))
+    }
+
+    /// Writes `bytes` to `ptr`.
+    pub fn write(&mut self, ptr: u32, bytes: &[u8]) -> Result<()> {
+        let start = ptr as usize;
+        let len = bytes.len();
+        self.bytes
+            .get_mut(start..start + len)
+            .ok_or_else(|| anyhow!("out-of-bounds write of {len} bytes at {ptr:#x}"))?
+            .copy_from_slice(bytes);
+        Ok(())
+    }
+
+    fn read_array<const N: usize>(&self, ptr: u32) -> Result<[u8; N]> {
+        Ok(self.read(ptr, N)?.try_into().unwrap())
+    }
+}
+
+/// Executes functions through the canonical ABI.
+///
+/// See the [module documentation](self) for more information.
+pub struct Interpreter<'a> {
+    resolve: &'a Resolve,
+    sizes: SizeAlign,
+    memory: Memory,
+    canonical_lists: bool,
+}
+
+impl<'a> Interpreter<'a> {
+    pub fn new(resolve: &'a Resolve) -> Interpreter<'a> {
+        let mut sizes = SizeAlign::default();
+        sizes.fill(resolve);
+        Interpreter {
+            resolve,
+            sizes,
+            memory: Memory::default(),
+            canonical_lists: true,
+        }
+    }
+
+    /// Configures whether lists of integers and floats are lowered and lifted
+    /// with `ListCanon*` instructions, as most generators do, or element by
+    /// element with `ListLower`/`ListLift` like all other lists.
+    pub fn canonical_lists(&mut self, canonical: bool) -> &mut Self {
+        self.canonical_lists = canonical;
+        self
+    }
+
+    /// Returns the linear memory used by calls made so far.
+    pub fn memory(&self) -> &Memory {
+        &self.memory
+    }
+
+    /// Calls `func` with `args`, returning its results.
+    ///
+    /// The arguments are lowered as for a guest calling an import, and then
+    /// lifted again as for a guest export which is implemented by
+    /// `implementation`. The results returned by `implementation` travel the
+    /// same way back, after which the post-return function of the export, if
+    /// any, is executed.
+    pub fn call(
+        &mut self,
+        func: &Function,
+        args: Vec<Value>,
+        implementation: &mut dyn FnMut(Vec<Value>) -> Vec<Value>,
+    ) -> Result<Vec<Value>> {
+        let import = self.record(
+            AbiVariant::GuestImport,
+            LiftLower::LowerArgsLiftResults,
+            func,
+        );
+        let export = self.record(
+            AbiVariant::GuestExport,
+            LiftLower::LiftArgsLowerResults,
+            func,
+        );
+        let sig = self.resolve.wasm_signature(AbiVariant::GuestImport, func);
+        let (results_size, _) = self.sizes.params(func.results.iter_types());
+
+        let resolve = self.resolve;
+        let sizes = &self.sizes;
+        let mut export_results = None;
+        let results = import.run(sizes, &mut self.memory, args, &mut |memory, mut args| {
+            // Imports take a pointer to write their results to, while
+            // exports return a pointer to their results instead, so the
+            // results are copied between the two like a host would.
+            let retptr = if sig.retptr { args.pop() } else { None };
+            let results =
+                export.run(sizes, memory, args, &mut |_, args| Ok(implementation(args)))?;
+            export_results = Some(results.clone());
+            match retptr {
+                Some(dst) => {
+                    let [src] = &results[..] else {
+                        bail!("expected a single return pointer, got {results:?}")
+                    };
+                    let bytes = memory.read(ptr(src)?, results_size)?.to_vec();
+                    memory.write(ptr(&dst)?, &bytes)?;
+                    Ok(Vec::new())
+                }
+                None => Ok(results),
+            }
+        })?;
+
+        if abi::guest_export_needs_post_return(resolve, func) {
+            let mut recorder = Recorder::new(sizes, self.canonical_lists);
+            abi::post_return(resolve, func, &mut recorder);
+            recorder.finish().run(
+                sizes,
+                &mut self.memory,
+                export_results.unwrap(),
+                &mut |_, _| bail!("post-return functions don't make calls"),
+            )?;
+        }
+        Ok(results)
+    }
+
+    /// Passes `value` of type `ty` through [`Interpreter::call`] as both the
+    /// argument and the result of a function, returning the value which comes
+    /// out the other end.
+    ///
+    /// A correct ABI implementation always returns a value equal to `value`.
+    pub fn round_trip(&mut self, ty: &Type, value: Value) -> Result<Value> {
+        let func = Function {
+            name: "round-trip".to_string(),
+            kind: FunctionKind::Freestanding,
+            params: vec![("value".to_string(), *ty)],
+            results: Results::Anon(*ty),
+            docs: Docs::default(),
+            stability: Stability::Unknown,
+        };
+        let mut results = self.call(&func, vec![value], &mut |args| args)?;
+        Ok(results.remove(0))
+    }
+
+    fn record(&self, variant: AbiVariant, lift_lower: LiftLower, func: &Function) -> Block {
+        let mut recorder = Recorder::new(&self.sizes, self.canonical_lists);
+        abi::call(self.resolve, variant, lift_lower, func, &mut recorder);
+        recorder.finish()
+    }
+}
+
+/// A `Bindgen` which records instructions into blocks of `Node`s to be
+/// executed later.
+///
+/// Instructions can't be executed as they're emitted since the blocks of
+/// variants are emitted for every case, yet only one of them is to be
+/// executed. Operands are registers which are assigned when a node is
+/// executed.
+struct Recorder<'a> {
+    sizes: &'a SizeAlign,
+    canonical_lists: bool,
+    registers: usize,
+    blocks: Vec<Vec<Node>>,
+    finished: Vec<Block>,
+}
+
+struct Block {
+    nodes: Vec<Node>,
+    results: Vec<usize>,
+}
+
+struct Node {
+    op: Op,
+    operands: Vec<usize>,
+    results: Vec<usize>,
+}
+
+/// An owned version of the parts of `Instruction` needed for execution.
+enum Op {
+    GetArg(usize),
+    I32Const(i32),
+    Bitcasts(Vec<Bitcast>),
+    ConstZero(Vec<WasmType>),
+    Load(Load, i32),
+    Store(Store, i32),
+    /// Lowers a primitive value of the type, or a handle, to a core value.
+    Lower(Type),
+    /// Lifts a primitive value of the type from a core value.
+    Lift(Type),
+    ListCanonLower(Type, bool),
+    ListCanonLift(Type),
+    StringLower(bool),
+    StringLift,
+    ListLower(Type, bool, Block),
+    ListLift(Type, Block),
+    IterElem,
+    IterBasePointer,
+    Unpack,
+    RecordLift,
+    TupleLift,
+    HandleLift,
+    FutureLift,
+    StreamLift,
+    FlagsLower(FlagsRepr),
+    FlagsLift(usize),
+    VariantPayloadName,
+    VariantLower(Vec<Block>),
+    VariantLift(Kind, Vec<Block>),
+    EnumLift(usize),
+    CallWasm,
+    CallInterface,
+    Return,
+    ReturnPointer(usize, usize),
+    Malloc(usize, usize),
+    GuestDeallocate(usize, usize),
+    GuestDeallocateString,
+    GuestDeallocateList(Type, Block),
+    GuestDeallocateVariant(Vec<Block>),
+}
+
+#[derive(Clone, Copy)]
+enum Load {
+    I32,
+    I32_8U,
+    I32_8S,
+    I32_16U,
+    I32_16S,
+    I64,
+    F32,
+    F64,
+}
+
+#[derive(Clone, Copy)]
+enum Store {
+    I32,
+    I32_8,
+    I32_16,
+    I64,
+    F32,
+    F64,
+}
+
+/// The shape of the value produced by a `VariantLift`.
+#[derive(Clone, Copy)]
+enum Kind {
+    Variant,
+    Option,
+    Result,
+}
+
+impl<'a> Recorder<'a> {
+    fn new(sizes: &'a SizeAlign, canonical_lists: bool) -> Recorder<'a> {
+        Recorder {
+            sizes,
+            canonical_lists,
+            registers: 0,
+            blocks: vec![Vec::new()],
+            finished: Vec::new(),
+        }
+    }
+
+    fn finish(mut self) -> Block {
+        assert_eq!(self.blocks.len(), 1);
+        assert!(self.finished.is_empty());
+        Block {
+            nodes: self.blocks.pop().unwrap(),
+            results: Vec::new(),
+        }
+    }
+
+    fn register(&mut self) -> usize {
+        self.registers += 1;
+        self.registers - 1
+    }
+
+    fn block(&mut self) -> Block {
+        self.finished.pop().unwrap()
+    }
+
+    fn blocks(&mut self, n: usize) -> Vec<Block> {
+        self.finished.split_off(self.finished.len() - n)
+    }
+
+    fn push(&mut self, op: Op, operands: Vec<usize>, results: usize) -> Vec<usize> {
+        let results = (0..results).map(|_| self.register()).collect::<Vec<_>>();
+        self.blocks.last_mut().unwrap().push(Node {
+            op,
+            operands,
+            results: results.clone(),
+        });
+        results
+    }
+}
+
+impl Bindgen for Recorder<'_> {
+    type Operand = usize;
+
+    fn emit(
+        &mut self,
+        _resolve: &Resolve,
+        inst: &Instruction<'_>,
+        operands: &mut Vec<usize>,
+        results: &mut Vec<usize>,
+    ) {
+        use Instruction as I;
+
+        let op = match inst {
+            I::GetArg { nth } => Op::GetArg(*nth),
+            I::I32Const { val } => Op::I32Const(*val),
+            I::Bitcasts { casts } => Op::Bitcasts(casts.to_vec()),
+            I::ConstZero { tys } => Op::ConstZero(tys.to_vec()),
+
+            I::I32Load { offset } | I::PointerLoad { offset } | I::LengthLoad { offset } => {
+                Op::Load(Load::I32, *offset)
+            }
+            I::I32Load8U { offset } => Op::Load(Load::I32_8U, *offset),
+            I::I32Load8S { offset } => Op::Load(Load::I32_8S, *offset),
+            I::I32Load16U { offset } => Op::Load(Load::I32_16U, *offset),
+            I::I32Load16S { offset } => Op::Load(Load::I32_16S, *offset),
+            I::I64Load { offset } => Op::Load(Load::I64, *offset),
+            I::F32Load { offset } => Op::Load(Load::F32, *offset),
+            I::F64Load { offset } => Op::Load(Load::F64, *offset),
+
+            I::I32Store { offset } | I::PointerStore { offset } | I::LengthStore { offset } => {
+                Op::Store(Store::I32, *offset)
+            }
+            I::I32Store8 { offset } => Op::Store(Store::I32_8, *offset),
+            I::I32Store16 { offset } => Op::Store(Store::I32_16, *offset),
+            I::I64Store { offset } => Op::Store(Store::I64, *offset),
+            I::F32Store { offset } => Op::Store(Store::F32, *offset),
+            I::F64Store { offset } => Op::Store(Store::F64, *offset),
+
+            I::I32FromChar => Op::Lower(Type::Char),
+            I::I64FromU64 => Op::Lower(Type::U64),
+            I::I64FromS64 => Op::Lower(Type::S64),
+            I::I32FromU32 => Op::Lower(Type::U32),
+            I::I32FromS32 => Op::Lower(Type::S32),
+            I::I32FromU16 => Op::Lower(Type::U16),
+            I::I32FromS16 => Op::Lower(Type::S16),
+            I::I32FromU8 => Op::Lower(Type::U8),
+            I::I32FromS8 => Op::Lower(Type::S8),
+            I::CoreF32FromF32 => Op::Lower(Type::F32),
+            I::CoreF64FromF64 => Op::Lower(Type::F64),
+            I::I32FromBool => Op::Lower(Type::Bool),
+            I::EnumLower { ty, .. }
+            | I::HandleLower { ty, .. }
+            | I::FutureLower { ty, .. }
+            | I::StreamLower { ty, .. } => Op::Lower(Type::Id(*ty)),
+
+            I::S8FromI32 => Op::Lift(Type::S8),
+            I::U8FromI32 => Op::Lift(Type::U8),
+            I::S16FromI32 => Op::Lift(Type::S16),
+            I::U16FromI32 => Op::Lift(Type::U16),
+            I::S32FromI32 => Op::Lift(Type::S32),
+            I::U32FromI32 => Op::Lift(Type::U32),
+            I::S64FromI64 => Op::Lift(Type::S64),
+            I::U64FromI64 => Op::Lift(Type::U64),
+            I::CharFromI32 => Op::Lift(Type::Char),
+            I::F32FromCoreF32 => Op::Lift(Type::F32),
+            I::F64FromCoreF64 => Op::Lift(Type::F64),
+            I::BoolFromI32 => Op::Lift(Type::Bool),
+
+            I::ListCanonLower { element, realloc } => {
+                Op::ListCanonLower(**element, realloc.is_some())
+            }
+            I::StringLower { realloc } => Op::StringLower(realloc.is_some()),
+            I::ListLower { element, realloc } => {
+                let block = self.block();
+                Op::ListLower(**element, realloc.is_some(), block)
+            }
+            I::ListCanonLift { element, .. } => Op::ListCanonLift(**element),
+            I::StringLift => Op::StringLift,
+            I::ListLift { element, .. } => {
+                let block = self.block();
+                Op::ListLift(**element, block)
+            }
+            I::IterElem { .. } => Op::IterElem,
+            I::IterBasePointer => Op::IterBasePointer,
+
+            I::RecordLower { .. } | I::TupleLower { .. } => Op::Unpack,
+            I::RecordLift { .. } => Op::RecordLift,
+            I::TupleLift { .. } => Op::TupleLift,
+            I::HandleLift { .. } => Op::HandleLift,
+            I::FutureLift { .. } => Op::FutureLift,
+            I::StreamLift { .. } => Op::StreamLift,
+            I::FlagsLower { flags, .. } => Op::FlagsLower(flags.repr()),
+            I::FlagsLift { flags, .. } => Op::FlagsLift(flags.flags.len()),
+
+            I::VariantPayloadName => Op::VariantPayloadName,
+            I::VariantLower { variant, .. } => Op::VariantLower(self.blocks(variant.cases.len())),
+            I::OptionLower { .. } | I::ResultLower { .. } => Op::VariantLower(self.blocks(2)),
+            I::VariantLift { variant, .. } => {
+                Op::VariantLift(Kind::Variant, self.blocks(variant.cases.len()))
+            }
+            I::OptionLift { .. } => Op::VariantLift(Kind::Option, self.blocks(2)),
+            I::ResultLift { .. } => Op::VariantLift(Kind::Result, self.blocks(2)),
+            I::EnumLift { enum_, .. } => Op::EnumLift(enum_.cases.len()),
+
+            I::CallWasm { .. } => Op::CallWasm,
+            I::CallInterface { .. } => Op::CallInterface,
+            I::Return { .. } => Op::Return,
+            I::Malloc { size, align, .. } => Op::Malloc(*size, *align),
+            I::GuestDeallocate { size, align } => Op::GuestDeallocate(*size, *align),
+            I::GuestDeallocateString => Op::GuestDeallocateString,
+            I::GuestDeallocateList { element } => {
+                let block = self.block();
+                Op::GuestDeallocateList(**element, block)
+            }
+            I::GuestDeallocateVariant { blocks } => {
+                Op::GuestDeallocateVariant(self.blocks(*blocks))
+            }
+        };
+        results.extend(self.push(op, operands.clone(), inst.results_len()));
+    }
+
+    fn return_pointer(&mut self, size: usize, align: usize) -> usize {
+        self.push(Op::ReturnPointer(size, align), Vec::new(), 1)[0]
+    }
+
+    fn push_block(&mut self) {
+        self.blocks.push(Vec::new());
+    }
+
+    fn finish_block(&mut self, operands: &mut Vec<usize>) {
+        let nodes = self.blocks.pop().unwrap();
+        self.finished.push(Block {
+            nodes,
+            results: operands.clone(),
+        });
+    }
+
+    fn sizes(&self) -> &SizeAlign {
+        self.sizes
+    }
+
+    fn is_list_canonical(&self, _resolve: &Resolve, element: &Type) -> bool {
+        self.canonical_lists
+            && matches!(
+                element,
+                Type::U8
+                    | Type::S8
+                    | Type::U16
+                    | Type::S16
+                    | Type::U32
+                    | Type::S32
+                    | Type::U64
+                    | Type::S64
+                    | Type::F32
+                    | Type::F64
+            )
+    }
+}
+
+type Callee<'a> = dyn FnMut(&mut Memory, Vec<Value>) -> Result<Vec<Value>> + 'a;
+
+/// State while executing a recorded function.
+struct Exec<'a, 'b> {
+    sizes: &'a SizeAlign,
+    memory: &'b mut Memory,
+    args: Vec<Value>,
+    registers: Vec<Option<Value>>,
+    callee: &'b mut Callee<'a>,
+    returned: Option<Vec<Value>>,
+}
+
+/// Values bound within the execution of a single block.
+#[derive(Default)]
+struct Frame {
+    payload: Option<Value>,
+    elem: Option<Value>,
+    base: Option<u32>,
+}
+
+impl Block {
+    /// Executes this block as the body of a function, returning the operands
+    /// of its `Return` instruction.
+    ///
+    /// `CallWasm` and `CallInterface` instructions are forwarded to `callee`.
+    fn run<'a>(
+        &self,
+        sizes: &'a SizeAlign,
+        memory: &mut Memory,
+        args: Vec<Value>,
+        callee: &mut Callee<'a>,
+    ) -> Result<Vec<Value>> {
+        let mut exec = Exec {
+            sizes,
+            memory,
+            args,
+            registers: Vec::new(),
+            callee,
+            returned: None,
+        };
+        exec.block(self, Frame::default())?;
+        exec.returned
+            .ok_or_else(|| anyhow!("function did not return"))
+    }
+}
+
+impl Exec<'_, '_> {
+    fn block(&mut self, block: &Block, frame: Frame) -> Result<Vec<Value>> {
+        for node in block.nodes.iter() {
+            let operands = node
+                .operands
+                .iter()
+                .map(|r| self.get(*r))
+                .collect::<Result<Vec<_>>>()?;
+            let results = match &node.op {
+                // These may produce nothing, for example the payload of a
+                // case without one, so they assign registers directly.
+                Op::VariantPayloadName => {
+                    self.set(node.results[0], frame.payload.clone());
+                    continue;
+                }
+                Op::IterElem => {
+                    self.set(node.results[0], frame.elem.clone());
+                    continue;
+                }
+                op => self.op(op, operands, &frame)?,
+            };
+            assert_eq!(results.len(), node.results.len());
+            for (r, value) in node.results.iter().zip(results) {
+                self.set(*r, Some(value));
+            }
+        }
+        block.results.iter().map(|r| self.get(*r)).collect()
+    }
+
+    fn get(&self, register: usize) -> Result<Value> {
+        self.registers
+            .get(register)
+            .cloned()
+            .flatten()
+            .ok_or_else(|| anyhow!("register {register} used before being assigned"))
+    }
+
+    fn set(&mut self, register: usize, value: Option<Value>) {
+        if self.registers.len() <= register {
+            self.registers.resize(register + 1, None);
+        }
+        self.registers[register] = value;
+    }
+
+    fn op(&mut self, op: &Op, mut operands: Vec<Value>, frame: &Frame) -> Result<Vec<Value>> {
+        Ok(match op {
+            Op::GetArg(nth) => match self.args.get(*nth) {
+                Some(arg) => vec![arg.clone()],
+                None => bail!("missing argument {nth}"),
+            },
+            Op::I32Const(val) => vec![Value::I32(*val)],
+            Op::Bitcasts(casts) => casts
+                .iter()
+                .zip(operands)
+                .map(|(cast, value)| bitcast(cast, value))
+                .collect::<Result<_>>()?,
+            Op::ConstZero(tys) => tys
+                .iter()
+                .map(|ty| match ty {
+                    WasmType::I32 | WasmType::Pointer | WasmType::Length => Value::I32(0),
+                    WasmType::I64 | WasmType::PointerOrI64 => Value::I64(0),
+                    WasmType::F32 => Value::F32(0.0),
+                    WasmType::F64 => Value::F64(0.0),
+                })
+                .collect(),
+
+            Op::Load(load, offset) => {
+                let addr = address(&operands[0], *offset)?;
+                let m = &self.memory;
+                vec![match load {
+                    Load::I32 => Value::I32(i32::from_le_bytes(m.read_array(addr)?)),
+                    Load::I32_8U => Value::I32(u8::from_le_bytes(m.read_array(addr)?).into()),
+                    Load::I32_8S => Value::I32(i8::from_le_bytes(m.read_array(addr)?).into()),
+                    Load::I32_16U => Value::I32(u16::from_le_bytes(m.read_array(addr)?).into()),
+                    Load::I32_16S => Value::I32(i16::from_le_bytes(m.read_array(addr)?).into()),
+                    Load::I64 => Value::I64(i64::from_le_bytes(m.read_array(addr)?)),
+                    Load::F32 => Value::F32(f32::from_le_bytes(m.read_array(addr)?)),
+                    Load::F64 => Value::F64(f64::from_le_bytes(m.read_array(addr)?)),
+                }]
+            }
+            Op::Store(store, offset) => {
+                let addr = address(&operands[1], *offset)?;
+                let bytes = match (store, &operands[0]) {
+                    (Store::I32, Value::I32(v)) => v.to_le_bytes().to_vec(),
+                    (Store::I32_8, Value::I32(v)) => (*v as u8).to_le_bytes().to_vec(),
+                    (Store::I32_16, Value::I32(v)) => (*v as u16).to_le_bytes().to_vec(),
+                    (Store::I64, Value::I64(v)) => v.to_le_bytes().to_vec(),
+                    (Store::F32, Value::F32(v)) => v.to_le_bytes().to_vec(),
+                    (Store::F64, Value::F64(v)) => v.to_le_bytes().to_vec(),
+                    (_, value) => bail!("cannot store {value:?}"),
+                };
+                self.memory.write(addr, &bytes)?;
+                Vec::new()
+            }
+
+            Op::Lower(ty) => vec![lower(ty, &operands[0])?],
+            Op::Lift(ty) => vec![lift(ty, &operands[0])?],
+
+            Op::ListCanonLower(element, owned) => {
+                let Value::List(values) = &operands[0] else {
+                    bail!("expected a list, got {:?}", operands[0])
+                };
+                let size = self.sizes.size(element);
+                let ptr = self
+                    .memory
+                    .alloc(values.len() * size, self.sizes.align(element), *owned);
+                for (i, value) in values.iter().enumerate() {
+                    let bytes = canonical_bytes(element, value)?;
+                    self.memory.write(ptr + (i * size) as u32, &bytes)?;
+                }
+                vec![Value::I32(ptr as i32), Value::I32(values.len() as i32)]
+            }
+            Op::ListCanonLift(element) => {
+                let (ptr, len) = (ptr(&operands[0])?, ptr(&operands[1])? as usize);
+                let size = self.sizes.size(element);
+                let bytes = self.memory.read(ptr, len * size)?;
+                let values = bytes
+                    .chunks(size.max(1))
+                    .take(len)
+                    .map(|chunk| canonical_value(element, chunk))
+                    .collect();
+                vec![Value::List(values)]
+            }
+            Op::StringLower(owned) => {
+                let Value::String(s) = &operands[0] else {
+                    bail!("expected a string, got {:?}", operands[0])
+                };
+                let ptr = self.memory.alloc(s.len(), 1, *owned);
+                self.memory.write(ptr, s.as_bytes())?;
+                vec![Value::I32(ptr as i32), Value::I32(s.len() as i32)]
+            }
+            Op::StringLift => {
+                let (ptr, len) = (ptr(&operands[0])?, ptr(&operands[1])? as usize);
+                let bytes = self.memory.read(ptr, len)?.to_vec();
+                vec![Value::String(String::from_utf8(bytes)?)]
+            }
+            Op::ListLower(element, owned, block) => {
+                let Value::List(values) = operands.remove(0) else {
+                    bail!("expected a list")
+                };
+                let size = self.sizes.size(element);
+                let len = values.len();
+                let ptr = self
+                    .memory
+                    .alloc(len * size, self.sizes.align(element), *owned);
+                for (i, value) in values.into_iter().enumerate() {
+                    let frame = Frame {
+                        elem: Some(value),
+                        base: Some(ptr + (i * size) as u32),
+                        ..Frame::default()
+                    };
+                    self.block(block, frame)?;
+                }
+                vec![Value::I32(ptr as i32), Value::I32(len as i32)]
+            }
+            Op::ListLift(element, block) => {
+                let (ptr, len) = (ptr(&operands[0])?, ptr(&operands[1])? as usize);
+                let size = self.sizes.size(element);
+                let mut values = Vec::with_capacity(len);
+                for i in 0..len {
+                    let frame = Frame {
+                        base: Some(ptr + (i * size) as u32),
+                        ..Frame::default()
+                    };
+                    values.extend(self.block(block, frame)?);
+                }
+                vec![Value::List(values)]
+            }
+            Op::IterBasePointer => match frame.base {
+                Some(base) => vec![Value::I32(base as i32)],
+                None => bail!("`IterBasePointer` used outside of a list"),
+            },
+
+            Op::Unpack => match operands.remove(0) {
+                Value::Record(fields) | Value::Tuple(fields) => fields,
+                value => bail!("expected a record or tuple, got {value:?}"),
+            },
+            Op::RecordLift => vec![Value::Record(operands)],
+            Op::TupleLift => vec![Value::Tuple(operands)],
+            Op::HandleLift => vec![Value::Handle(ptr(&operands[0])?)],
+            Op::FutureLift => vec![Value::Future(ptr(&operands[0])?)],
+            Op::StreamLift => vec![Value::Stream(ptr(&operands[0])?)],
+
+            Op::FlagsLower(repr) => {
+                let Value::Flags(flags) = &operands[0] else {
+                    bail!("expected flags, got {:?}", operands[0])
+                };
+                let mut words = vec![0u32; repr.count()];
+                for (i, _) in flags.iter().enumerate().filter(|(_, set)| **set) {
+                    words[i / 32] |= 1 << (i % 32);
+                }
+                words.into_iter().map(|w| Value::I32(w as i32)).collect()
+            }
+            Op::FlagsLift(n) => {
+                let words = operands.iter().map(ptr).collect::<Result<Vec<_>>>()?;
+                vec![Value::Flags(
+                    (0..*n)
+                        .map(|i| words[i / 32] & (1 << (i % 32)) != 0)
+                        .collect(),
+                )]
+            }
+
+            Op::VariantLower(blocks) => {
+                let (discriminant, payload) = match operands.remove(0) {
+                    Value::Variant(i, payload) => (i as usize, payload),
+                    Value::Option(None) => (0, None),
+                    Value::Option(Some(payload)) => (1, Some(payload)),
+                    Value::Result(Ok(payload)) => (0, payload),
+                    Value::Result(Err(payload)) => (1, payload),
+                    value => bail!("expected a variant, got {value:?}"),
+                };
+                let Some(block) = blocks.get(discriminant) else {
+                    bail!("invalid discriminant {discriminant}")
+                };
+                let frame = Frame {
+                    payload: payload.map(|p| *p),
+                    ..Frame::default()
+                };
+                self.block(block, frame)?
+            }
+            Op::VariantLift(kind, blocks) => {
+                let discriminant = discriminant(&operands[0])?;
+                let Some(block) = blocks.get(discriminant) else {
+                    bail!("invalid discriminant {discriminant}")
+                };
+                let payload = self.block(block, Frame::default())?.pop().map(Box::new);
+                vec![match (kind, discriminant) {
+                    (Kind::Variant, i) => Value::Variant(i as u32, payload),
+                    (Kind::Option, 0) => Value::Option(None),
+                    (Kind::Option, _) => Value::Option(payload),
+                    (Kind::Result, 0) => Value::Result(Ok(payload)),
+                    (Kind::Result, _) => Value::Result(Err(payload)),
+                }]
+            }
+            Op::EnumLift(n) => {
+                let discriminant = discriminant(&operands[0])?;
+                if discriminant >= *n {
+                    bail!("invalid discriminant {discriminant}");
+                }
+                vec![Value::Enum(discriminant as u32)]
+            }
+
+            Op::CallWasm | Op::CallInterface => (self.callee)(self.memory, operands)?,
+            Op::Return => {
+                self.returned = Some(operands);
+                Vec::new()
+            }
+            Op::ReturnPointer(size, align) => {
+                vec![Value::I32(self.memory.alloc(*size, *align, false) as i32)]
+            }
+            Op::Malloc(size, align) => {
+                vec![Value::I32(self.memory.alloc(*size, *align, true) as i32)]
+            }
+            Op::GuestDeallocate(size, align) => {
+                self.memory.free(ptr(&operands[0])?, *size, *align)?;
+                Vec::new()
+            }
+            Op::GuestDeallocateString => {
+                let (ptr, len) = (ptr(&operands[0])?, ptr(&operands[1])? as usize);
+                self.memory.free(ptr, len, 1)?;
+                Vec::new()
+            }
+            Op::GuestDeallocateList(element, block) => {
+                let (ptr, len) = (ptr(&operands[0])?, ptr(&operands[1])? as usize);
+                let size = self.sizes.size(element);
+                for i in 0..len {
+                    let frame = Frame {
+                        base: Some(ptr + (i * size) as u32),
+                        ..Frame::default()
+                    };
+                    self.block(block, frame)?;
+                }
+                self.memory
+                    .free(ptr, len * size, self.sizes.align(element))?;
+                Vec::new()
+            }
+            Op::GuestDeallocateVariant(blocks) => {
+                let discriminant = discriminant(&operands[0])?;
+                let Some(block) = blocks.get(discriminant) else {
+                    bail!("invalid discriminant {discriminant}")
+                };
+                self.block(block, Frame::default())?;
+                Vec::new()
+            }
+            Op::VariantPayloadName | Op::IterElem => unreachable!(),
+        })
+    }
+}
+
+fn ptr(value: &Value) -> Result<u32> {
+    match value {
+        Value::I32(v) => Ok(*v as u32),
+        value => bail!("expected an i32, got {value:?}"),
+    }
+}
+
+fn address(value: &Value, offset: i32) -> Result<u32> {
+    Ok(ptr(value)?.wrapping_add(offset as u32))
+}
+
+fn discriminant(value: &Value) -> Result<usize> {
+    match value {
+        Value::I32(v) => Ok(*v as u32 as usize),
+        Value::I64(v) => Ok(*v as u64 as usize),
+        value => bail!("expected a discriminant, got {value:?}"),
+    }
+}
+
+fn bitcast(cast: &Bitcast, value: Value) -> Result<Value> {
+    use Value::*;
+
+    Ok(match (cast, value) {
+        (Bitcast::None, value) => value,
+        (Bitcast::F32ToI32, F32(v)) => I32(v.to_bits() as i32),
+        (Bitcast::F64ToI64, F64(v)) => I64(v.to_bits() as i64),
+        (Bitcast::I32ToI64 | Bitcast::PToP64 | Bitcast::LToI64, I32(v)) => I64(v as u32 as i64),
+        (Bitcast::F32ToI64, F32(v)) => I64(v.to_bits() as i64),
+        (Bitcast::I32ToF32, I32(v)) => F32(f32::from_bits(v as u32)),
+        (Bitcast::I64ToF64, I64(v)) => F64(f64::from_bits(v as u64)),
+        (Bitcast::I64ToI32 | Bitcast::P64ToP | Bitcast::I64ToL, I64(v)) => I32(v as i32),
+        (Bitcast::I64ToF32, I64(v)) => F32(f32::from_bits(v as u32)),
+        (Bitcast::P64ToI64 | Bitcast::I64ToP64, I64(v)) => I64(v),
+        (
+            Bitcast::I32ToP
+            | Bitcast::PToI32
+            | Bitcast::PToL
+            | Bitcast::LToP
+            | Bitcast::I32ToL
+            | Bitcast::LToI32,
+            I32(v),
+        ) => I32(v),
+        (Bitcast::Sequence(casts), value) => {
+            let [first, second] = &**casts;
+            bitcast(second, bitcast(first, value)?)?
+        }
+        (cast, value) => bail!("cannot apply {cast:?} to {value:?}"),
+    })
+}
+
+/// Lowers a primitive value, or a value represented by a handle, to a core
+/// wasm value.
+fn lower(ty: &Type, value: &Value) -> Result<Value> {
+    use Value::*;
+
+    Ok(match (ty, value) {
+        (Type::Bool, Bool(v)) => I32(*v as i32),
+        (Type::U8, U8(v)) => I32(*v as i32),
+        (Type::S8, S8(v)) => I32(*v as i32),
+        (Type::U16, U16(v)) => I32(*v as i32),
+        (Type::S16, S16(v)) => I32(*v as i32),
+        (Type::U32, U32(v)) => I32(*v as i32),
+        (Type::S32, S32(v)) => I32(*v),
+        (Type::U64, U64(v)) => I64(*v as i64),
+        (Type::S64, S64(v)) => I64(*v),
+        (Type::Char, Char(v)) => I32(*v as i32),
+        (Type::F32, F32(v)) => F32(*v),
+        (Type::F64, F64(v)) => F64(*v),
+        (Type::Id(_), Enum(v) | Handle(v) | Future(v) | Stream(v)) => I32(*v as i32),
+        (ty, value) => bail!("cannot lower {value:?} as {ty:?}"),
+    })
+}
+
+/// Lifts a primitive value from a core wasm value.
+fn lift(ty: &Type, value: &Value) -> Result<Value> {
+    use Value::*;
+
+    Ok(match (ty, value) {
+        (Type::Bool, I32(v)) => Bool(*v != 0),
+        (Type::U8, I32(v)) => U8(*v as u8),
+        (Type::S8, I32(v)) => S8(*v as i8),
+        (Type::U16, I32(v)) => U16(*v as u16),
+        (Type::S16, I32(v)) => S16(*v as i16),
+        (Type::U32, I32(v)) => U32(*v as u32),
+        (Type::S32, I32(v)) => S32(*v),
+        (Type::U64, I64(v)) => U64(*v as u64),
+        (Type::S64, I64(v)) => S64(*v),
+        (Type::Char, I32(v)) => match char::from_u32(*v as u32) {
+            Some(c) => Char(c),
+            None => bail!("invalid char {v:#x}"),
+        },
+        (Type::F32, F32(v)) => F32(*v),
+        (Type::F64, F64(v)) => F64(*v),
+        (ty, value) => bail!("cannot lift {ty:?} from {value:?}"),
+    })
+}
+
+/// Returns the in-memory representation of an element of a canonical list.
+fn canonical_bytes(element: &Type, value: &Value) -> Result<Vec<u8>> {
+    use Value::*;
+
+    Ok(match (element, value) {
+        (Type::U8, U8(v)) => v.to_le_bytes().to_vec(),
+        (Type::S8, S8(v)) => v.to_le_bytes().to_vec(),
+        (Type::U16, U16(v)) => v.to_le_bytes().to_vec(),
+        (Type::S16, S16(v)) => v.to_le_bytes().to_vec(),
+        (Type::U32, U32(v)) => v.to_le_bytes().to_vec(),
+        (Type::S32, S32(v)) => v.to_le_bytes().to_vec(),
+        (Type::U64, U64(v)) => v.to_le_bytes().to_vec(),
+        (Type::S64, S64(v)) => v.to_le_bytes().to_vec(),
+        (Type::F32, F32(v)) => v.to_le_bytes().to_vec(),
+        (Type::F64, F64(v)) => v.to_le_bytes().to_vec(),
+        (ty, value) => bail!("cannot store {value:?} in a list of {ty:?}"),
+    })
+}
+
+/// The inverse of `canonical_bytes`.
+fn canonical_value(element: &Type, bytes: &[u8]) -> Value {
+    macro_rules! from_le {
+        ($ty:ident) => {
+            $ty::from_le_bytes(bytes.try_into().unwrap())
+        };
+    }
+    match element {
+        Type::U8 => Value::U8(from_le!(u8)),
+        Type::S8 => Value::S8(from_le!(i8)),
+        Type::U16 => Value::U16(from_le!(u16)),
+        Type::S16 => Value::S16(from_le!(i16)),
+        Type::U32 => Value::U32(from_le!(u32)),
+        Type::S32 => Value::S32(from_le!(i32)),
+        Type::U64 => Value::U64(from_le!(u64)),
+        Type::S64 => Value::S64(from_le!(i64)),
+        Type::F32 => Value::F32(from_le!(f32)),
+        Type::F64 => Value::F64(from_le!(f64)),
+        _ => unreachable!(),
+    }
+}
+
+fn align_to(val: usize, align: usize) -> usize {
+    (val + align - 1) & !(align - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WIT: &str = r#"
+        package test:interpreter;
+
+        interface types {
+            record point {
+                x: s32,
+                y: f64,
+                name: string,
+            }
+            flags small { a, b, c }
+            enum color { red, green, blue }
+            variant shape {
+                none,
+                circle(f32),
+                rect(point),
+                label(string),
+                bits(u64),
+            }
+            resource thing;
+
+            type bytes = list<u8>;
+            type floats = list<f64>;
+            type strings = list<list<string>>;
+            type shapes = list<shape>;
+            type pair = tuple<char, option<option<u16>>>;
+            type outcome = result<list<s64>, shape>;
+            type wide = tuple<u64, u64, u64, u64, u64, u64, u64, u64, u64,
+                              u64, u64, u64, u64, u64, u64, u64, u64>;
+            type handle = own<thing>;
+        }
+    "#;
+
+    fn resolve() -> Resolve {
+        let mut resolve = Resolve::default();
+        resolve.push_str("test.wit", WIT).unwrap();
+        resolve
+    }
+
+    fn ty(resolve: &Resolve, name: &str) -> Type {
+        let (_, iface) = resolve.interfaces.iter().next().unwrap();
+        Type::Id(iface.types[name])
+    }
+
+    fn s(s: &str) -> Value {
+        Value::String(s.to_string())
+    }
+
+    fn point() -> Value {
+        Value::Record(vec![Value::S32(-3), Value::F64(1.5), s("origin")])
+    }
+
+    fn shapes() -> Value {
+        Value::List(vec![
+            Value::Variant(0, None),
+            Value::Variant(1, Some(Box::new(Value::F32(2.5)))),
+            Value::Variant(2, Some(Box::new(point()))),
+            Value::Variant(3, Some(Box::new(s("hi")))),
+            Value::Variant(4, Some(Box::new(Value::U64(u64::MAX)))),
+        ])
+    }
+
+    /// Round-trips every value through `interpreter`, asserting it comes back
+    /// unchanged and that no memory is leaked.
+    fn assert_round_trips(interpreter: &mut Interpreter<'_>, values: Vec<(Type, Value)>) {
+        for (ty, value) in values {
+            let result = interpreter.round_trip(&ty, value.clone()).unwrap();
+            assert_eq!(result, value, "round trip of {ty:?}");
+            assert_eq!(interpreter.memory().live_allocations(), 0, "{value:?}");
+        }
+    }
+
+    fn values(resolve: &Resolve) -> Vec<(Type, Value)> {
+        let t = |name| ty(resolve, name);
+        vec![
+            (Type::Bool, Value::Bool(true)),
+            (Type::U8, Value::U8(u8::MAX)),
+            (Type::S8, Value::S8(i8::MIN)),
+            (Type::U16, Value::U16(u16::MAX)),
+            (Type::S16, Value::S16(i16::MIN)),
+            (Type::U32, Value::U32(u32::MAX)),
+            (Type::S32, Value::S32(i32::MIN)),
+            (Type::U64, Value::U64(u64::MAX)),
+            (Type::S64, Value::S64(i64::MIN)),
+            (Type::F32, Value::F32(-0.25)),
+            (Type::F64, Value::F64(f64::MAX)),
+            (Type::Char, Value::Char('\u{1F980}')),
+            (Type::String, s("")),
+            (Type::String, s("hello, world")),
+            (t("point"), point()),
+            (t("small"), Value::Flags(vec![true, false, true])),
+            (t("color"), Value::Enum(2)),
+            (t("shapes"), shapes()),
+            (t("bytes"), Value::List(vec![Value::U8(1), Value::U8(255)])),
+            (
+                t("floats"),
+                Value::List(vec![Value::F64(0.5), Value::F64(-2.0)]),
+            ),
+            (
+                t("strings"),
+                Value::List(vec![
+                    Value::List(vec![s("a"), s("bc")]),
+                    Value::List(vec![]),
+                    Value::List(vec![s("")]),
+                ]),
+            ),
+            (
+                t("pair"),
+                Value::Tuple(vec![
+                    Value::Char('x'),
+                    Value::Option(Some(Box::new(Value::Option(None)))),
+                ]),
+            ),
+            (
+                t("pair"),
+                Value::Tuple(vec![
+                    Value::Char('y'),
+                    Value::Option(Some(Box::new(Value::Option(Some(Box::new(Value::U16(7))))))),
+                ]),
+            ),
+            (
+                t("outcome"),
+                Value::Result(Ok(Some(Box::new(Value::List(vec![Value::S64(-1)]))))),
+            ),
+            (
+                t("outcome"),
+                Value::Result(Err(Some(Box::new(Value::Variant(
+                    3,
+                    Some(Box::new(s("error"))),
+                ))))),
+            ),
+            (
+                t("wide"),
+                Value::Tuple((0..17).map(|i| Value::U64(i << 40)).collect()),
+            ),
+            (t("handle"), Value::Handle(42)),
+        ]
+    }
+
+    #[test]
+    fn round_trip() {
+        let resolve = resolve();
+        let mut interpreter = Interpreter::new(&resolve);
+        assert_round_trips(&mut interpreter, values(&resolve));
+    }
+
+    #[test]
+    fn round_trip_without_canonical_lists() {
+        let resolve = resolve();
+        let mut interpreter = Interpreter::new(&resolve);
+        interpreter.canonical_lists(false);
+        assert_round_trips(&mut interpreter, values(&resolve));
+    }
+
+    #[test]
+    fn many_flags() {
+        let mut resolve = Resolve::default();
+        let names = (0..40).map(|i| format!("flag{i}")).collect::<Vec<_>>();
+        resolve
+            .push_str(
+                "test.wit",
+                &format!(
+                    "package test:many; interface i {{ flags many {{ {} }} }}",
+                    names.join(", ")
+                ),
+            )
+            .unwrap();
+        let ty = ty(&resolve, "many");
+        let value = Value::Flags((0..40).map(|i| i % 3 == 0).collect());
+        let mut interpreter = Interpreter::new(&resolve);
+        assert_round_trips(&mut interpreter, vec![(ty, value)]);
+    }
+
+    #[test]
+    fn many_params() {
+        let resolve = resolve();
+        let (params, args) = values(&resolve)
+            .into_iter()
+            .enumerate()
+            .map(|(i, (ty, value))| ((format!("p{i}"), ty), value))
+            .unzip::<_, _, Vec<_>, Vec<_>>();
+        let func = Function {
+            name: "many".to_string(),
+            kind: FunctionKind::Freestanding,
+            results: Results::Named(params.clone()),
+            params,
+            docs: Docs::default(),
+            stability: Stability::Unknown,
+        };
+        assert!(
+            resolve
+                .wasm_signature(AbiVariant::GuestImport, &func)
+                .indirect_params
+        );
+
+        let mut interpreter = Interpreter::new(&resolve);
+        let mut received = None;
+        let results = interpreter
+            .call(&func, args.clone(), &mut |args| {
+                received = Some(args.clone());
+                args
+            })
+            .unwrap();
+        assert_eq!(received.as_ref(), Some(&args));
+        assert_eq!(results, args);
+        assert_eq!(interpreter.memory().live_allocations(), 0);
+    }
+
+    #[test]
+    fn invalid_values_are_rejected() {
+        let resolve = resolve();
+        let func = Function {
+            name: "f".to_string(),
+            kind: FunctionKind::Freestanding,
+            params: Vec::new(),
+            results: Results::Anon(ty(&resolve, "color")),
+            docs: Docs::default(),
+            stability: Stability::Unknown,
+        };
+        let mut interpreter = Interpreter::new(&resolve);
+        let err = interpreter
+            .call(&func, Vec::new(), &mut |_| vec![Value::Enum(3)])
+            .unwrap_err();
+        assert!(err.to_string().contains("invalid discriminant 3"), "{err}");
+
+        let err = interpreter
+            .round_trip(&Type::U32, Value::S32(1))
+            .unwrap_err();
+        assert!(err.to_string().contains("cannot lower"), "{err}");
+    }
+}
@@ -4,6 +4,7 @@ use anyhow::Result;
 pub use wit_parser;
 use wit_parser::*;
 pub mod abi;
+pub mod interpreter;
 mod ns;
 pub use ns::Ns;
 pub mod source;
@@ -12,6 +13,8 @@ mod types;
 pub use types::{TypeInfo, Types};
 mod path;
 pub use path::name_package_module;
+#[cfg(feature = "cargo")]
+pub mod cargo;
 
 #[derive(Default, Copy, Clone, PartialEq, Eq, Debug)]
 pub enum Direction {
@@ -217,6 +220,41 @@ pub trait AnonymousTypeGenerator<'a> {
     }
 }
 
+/// Returns the name of the WIT feature gating an item with `stability`, if
+/// any.
+///
+/// This is the feature of `@unstable(feature = foo)` as well as the optional
+/// feature of `@since(version = 1.2.3, feature = foo)`.
+pub fn stability_feature(stability: &Stability) -> Option<&str> {
+    match stability {
+        Stability::Stable { feature, .. } => feature.as_deref(),
+        Stability::Unstable { feature } => Some(feature),
+        Stability::Unknown => None,
+    }
+}
+
+/// Returns whether an item with `stability` is annotated as `@unstable`.
+pub fn is_unstable(stability: &Stability) -> bool {
+    matches!(stability, Stability::Unstable { .. })
+}
+
+/// Returns the deprecation note of an item, if it's deprecated.
+///
+/// WIT as understood by `wit-parser` has no `@deprecated` annotation, so an
+/// item is considered deprecated when a line of its documentation starts
+/// with the `@deprecated` tag, followed by whitespace or the end of the
+/// line. Any text following the tag is used as the note, which may be empty.
+pub fn deprecation(docs: &Docs) -> Option<&str> {
+    docs.contents
+        .as_deref()?
+        .lines()
+        .find_map(|line| {
+            let note = line.trim().strip_prefix("@deprecated")?;
+            (note.is_empty() || note.starts_with(char::is_whitespace)).then_some(note)
+        })
+        .map(|note| note.trim())
+}
+
 pub fn generated_preamble(src: &mut Source, version: &str) {
     uwriteln!(src, "// Generated by `wit-bindgen` {version}. DO NOT EDIT!")
 }
@@ -229,3 +267,28 @@ pub fn dealias(resolve: &Resolve, mut id: TypeId) -> TypeId {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::deprecation;
+    use wit_parser::Docs;
+
+    fn docs(contents: &str) -> Docs {
+        Docs {
+            contents: Some(contents.to_string()),
+        }
+    }
+
+    #[test]
+    fn deprecation_tag() {
+        assert_eq!(deprecation(&Docs::default()), None);
+        assert_eq!(deprecation(&docs("@deprecated")), Some(""));
+        assert_eq!(
+            deprecation(&docs("x\n  @deprecated  use y\n")),
+            Some("use y")
+        );
+        assert_eq!(deprecation(&docs("@deprecated\tuse y")), Some("use y"));
+        assert_eq!(deprecation(&docs("@deprecatedfoo")), None);
+        assert_eq!(deprecation(&docs("not @deprecated")), None);
+    }
+}
@@ -5,6 +5,8 @@ use wit_parser::*;
 #[derive(Default)]
 pub struct Types {
     type_info: HashMap<TypeId, TypeInfo>,
+    interface_stability: HashMap<InterfaceId, Stability>,
+    sizes: SizeAlign,
 }
 
 #[derive(Default, Clone, Copy, Debug)]
@@ -38,6 +40,30 @@ pub struct TypeInfo {
 
     /// Whether this type (transitively) has an own handle.
     pub has_own_handle: bool,
+
+    /// Whether this type (transitively) has an `f32` or `f64`.
+    pub has_float: bool,
+
+    /// Whether this type is "plain old data": it's made up of only integers
+    /// and floats, possibly within records and tuples.
+    ///
+    /// Such types have no invalid bit patterns and own no resources, so
+    /// their in-memory representation in the canonical ABI can be copied
+    /// directly to and from the same layout in a language.
+    pub is_pod: bool,
+
+    /// How deeply compound types are nested within this type.
+    ///
+    /// Primitives, enums, flags and handles have a depth of 0, and every
+    /// other type is one deeper than the deepest type it contains. Type
+    /// aliases have the depth of the type they refer to.
+    pub max_depth: usize,
+
+    /// The size, in bytes, of this type in the canonical ABI.
+    pub size: usize,
+
+    /// The alignment, in bytes, of this type in the canonical ABI.
+    pub align: usize,
 }
 
 impl std::ops::BitOrAssign for TypeInfo {
@@ -49,6 +75,8 @@ impl std::ops::BitOrAssign for TypeInfo {
         self.has_resource |= rhs.has_resource;
         self.has_borrow_handle |= rhs.has_borrow_handle;
         self.has_own_handle |= rhs.has_own_handle;
+        self.has_float |= rhs.has_float;
+        self.max_depth = self.max_depth.max(rhs.max_depth);
     }
 }
 
@@ -59,10 +87,16 @@ impl TypeInfo {
     pub fn is_copy(&self) -> bool {
         !self.has_list && !self.has_resource
     }
+    /// Whether equality of this type is reflexive, meaning it can also be
+    /// hashed and totally ordered.
+    pub fn is_eq(&self) -> bool {
+        !self.has_float && !self.has_resource
+    }
 }
 
 impl Types {
     pub fn analyze(&mut self, resolve: &Resolve) {
+        self.sizes.fill(resolve);
         for (t, _) in resolve.types.iter() {
             self.type_id_info(resolve, t);
         }
@@ -77,10 +111,17 @@ impl Types {
                     WorldItem::Function(f) => {
                         self.type_info_func(resolve, f, import);
                     }
-                    WorldItem::Interface { id, stability: _ } => {
+                    WorldItem::Interface { id, stability } => {
                         for (_, f) in resolve.interfaces[*id].functions.iter() {
                             self.type_info_func(resolve, f, import);
                         }
+                        let prev = self
+                            .interface_stability
+                            .entry(*id)
+                            .or_insert(Stability::Unknown);
+                        if prev.is_unknown() {
+                            *prev = stability.clone();
+                        }
                     }
                     WorldItem::Type(_) => {}
                 }
@@ -146,6 +187,22 @@ impl Types {
         self.type_info[&id]
     }
 
+    /// Returns the stability with which the interface `id` was imported or
+    /// exported by a world.
+    ///
+    /// If the interface was not annotated in any world then the stability
+    /// of the interface's own definition is returned.
+    pub fn interface_stability<'a>(
+        &'a self,
+        resolve: &'a Resolve,
+        id: InterfaceId,
+    ) -> &'a Stability {
+        match self.interface_stability.get(&id) {
+            Some(stability) if !stability.is_unknown() => stability,
+            _ => &resolve.interfaces[id].stability,
+        }
+    }
+
     pub fn type_id_info(&mut self, resolve: &Resolve, ty: TypeId) -> TypeInfo {
         if let Some(info) = self.type_info.get(&ty) {
             return *info;
@@ -195,13 +252,37 @@ impl Types {
             }
             TypeDefKind::Future(ty) => {
                 info = self.optional_type_info(resolve, ty.as_ref());
+                // Like own handles, futures are consumed when passed along.
+                info.has_resource = true;
+                info.has_own_handle = true;
             }
             TypeDefKind::Stream(stream) => {
                 info = self.optional_type_info(resolve, stream.element.as_ref());
                 info |= self.optional_type_info(resolve, stream.end.as_ref());
+                info.has_resource = true;
+                info.has_own_handle = true;
             }
             TypeDefKind::Unknown => unreachable!(),
         }
+        info.is_pod = match &resolve.types[ty].kind {
+            TypeDefKind::Record(r) => r
+                .fields
+                .iter()
+                .all(|field| self.type_info(resolve, &field.ty).is_pod),
+            TypeDefKind::Tuple(t) => t.types.iter().all(|ty| self.type_info(resolve, ty).is_pod),
+            TypeDefKind::Type(ty) => self.type_info(resolve, ty).is_pod,
+            _ => false,
+        };
+        match &resolve.types[ty].kind {
+            TypeDefKind::Type(_)
+            | TypeDefKind::Resource
+            | TypeDefKind::Handle(_)
+            | TypeDefKind::Flags(_)
+            | TypeDefKind::Enum(_) => {}
+            _ => info.max_depth += 1,
+        }
+        info.size = self.sizes.size(&Type::Id(ty));
+        info.align = self.sizes.align(&Type::Id(ty));
         let prev = self.type_info.insert(ty, info);
         assert!(prev.is_none());
         info
@@ -212,8 +293,15 @@ impl Types {
         match ty {
             Type::String => info.has_list = true,
             Type::Id(id) => return self.type_id_info(resolve, *id),
-            _ => {}
+            Type::F32 | Type::F64 => {
+                info.has_float = true;
+                info.is_pod = true;
+            }
+            Type::Bool | Type::Char => {}
+            _ => info.is_pod = true,
         }
+        info.size = self.sizes.size(ty);
+        info.align = self.sizes.align(ty);
         info
     }
 
@@ -224,3 +312,53 @@ impl Types {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn facts() {
+        let mut resolve = Resolve::default();
+        resolve
+            .push_str(
+                "test.wit",
+                r#"
+                    package test:types;
+
+                    interface i {
+                        resource r;
+                        record point { x: u32, y: f64 }
+                        type alias = point;
+                        record nested { a: list<option<point>>, b: own<r> }
+                        enum e { a }
+                        record async { f: future<u32>, s: list<stream<u8>> }
+                    }
+                "#,
+            )
+            .unwrap();
+        let mut types = Types::default();
+        types.analyze(&resolve);
+        let (_, iface) = resolve.interfaces.iter().next().unwrap();
+        let info = |name: &str| types.get(iface.types[name]);
+
+        let point = info("point");
+        assert!(point.has_float && point.is_pod && !point.is_eq());
+        assert_eq!((point.max_depth, point.size, point.align), (1, 16, 8));
+
+        let alias = info("alias");
+        assert!(alias.is_pod);
+        assert_eq!((alias.max_depth, alias.size, alias.align), (1, 16, 8));
+
+        let nested = info("nested");
+        assert!(nested.has_float && nested.has_resource && !nested.is_pod);
+        assert_eq!((nested.max_depth, nested.size, nested.align), (4, 12, 4));
+
+        let e = info("e");
+        assert!(!e.is_pod && e.is_eq());
+        assert_eq!((e.max_depth, e.size, e.align), (0, 1, 1));
+
+        let async_ = info("async");
+        assert!(async_.has_resource && async_.has_own_handle && !async_.is_clone());
+    }
+}
@@ -0,0 +1,2198 @@
+use anyhow::Result;
+use heck::*;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::mem;
+use wit_bindgen_c::{
+    component_type_object, contains_own_handle, flags_repr,
+    imported_types_used_by_exported_interfaces, int_repr, to_c_ident,
+};
+use wit_bindgen_core::abi::{
+    self, AbiVariant, Bindgen, Bitcast, Instruction, LiftLower, WasmSignature, WasmType,
+};
+use wit_bindgen_core::{
+    dealias, deprecation, uwrite, uwriteln, wit_parser::*, Direction, Files,
+    InterfaceGenerator as _, Ns, Source, WorldGenerator,
+};
+use wit_component::StringEncoding;
+
+#[derive(Default)]
+struct Cpp {
+    opts: Opts,
+    world: String,
+    world_id: Option<WorldId>,
+    sizes: SizeAlign,
+    names: Ns,
+    h_src: Source,
+    cpp_fns: Source,
+    cpp_adapters: Source,
+    return_pointer_area_size: usize,
+    return_pointer_area_align: usize,
+
+    /// Fully qualified C++ names of the records, variants, enums, flags and
+    /// resources defined so far.
+    type_names: HashMap<TypeId, String>,
+    /// Whether each resource defined so far is imported or exported.
+    resources: HashMap<TypeId, Direction>,
+}
+
+#[derive(Default, Debug, Clone)]
+#[cfg_attr(feature = "clap", derive(clap::Args))]
+pub struct Opts {
+    /// Skip generating an object file which contains type information for the
+    /// world that is being generated.
+    #[cfg_attr(feature = "clap", arg(long, default_value_t = false))]
+    pub no_object_file: bool,
+
+    /// Add the specified suffix to the name of the custome section containing
+    /// the component type.
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub type_section_suffix: Option<String>,
+}
+
+impl Opts {
+    pub fn build(&self) -> Box<dyn WorldGenerator> {
+        Box::new(Cpp {
+            opts: self.clone(),
+            ..Cpp::default()
+        })
+    }
+}
+
+/// Signature of a function as seen by C++ code.
+struct CppSig {
+    /// Name of the function within its namespace or class.
+    name: String,
+    /// Return type, empty for constructors of imported resources.
+    ret: String,
+    /// Types and names of all parameters, excluding `self` of methods.
+    params: Vec<(String, String)>,
+}
+
+impl CppSig {
+    fn params(&self) -> String {
+        self.params
+            .iter()
+            .map(|(ty, name)| format!("{ty} {name}"))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+impl WorldGenerator for Cpp {
+    fn preprocess(&mut self, resolve: &Resolve, world: WorldId) {
+        self.world = resolve.worlds[world].name.clone();
+        self.sizes.fill(resolve);
+        self.world_id = Some(world);
+    }
+
+    fn import_interface(
+        &mut self,
+        resolve: &Resolve,
+        name: &WorldKey,
+        id: InterfaceId,
+        _files: &mut Files,
+    ) -> Result<()> {
+        let module = resolve.name_world_key(name);
+        let mut gen = self.interface(resolve, true, Some((id, name)), module);
+        gen.define_interface_types(id);
+
+        for (_name, func) in resolve.interfaces[id].functions.iter() {
+            gen.import(func);
+        }
+
+        gen.finish();
+        Ok(())
+    }
+
+    fn import_funcs(
+        &mut self,
+        resolve: &Resolve,
+        _world: WorldId,
+        funcs: &[(&str, &Function)],
+        _files: &mut Files,
+    ) {
+        let mut gen = self.interface(resolve, true, None, "$root".to_string());
+        gen.define_function_types(funcs);
+
+        for (_name, func) in funcs.iter() {
+            gen.import(func);
+        }
+
+        gen.finish();
+    }
+
+    fn export_interface(
+        &mut self,
+        resolve: &Resolve,
+        name: &WorldKey,
+        id: InterfaceId,
+        _files: &mut Files,
+    ) -> Result<()> {
+        let module = resolve.name_world_key(name);
+        let mut gen = self.interface(resolve, false, Some((id, name)), module);
+        gen.define_interface_types(id);
+
+        for (_name, func) in resolve.interfaces[id].functions.iter() {
+            gen.export(func);
+        }
+
+        gen.finish();
+        Ok(())
+    }
+
+    fn export_funcs(
+        &mut self,
+        resolve: &Resolve,
+        _world: WorldId,
+        funcs: &[(&str, &Function)],
+        _files: &mut Files,
+    ) -> Result<()> {
+        let mut gen = self.interface(resolve, false, None, "$root".to_string());
+        gen.define_function_types(funcs);
+
+        for (_name, func) in funcs.iter() {
+            gen.export(func);
+        }
+
+        gen.finish();
+        Ok(())
+    }
+
+    fn import_types(
+        &mut self,
+        resolve: &Resolve,
+        _world: WorldId,
+        types: &[(&str, TypeId)],
+        _files: &mut Files,
+    ) {
+        let mut gen = self.interface(resolve, true, None, "$root".to_string());
+        let mut live = LiveTypes::default();
+        for (_, id) in types {
+            live.add_type_id(resolve, *id);
+        }
+        gen.define_live_types(live);
+        gen.finish();
+    }
+
+    fn pre_export_interface(&mut self, resolve: &Resolve, _files: &mut Files) -> Result<()> {
+        // Same as the C generator, types of interfaces which are both imported
+        // and exported are defined a second time for the exports.
+        let live_import_types =
+            imported_types_used_by_exported_interfaces(resolve, self.world_id.unwrap());
+        self.type_names.retain(|k, _| live_import_types.contains(k));
+        self.resources.retain(|k, _| live_import_types.contains(k));
+        Ok(())
+    }
+
+    fn finish(&mut self, resolve: &Resolve, id: WorldId, files: &mut Files) -> Result<()> {
+        let snake = self.world.to_snake_case();
+        let version = env!("CARGO_PKG_VERSION");
+
+        let mut h_str = Source::default();
+        wit_bindgen_core::generated_preamble(&mut h_str, version);
+        h_str.push_str(&format!(
+            "#ifndef __BINDINGS_{0}_CPP_H
+            #define __BINDINGS_{0}_CPP_H
+
+            #include <cstddef>
+            #include <cstdint>
+            #include <expected>
+            #include <functional>
+            #include <memory>
+            #include <optional>
+            #include <span>
+            #include <string>
+            #include <string_view>
+            #include <tuple>
+            #include <variant>
+            #include <vector>
+
+            #ifndef __WIT_CPP_RUNTIME
+            #define __WIT_CPP_RUNTIME
+            namespace wit {{
+            // Releases an exported resource once the guest no longer owns it,
+            // either by deleting it or by dropping the handle it was given to.
+            template <class R> struct ResourceDeleter {{
+            void operator()(R *rep) const {{ R::drop_rep(rep); }}
+            }};
+
+            // Owned instance of an exported resource.
+            template <class R> using Owned = std::unique_ptr<R, ResourceDeleter<R>>;
+            }}
+            #endif
+            ",
+            self.world.to_shouty_snake_case(),
+        ));
+        h_str.push_str(&self.h_src);
+        uwriteln!(h_str, "\n#endif");
+
+        let linking_symbol = component_type_object::linking_symbol(&self.world);
+        let mut cpp_str = Source::default();
+        wit_bindgen_core::generated_preamble(&mut cpp_str, version);
+        cpp_str.push_str(&format!(
+            r#"#include "{snake}_cpp.h"
+            #include <cstdlib>
+            #include <cstring>
+            #include <bit>
+            #include <deque>
+
+            // Canonical ABI intrinsics
+
+            extern "C" __attribute__((__weak__, __export_name__("cabi_realloc")))
+            void *cabi_realloc(void *ptr, size_t old_size, size_t align, size_t new_size) {{
+            (void) old_size;
+            if (new_size == 0) return (void*) align;
+            void *ret = realloc(ptr, new_size);
+            if (!ret) abort();
+            return ret;
+            }}
+
+            // Ensure that the *_component_type.o object is linked in
+            extern "C" void {linking_symbol}(void);
+            extern "C" void {linking_symbol}_public_use_in_this_compilation_unit(void) {{
+            {linking_symbol}();
+            }}
+            "#,
+        ));
+        cpp_str.push_str(&self.cpp_fns);
+
+        uwriteln!(cpp_str, "\n// Component Adapters");
+
+        // Exports return their results through a statically-allocated area
+        // which must stay live until the post-return call.
+        if self.return_pointer_area_size > 0 {
+            cpp_str.push_str(&format!(
+                "
+                alignas({}) static uint8_t RET_AREA[{}];
+                ",
+                self.return_pointer_area_align, self.return_pointer_area_size,
+            ));
+        }
+        cpp_str.push_str(&self.cpp_adapters);
+
+        files.push(&format!("{snake}_cpp.h"), h_str.as_bytes());
+        files.push(&format!("{snake}.cpp"), cpp_str.as_bytes());
+        if !self.opts.no_object_file {
+            files.push(
+                &format!("{snake}_component_type.o",),
+                component_type_object::object(
+                    resolve,
+                    id,
+                    &self.world,
+                    StringEncoding::UTF8,
+                    self.opts.type_section_suffix.as_deref(),
+                )
+                .unwrap()
+                .as_slice(),
+            );
+        }
+
+        Ok(())
+    }
+}
+
+impl Cpp {
+    fn interface<'a>(
+        &'a mut self,
+        resolve: &'a Resolve,
+        in_import: bool,
+        interface: Option<(InterfaceId, &'a WorldKey)>,
+        module: String,
+    ) -> InterfaceGenerator<'a> {
+        let namespace = self.namespace(resolve, interface.map(|(_, key)| key), in_import);
+        InterfaceGenerator {
+            gen: self,
+            resolve,
+            in_import,
+            interface,
+            module,
+            namespace,
+            h_src: Source::default(),
+            cpp_fns: Source::default(),
+            cpp_adapters: Source::default(),
+            guest_methods: Source::default(),
+        }
+    }
+
+    /// Returns the namespace, without a leading `::`, that the bindings for
+    /// the interface `key`, or the world if `None`, are placed in.
+    fn namespace(&self, resolve: &Resolve, key: Option<&WorldKey>, in_import: bool) -> String {
+        let mut ns = Vec::new();
+        if !in_import {
+            ns.push("exports".to_string());
+        }
+        match key {
+            None => ns.push(to_c_ident(&self.world)),
+            Some(WorldKey::Name(name)) => ns.push(to_c_ident(name)),
+            Some(WorldKey::Interface(id)) => {
+                let iface = &resolve.interfaces[*id];
+                let pkg = &resolve.packages[iface.package.unwrap()];
+                ns.push(to_c_ident(&pkg.name.namespace));
+                ns.push(to_c_ident(&pkg.name.name));
+                let pkg_has_multiple_versions = resolve.packages.iter().any(|(_, p)| {
+                    p.name.namespace == pkg.name.namespace
+                        && p.name.name == pkg.name.name
+                        && p.name.version != pkg.name.version
+                });
+                if pkg_has_multiple_versions {
+                    if let Some(version) = &pkg.name.version {
+                        let version = version.to_string().replace(['.', '-', '+'], "_");
+                        ns.push(format!("v{version}"));
+                    }
+                }
+                ns.push(to_c_ident(iface.name.as_ref().unwrap()));
+            }
+        }
+        ns.join("::")
+    }
+
+    fn type_name(&self, resolve: &Resolve, ty: &Type) -> String {
+        match ty {
+            Type::Bool => "bool".to_string(),
+            Type::Char => "char32_t".to_string(),
+            Type::U8 => "uint8_t".to_string(),
+            Type::S8 => "int8_t".to_string(),
+            Type::U16 => "uint16_t".to_string(),
+            Type::S16 => "int16_t".to_string(),
+            Type::U32 => "uint32_t".to_string(),
+            Type::S32 => "int32_t".to_string(),
+            Type::U64 => "uint64_t".to_string(),
+            Type::S64 => "int64_t".to_string(),
+            Type::F32 => "float".to_string(),
+            Type::F64 => "double".to_string(),
+            Type::String => "std::string".to_string(),
+            Type::Id(id) => match &resolve.types[*id].kind {
+                TypeDefKind::Record(_)
+                | TypeDefKind::Variant(_)
+                | TypeDefKind::Enum(_)
+                | TypeDefKind::Flags(_)
+                | TypeDefKind::Resource => self.type_names[id].clone(),
+
+                // Aliases are always expanded as they can't be forward
+                // declared for use by resource methods.
+                TypeDefKind::Type(t) => self.type_name(resolve, t),
+
+                TypeDefKind::Handle(Handle::Own(resource)) => {
+                    let resource = dealias(resolve, *resource);
+                    let name = &self.type_names[&resource];
+                    match self.resources[&resource] {
+                        Direction::Import => name.clone(),
+                        Direction::Export => format!("::wit::Owned<{name}>"),
+                    }
+                }
+                TypeDefKind::Handle(Handle::Borrow(resource)) => {
+                    let resource = dealias(resolve, *resource);
+                    let name = &self.type_names[&resource];
+                    match self.resources[&resource] {
+                        Direction::Import => format!("std::reference_wrapper<const {name}>"),
+                        Direction::Export => format!("std::reference_wrapper<{name}>"),
+                    }
+                }
+                TypeDefKind::Tuple(t) => {
+                    let types = t
+                        .types
+                        .iter()
+                        .map(|ty| self.type_name(resolve, ty))
+                        .collect::<Vec<_>>();
+                    format!("std::tuple<{}>", types.join(", "))
+                }
+                TypeDefKind::Option(t) => {
+                    format!("std::optional<{}>", self.type_name(resolve, t))
+                }
+                TypeDefKind::Result(r) => {
+                    let ok = match &r.ok {
+                        Some(ty) => self.type_name(resolve, ty),
+                        None => "void".to_string(),
+                    };
+                    let err = match &r.err {
+                        Some(ty) => self.type_name(resolve, ty),
+                        None => "std::monostate".to_string(),
+                    };
+                    format!("std::expected<{ok}, {err}>")
+                }
+                TypeDefKind::List(t) => format!("std::vector<{}>", self.type_name(resolve, t)),
+                TypeDefKind::Future(_) | TypeDefKind::Stream(_) => "uint32_t".to_string(),
+                TypeDefKind::Unknown => unreachable!(),
+            },
+        }
+    }
+
+    /// Returns the type of a parameter of an imported function.
+    ///
+    /// Strings and lists are borrowed from the caller and aggregates are
+    /// passed by reference, unless they own a handle which the call consumes.
+    fn import_param_type(&self, resolve: &Resolve, ty: &Type) -> String {
+        let id = match ty {
+            Type::String => return "std::string_view".to_string(),
+            Type::Id(id) => *id,
+            _ => return self.type_name(resolve, ty),
+        };
+        match &resolve.types[id].kind {
+            TypeDefKind::Type(t) => self.import_param_type(resolve, t),
+            TypeDefKind::Handle(Handle::Borrow(resource)) => {
+                let resource = dealias(resolve, *resource);
+                format!("{} const&", self.type_names[&resource])
+            }
+            TypeDefKind::Enum(_)
+            | TypeDefKind::Flags(_)
+            | TypeDefKind::Future(_)
+            | TypeDefKind::Stream(_) => self.type_name(resolve, ty),
+            TypeDefKind::List(element)
+                if !contains_own_handle(resolve, element) && !is_bool(resolve, element) =>
+            {
+                format!("std::span<const {}>", self.type_name(resolve, element))
+            }
+            _ if contains_own_handle(resolve, ty) => format!("{}&&", self.type_name(resolve, ty)),
+            _ => format!("{} const&", self.type_name(resolve, ty)),
+        }
+    }
+
+    /// Returns the type of a parameter of an exported function, which owns
+    /// all of its arguments except for borrowed handles.
+    fn export_param_type(&self, resolve: &Resolve, ty: &Type) -> String {
+        if let Type::Id(id) = ty {
+            match &resolve.types[*id].kind {
+                TypeDefKind::Type(t) => return self.export_param_type(resolve, t),
+                TypeDefKind::Handle(Handle::Borrow(resource)) => {
+                    let resource = dealias(resolve, *resource);
+                    let name = &self.type_names[&resource];
+                    return match self.resources[&resource] {
+                        Direction::Import => format!("{name} const&"),
+                        Direction::Export => format!("{name}&"),
+                    };
+                }
+                _ => {}
+            }
+        }
+        self.type_name(resolve, ty)
+    }
+}
+
+/// Returns whether `ty` is a `bool`, whose `std::vector` isn't contiguous.
+fn is_bool(resolve: &Resolve, ty: &Type) -> bool {
+    match ty {
+        Type::Bool => true,
+        Type::Id(id) => match &resolve.types[*id].kind {
+            TypeDefKind::Type(t) => is_bool(resolve, t),
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// Returns the name of the struct representing `case` of the variant `name`,
+/// which can't be the same as the name of the variant itself.
+fn variant_case_name(name: &str, case: &str) -> String {
+    let case = case.to_upper_camel_case();
+    if case == name {
+        format!("{case}_")
+    } else {
+        case
+    }
+}
+
+struct InterfaceGenerator<'a> {
+    gen: &'a mut Cpp,
+    resolve: &'a Resolve,
+    in_import: bool,
+    interface: Option<(InterfaceId, &'a WorldKey)>,
+    /// Name of the core wasm module functions and resource intrinsics of this
+    /// interface are imported from, or exported with.
+    module: String,
+    namespace: String,
+    h_src: Source,
+    cpp_fns: Source,
+    cpp_adapters: Source,
+    /// Declarations of the pure virtual methods of the `Guest` class which
+    /// exported functions dispatch to.
+    guest_methods: Source,
+}
+
+impl<'a> wit_bindgen_core::InterfaceGenerator<'a> for InterfaceGenerator<'a> {
+    fn resolve(&self) -> &'a Resolve {
+        self.resolve
+    }
+
+    fn type_record(&mut self, id: TypeId, _name: &str, record: &Record, docs: &Docs) {
+        let name = self.local_name(id);
+        self.h_src.push_str("\n");
+        self.docs(docs);
+        uwriteln!(self.h_src, "struct {name} {{");
+        for field in record.fields.iter() {
+            self.docs(&field.docs);
+            let ty = self.gen.type_name(self.resolve, &field.ty);
+            uwriteln!(self.h_src, "{ty} {};", to_c_ident(&field.name));
+        }
+        uwriteln!(self.h_src, "}};");
+    }
+
+    fn type_resource(&mut self, id: TypeId, name: &str, docs: &Docs) {
+        let class = self.local_name(id);
+        let flat_ns = self.namespace.replace("::", "_");
+        let snake = name.to_snake_case();
+        self.h_src.push_str("\n");
+        self.docs(docs);
+        uwriteln!(self.h_src, "class {class} {{");
+        uwriteln!(self.h_src, "public:");
+
+        if self.in_import {
+            for func in self.resource_funcs(id) {
+                let sig = self.sig(func);
+                let params = sig.params();
+                self.docs(&func.docs);
+                let deprecated = self.deprecated(func);
+                match &func.kind {
+                    FunctionKind::Constructor(_) => {
+                        let explicit = if sig.params.len() == 1 {
+                            "explicit "
+                        } else {
+                            ""
+                        };
+                        uwriteln!(self.h_src, "{deprecated}{explicit}{class}({params});");
+                    }
+                    FunctionKind::Method(_) => {
+                        uwriteln!(
+                            self.h_src,
+                            "{deprecated}{} {}({params}) const;",
+                            sig.ret,
+                            sig.name
+                        );
+                    }
+                    FunctionKind::Static(_) => {
+                        uwriteln!(
+                            self.h_src,
+                            "{deprecated}static {} {}({params});",
+                            sig.ret,
+                            sig.name
+                        );
+                    }
+                    FunctionKind::Freestanding => unreachable!(),
+                }
+            }
+
+            // Imported resources are move-only owners of their handle which
+            // is dropped along with the object.
+            self.h_src.push_str(&format!(
+                "
+                ~{class}();
+                {class}({class}&& other) noexcept;
+                {class}& operator=({class}&& other) noexcept;
+                {class}({class} const&) = delete;
+                {class}& operator=({class} const&) = delete;
+
+                // Takes ownership of the handle `handle`.
+                static {class} from_handle(int32_t handle);
+                // Refers to the borrowed handle `handle` without dropping it.
+                static {class} from_borrowed_handle(int32_t handle);
+                // Returns the handle of this resource.
+                int32_t handle() const;
+                // Gives up ownership of the handle of this resource.
+                int32_t into_handle();
+
+                private:
+                struct FromHandle {{}};
+                {class}(FromHandle, int32_t handle, bool owned);
+                int32_t handle_;
+                bool owned_;
+                }};
+                "
+            ));
+
+            let drop_fn = self
+                .gen
+                .names
+                .tmp(&format!("__wasm_import_{flat_ns}_{snake}_drop"));
+            self.cpp_fns.push_str(&format!(
+                r#"
+                extern "C" __attribute__((__import_module__("{module}"), __import_name__("[resource-drop]{name}")))
+                void {drop_fn}(int32_t handle);
+
+                {class}::{class}(FromHandle, int32_t handle, bool owned) : handle_(handle), owned_(owned) {{}}
+
+                {class}::~{class}() {{
+                if (owned_ && handle_ >= 0) {{
+                {drop_fn}(handle_);
+                }}
+                }}
+
+                {class}::{class}({class}&& other) noexcept : handle_(other.handle_), owned_(other.owned_) {{
+                other.handle_ = -1;
+                }}
+
+                {class}& {class}::operator=({class}&& other) noexcept {{
+                if (this != &other) {{
+                if (owned_ && handle_ >= 0) {{
+                {drop_fn}(handle_);
+                }}
+                handle_ = other.handle_;
+                owned_ = other.owned_;
+                other.handle_ = -1;
+                }}
+                return *this;
+                }}
+
+                {class} {class}::from_handle(int32_t handle) {{
+                return {class}(FromHandle{{}}, handle, true);
+                }}
+
+                {class} {class}::from_borrowed_handle(int32_t handle) {{
+                return {class}(FromHandle{{}}, handle, false);
+                }}
+
+                int32_t {class}::handle() const {{
+                return handle_;
+                }}
+
+                int32_t {class}::into_handle() {{
+                int32_t handle = handle_;
+                handle_ = -1;
+                return handle;
+                }}
+                "#,
+                module = self.module,
+            ));
+        } else {
+            // Exported resources are implemented by subclassing this class,
+            // and instances are owned through `wit::Owned` until they're given
+            // to the host.
+            uwriteln!(self.h_src, "using Owned = ::wit::Owned<{class}>;");
+            uwriteln!(self.h_src, "virtual ~{class}() = default;");
+            for func in self.resource_funcs(id) {
+                let sig = self.sig(func);
+                let params = sig.params();
+                self.docs(&func.docs);
+                match &func.kind {
+                    FunctionKind::Constructor(_) => {
+                        uwriteln!(self.h_src, "static Owned {}({params});", sig.name);
+                    }
+                    FunctionKind::Method(_) => {
+                        uwriteln!(
+                            self.h_src,
+                            "virtual {} {}({params}) = 0;",
+                            sig.ret,
+                            sig.name
+                        );
+                    }
+                    FunctionKind::Static(_) => {
+                        uwriteln!(self.h_src, "static {} {}({params});", sig.ret, sig.name);
+                    }
+                    FunctionKind::Freestanding => unreachable!(),
+                }
+            }
+            self.h_src.push_str(&format!(
+                "
+                // Gives ownership of `rep` to a handle, creating one if `rep`
+                // doesn't have one yet.
+                static int32_t into_handle(Owned rep);
+                // Takes ownership of the resource behind the handle `handle`.
+                static Owned from_handle(int32_t handle);
+                // Called by `wit::ResourceDeleter` to release `rep`.
+                static void drop_rep({class} *rep);
+                // Called once the last handle to `rep` has been dropped.
+                static void destroy({class} *rep);
+
+                private:
+                int32_t handle_ = -1;
+                }};
+                "
+            ));
+
+            let new_fn = self
+                .gen
+                .names
+                .tmp(&format!("__wasm_import_{flat_ns}_{snake}_new"));
+            let rep_fn = self
+                .gen
+                .names
+                .tmp(&format!("__wasm_import_{flat_ns}_{snake}_rep"));
+            let drop_fn = self
+                .gen
+                .names
+                .tmp(&format!("__wasm_import_{flat_ns}_{snake}_drop"));
+            let dtor_fn = self
+                .gen
+                .names
+                .tmp(&format!("__wasm_export_{flat_ns}_{snake}_dtor"));
+            let module = &self.module;
+            self.cpp_fns.push_str(&format!(
+                r#"
+                extern "C" __attribute__((__import_module__("[export]{module}"), __import_name__("[resource-new]{name}")))
+                int32_t {new_fn}(int32_t rep);
+
+                extern "C" __attribute__((__import_module__("[export]{module}"), __import_name__("[resource-rep]{name}")))
+                int32_t {rep_fn}(int32_t handle);
+
+                extern "C" __attribute__((__import_module__("[export]{module}"), __import_name__("[resource-drop]{name}")))
+                void {drop_fn}(int32_t handle);
+
+                int32_t {class}::into_handle({class}::Owned rep) {{
+                {class} *ptr = rep.release();
+                if (ptr->handle_ < 0) {{
+                ptr->handle_ = {new_fn}((int32_t) (uintptr_t) ptr);
+                }}
+                return ptr->handle_;
+                }}
+
+                {class}::Owned {class}::from_handle(int32_t handle) {{
+                {class} *ptr = ({class} *) (uintptr_t) {rep_fn}(handle);
+                ptr->handle_ = handle;
+                return {class}::Owned(ptr);
+                }}
+
+                void {class}::drop_rep({class} *rep) {{
+                if (rep->handle_ < 0) {{
+                delete rep;
+                }} else {{
+                {drop_fn}(rep->handle_);
+                }}
+                }}
+
+                void {class}::destroy({class} *rep) {{
+                rep->handle_ = -1;
+                delete rep;
+                }}
+                "#
+            ));
+            self.cpp_adapters.push_str(&format!(
+                r#"
+                extern "C" __attribute__((__export_name__("{module}#[dtor]{name}")))
+                void {dtor_fn}(int32_t rep) {{
+                {class}::destroy(({class} *) (uintptr_t) rep);
+                }}
+                "#
+            ));
+        }
+    }
+
+    fn type_tuple(&mut self, id: TypeId, _name: &str, _tuple: &Tuple, docs: &Docs) {
+        self.type_using(id, docs);
+    }
+
+    fn type_flags(&mut self, id: TypeId, _name: &str, flags: &Flags, docs: &Docs) {
+        let name = self.local_name(id);
+        let repr = int_repr(flags_repr(flags));
+        self.h_src.push_str("\n");
+        self.docs(docs);
+        uwriteln!(self.h_src, "enum class {name} : {repr} {{");
+        if !flags
+            .flags
+            .iter()
+            .any(|f| f.name.to_upper_camel_case() == "None")
+        {
+            uwriteln!(self.h_src, "kNone = 0,");
+        }
+        for (i, flag) in flags.flags.iter().enumerate() {
+            self.docs(&flag.docs);
+            uwriteln!(
+                self.h_src,
+                "k{} = {repr}(1) << {i},",
+                flag.name.to_upper_camel_case()
+            );
+        }
+        uwriteln!(self.h_src, "}};");
+        self.h_src.push_str(&format!(
+            "
+            constexpr inline {name} operator|({name} a, {name} b) {{
+            return {name}({repr}(a) | {repr}(b));
+            }}
+            constexpr inline {name} operator&({name} a, {name} b) {{
+            return {name}({repr}(a) & {repr}(b));
+            }}
+            "
+        ));
+    }
+
+    fn type_variant(&mut self, id: TypeId, _name: &str, variant: &Variant, docs: &Docs) {
+        let name = self.local_name(id);
+        self.h_src.push_str("\n");
+        self.docs(docs);
+        uwriteln!(self.h_src, "struct {name} {{");
+        let mut cases = Vec::new();
+        for case in variant.cases.iter() {
+            self.docs(&case.docs);
+            let case_name = variant_case_name(&name, &case.name);
+            match &case.ty {
+                Some(ty) => {
+                    let ty = self.gen.type_name(self.resolve, ty);
+                    uwriteln!(self.h_src, "struct {case_name} {{");
+                    uwriteln!(self.h_src, "{ty} value;");
+                    uwriteln!(self.h_src, "}};");
+                }
+                None => uwriteln!(self.h_src, "struct {case_name} {{}};"),
+            }
+            cases.push(case_name);
+        }
+        uwriteln!(self.h_src, "std::variant<{}> variants;", cases.join(", "));
+        uwriteln!(self.h_src, "}};");
+    }
+
+    fn type_option(&mut self, id: TypeId, _name: &str, _payload: &Type, docs: &Docs) {
+        self.type_using(id, docs);
+    }
+
+    fn type_result(&mut self, id: TypeId, _name: &str, _result: &Result_, docs: &Docs) {
+        self.type_using(id, docs);
+    }
+
+    fn type_enum(&mut self, id: TypeId, _name: &str, enum_: &Enum, docs: &Docs) {
+        let name = self.local_name(id);
+        self.h_src.push_str("\n");
+        self.docs(docs);
+        uwriteln!(
+            self.h_src,
+            "enum class {name} : {} {{",
+            int_repr(enum_.tag())
+        );
+        for case in enum_.cases.iter() {
+            self.docs(&case.docs);
+            uwriteln!(self.h_src, "k{},", case.name.to_upper_camel_case());
+        }
+        uwriteln!(self.h_src, "}};");
+    }
+
+    fn type_alias(&mut self, id: TypeId, _name: &str, _ty: &Type, docs: &Docs) {
+        self.type_using(id, docs);
+    }
+
+    fn type_list(&mut self, id: TypeId, _name: &str, _ty: &Type, docs: &Docs) {
+        self.type_using(id, docs);
+    }
+
+    fn type_builtin(&mut self, id: TypeId, _name: &str, _ty: &Type, docs: &Docs) {
+        self.type_using(id, docs);
+    }
+}
+
+impl<'a> InterfaceGenerator<'a> {
+    /// Appends the bindings of this interface, wrapped in its namespace, to
+    /// the output of the world.
+    fn finish(mut self) {
+        if !self.guest_methods.is_empty() {
+            self.finish_guest();
+        }
+        let ns = &self.namespace;
+        for (src, dst) in [
+            (&self.h_src, &mut self.gen.h_src),
+            (&self.cpp_fns, &mut self.gen.cpp_fns),
+            (&self.cpp_adapters, &mut self.gen.cpp_adapters),
+        ] {
+            if src.is_empty() {
+                continue;
+            }
+            uwriteln!(dst, "\nnamespace {ns} {{");
+            dst.push_str(src);
+            uwriteln!(dst, "}}");
+        }
+    }
+
+    /// Defines the abstract `Guest` class implementing the exported functions
+    /// along with `guest`, which the component defines to return the
+    /// implementation they dispatch to.
+    fn finish_guest(&mut self) {
+        uwriteln!(
+            self.h_src,
+            "
+            // Implementation of the exported functions.
+            class Guest {{
+            public:
+            virtual ~Guest() = default;"
+        );
+        self.h_src.push_str(&self.guest_methods);
+        uwriteln!(
+            self.h_src,
+            "}};
+
+            // Returns the implementation which exported functions dispatch to.
+            //
+            // This is defined by the component and called on every call to an
+            // exported function. Static initializers aren't run in components, so
+            // the implementation is best created on the first call, for example as
+            // a function-local static.
+            Guest &guest();"
+        );
+    }
+
+    fn define_interface_types(&mut self, id: InterfaceId) {
+        let mut live = LiveTypes::default();
+        live.add_interface(self.resolve, id);
+        self.define_live_types(live);
+    }
+
+    fn define_function_types(&mut self, funcs: &[(&str, &Function)]) {
+        let mut live = LiveTypes::default();
+        for (_, func) in funcs {
+            live.add_func(self.resolve, func);
+        }
+        self.define_live_types(live);
+    }
+
+    fn define_live_types(&mut self, live: LiveTypes) {
+        // Names are assigned up front as methods of resources may refer to
+        // types defined after the resource itself.
+        let mut types = Vec::new();
+        for id in live.iter() {
+            if self.gen.type_names.contains_key(&id) {
+                continue;
+            }
+            let ty = &self.resolve.types[id];
+            let name = match &ty.name {
+                Some(name) => to_upper_camel_case(name),
+                None => continue,
+            };
+            let name = format!("::{}::{name}", self.namespace);
+            self.gen.type_names.insert(id, name);
+            if let TypeDefKind::Resource = ty.kind {
+                let direction = if self.in_import {
+                    Direction::Import
+                } else {
+                    Direction::Export
+                };
+                self.gen.resources.insert(id, direction);
+            }
+            types.push(id);
+        }
+
+        let has_resources = types
+            .iter()
+            .any(|id| matches!(self.resolve.types[*id].kind, TypeDefKind::Resource));
+        if has_resources {
+            self.h_src.push_str("\n");
+            for id in types.iter() {
+                let name = self.local_name(*id);
+                match &self.resolve.types[*id].kind {
+                    TypeDefKind::Record(_) | TypeDefKind::Variant(_) => {
+                        uwriteln!(self.h_src, "struct {name};");
+                    }
+                    TypeDefKind::Resource => uwriteln!(self.h_src, "class {name};"),
+                    TypeDefKind::Enum(e) => {
+                        uwriteln!(self.h_src, "enum class {name} : {};", int_repr(e.tag()));
+                    }
+                    TypeDefKind::Flags(f) => {
+                        let repr = int_repr(flags_repr(f));
+                        uwriteln!(self.h_src, "enum class {name} : {repr};");
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        for id in types {
+            let name = self.resolve.types[id].name.clone().unwrap();
+            self.define_type(&name, id);
+        }
+    }
+
+    /// Returns the name of the named type `id` within its namespace.
+    fn local_name(&self, id: TypeId) -> String {
+        let name = &self.gen.type_names[&id];
+        name.rsplit("::").next().unwrap().to_string()
+    }
+
+    fn type_using(&mut self, id: TypeId, docs: &Docs) {
+        let name = self.local_name(id);
+        let ty = match &self.resolve.types[id].kind {
+            TypeDefKind::Type(t) => self.gen.type_name(self.resolve, t),
+            _ => self.gen.type_name(self.resolve, &Type::Id(id)),
+        };
+        self.h_src.push_str("\n");
+        self.docs(docs);
+        uwriteln!(self.h_src, "using {name} = {ty};");
+    }
+
+    /// Returns the methods, static functions and constructor of `resource`.
+    fn resource_funcs(&self, resource: TypeId) -> Vec<&'a Function> {
+        let funcs: Vec<&'a Function> = match self.interface {
+            Some((id, _)) => self.resolve.interfaces[id].functions.values().collect(),
+            None => {
+                let world = &self.resolve.worlds[self.gen.world_id.unwrap()];
+                let items = if self.in_import {
+                    &world.imports
+                } else {
+                    &world.exports
+                };
+                items
+                    .values()
+                    .filter_map(|item| match item {
+                        WorldItem::Function(func) => Some(func),
+                        _ => None,
+                    })
+                    .collect()
+            }
+        };
+        funcs
+            .into_iter()
+            .filter(|func| match func.kind {
+                FunctionKind::Method(id)
+                | FunctionKind::Static(id)
+                | FunctionKind::Constructor(id) => id == resource,
+                FunctionKind::Freestanding => false,
+            })
+            .collect()
+    }
+
+    fn sig(&self, func: &Function) -> CppSig {
+        let name = match &func.kind {
+            FunctionKind::Constructor(id) if self.in_import => self.local_name(*id),
+            FunctionKind::Constructor(_) => "create".to_string(),
+            _ => to_c_ident(func.item_name()),
+        };
+        let ret = match &func.kind {
+            FunctionKind::Constructor(_) if self.in_import => String::new(),
+            _ => self.results_type(func),
+        };
+        let skip = match &func.kind {
+            FunctionKind::Method(_) => 1,
+            _ => 0,
+        };
+        let params = func
+            .params
+            .iter()
+            .skip(skip)
+            .map(|(name, ty)| {
+                let ty = if self.in_import {
+                    self.gen.import_param_type(self.resolve, ty)
+                } else {
+                    self.gen.export_param_type(self.resolve, ty)
+                };
+                (ty, to_c_ident(name))
+            })
+            .collect();
+        CppSig { name, ret, params }
+    }
+
+    fn results_type(&self, func: &Function) -> String {
+        let mut types = func
+            .results
+            .iter_types()
+            .map(|ty| self.gen.type_name(self.resolve, ty))
+            .collect::<Vec<_>>();
+        match types.len() {
+            0 => "void".to_string(),
+            1 => types.pop().unwrap(),
+            _ => format!("std::tuple<{}>", types.join(", ")),
+        }
+    }
+
+    fn deprecated(&self, func: &Function) -> String {
+        match deprecation(&func.docs) {
+            Some("") => "[[deprecated]] ".to_string(),
+            Some(note) => format!("[[deprecated({note:?})]] "),
+            None => String::new(),
+        }
+    }
+
+    fn docs(&mut self, docs: &Docs) {
+        push_docs(&mut self.h_src, docs);
+    }
+
+    fn import(&mut self, func: &Function) {
+        let sig = self.sig(func);
+        let params = sig.params();
+
+        // Members of resources are declared by the class itself.
+        if let FunctionKind::Freestanding = func.kind {
+            self.h_src.push_str("\n");
+            self.docs(&func.docs);
+            let deprecated = self.deprecated(func);
+            uwriteln!(
+                self.h_src,
+                "{deprecated}{} {}({params});",
+                sig.ret,
+                sig.name
+            );
+        }
+
+        // Declare the raw wasm import that is called by the definition of the
+        // function below.
+        let wasm_sig = self.resolve.wasm_signature(AbiVariant::GuestImport, func);
+        let flat_ns = self.namespace.replace("::", "_");
+        let import_name = self.gen.names.tmp(&format!(
+            "__wasm_import_{flat_ns}_{}",
+            func.name.to_snake_case()
+        ));
+        uwriteln!(
+            self.cpp_fns,
+            "\nextern \"C\" __attribute__((__import_module__(\"{}\"), __import_name__(\"{}\")))",
+            self.module,
+            func.name
+        );
+        uwriteln!(
+            self.cpp_fns,
+            "{} {import_name}({});",
+            wasm_results_type(&wasm_sig),
+            wasm_sig
+                .params
+                .iter()
+                .map(|ty| wasm_type(*ty))
+                .collect::<Vec<_>>()
+                .join(", "),
+        );
+
+        let head = match &func.kind {
+            FunctionKind::Freestanding => format!("{} {}({params})", sig.ret, sig.name),
+            FunctionKind::Method(id) => {
+                let class = self.local_name(*id);
+                format!("{} {class}::{}({params}) const", sig.ret, sig.name)
+            }
+            FunctionKind::Static(id) => {
+                let class = self.local_name(*id);
+                format!("{} {class}::{}({params})", sig.ret, sig.name)
+            }
+            FunctionKind::Constructor(id) => {
+                let class = self.local_name(*id);
+                format!("{class}::{class}({params}) : handle_(-1), owned_(true)")
+            }
+        };
+
+        let mut f = FunctionBindgen::new(self, import_name, sig.ret.clone());
+        if let FunctionKind::Method(_) = func.kind {
+            f.params.push("(*this)".to_string());
+        }
+        for (_, name) in sig.params.iter() {
+            f.locals.insert(name).unwrap();
+            f.params.push(name.clone());
+        }
+        abi::call(
+            f.gen.resolve,
+            AbiVariant::GuestImport,
+            LiftLower::LowerArgsLiftResults,
+            func,
+            &mut f,
+        );
+        let src = f.finish();
+
+        uwriteln!(self.cpp_fns, "\n{head} {{");
+        self.cpp_fns.push_str(&src);
+        uwriteln!(self.cpp_fns, "}}");
+    }
+
+    fn export(&mut self, func: &Function) {
+        let sig = self.sig(func);
+
+        // Freestanding functions are methods of the `Guest` class while
+        // members of resources are declared by the class itself.
+        if let FunctionKind::Freestanding = func.kind {
+            push_docs(&mut self.guest_methods, &func.docs);
+            uwriteln!(
+                self.guest_methods,
+                "virtual {} {}({}) = 0;",
+                sig.ret,
+                sig.name,
+                sig.params()
+            );
+        }
+
+        let wasm_sig = self.resolve.wasm_signature(AbiVariant::GuestExport, func);
+        let core_module_name = self
+            .interface
+            .map(|(_, key)| self.resolve.name_world_key(key));
+        let export_name = func.core_export_name(core_module_name.as_deref());
+        let flat_ns = self.namespace.replace("::", "_");
+        let export_fn = self.gen.names.tmp(&format!(
+            "__wasm_export_{flat_ns}_{}",
+            func.name.to_snake_case()
+        ));
+
+        let mut f = FunctionBindgen::new(self, sig.name.clone(), sig.ret.clone());
+        let mut params = Vec::new();
+        for ty in wasm_sig.params.iter() {
+            let name = f.locals.tmp("arg");
+            params.push(format!("{} {name}", wasm_type(*ty)));
+            f.params.push(name);
+        }
+        abi::call(
+            f.gen.resolve,
+            AbiVariant::GuestExport,
+            LiftLower::LiftArgsLowerResults,
+            func,
+            &mut f,
+        );
+        let src = f.finish();
+
+        uwriteln!(
+            self.cpp_adapters,
+            "\nextern \"C\" __attribute__((__export_name__(\"{export_name}\")))"
+        );
+        uwriteln!(
+            self.cpp_adapters,
+            "{} {export_fn}({}) {{",
+            wasm_results_type(&wasm_sig),
+            params.join(", ")
+        );
+        self.cpp_adapters.push_str(&src);
+        uwriteln!(self.cpp_adapters, "}}");
+
+        if abi::guest_export_needs_post_return(self.resolve, func) {
+            let mut f = FunctionBindgen::new(self, String::new(), String::new());
+            let mut params = Vec::new();
+            for (i, ty) in wasm_sig.results.iter().enumerate() {
+                let name = format!("arg{i}");
+                f.locals.insert(&name).unwrap();
+                params.push(format!("{} {name}", wasm_type(*ty)));
+                f.params.push(name);
+            }
+            abi::post_return(f.gen.resolve, func, &mut f);
+            let src = f.finish();
+
+            uwriteln!(
+                self.cpp_adapters,
+                "\nextern \"C\" __attribute__((__weak__, __export_name__(\"cabi_post_{export_name}\")))"
+            );
+            uwriteln!(
+                self.cpp_adapters,
+                "void {export_fn}_post_return({}) {{",
+                params.join(", ")
+            );
+            self.cpp_adapters.push_str(&src);
+            uwriteln!(self.cpp_adapters, "}}");
+        }
+    }
+}
+
+struct FunctionBindgen<'a, 'b> {
+    gen: &'a mut InterfaceGenerator<'b>,
+    locals: Ns,
+    src: Source,
+    /// The raw wasm import called by imports, or the name of the function
+    /// implementing an export.
+    callee: String,
+    /// Return type of the C++ function, used to return multiple results.
+    ret: String,
+    block_storage: Vec<Source>,
+    blocks: Vec<(String, Vec<String>)>,
+    payloads: Vec<String>,
+    params: Vec<String>,
+    import_return_pointer_area: Option<String>,
+    import_return_pointer_area_size: usize,
+    import_return_pointer_area_align: usize,
+
+    /// Allocations of lists passed to an import, which are freed once the
+    /// import returns.
+    cleanup: Option<String>,
+
+    /// Storage for the handles of imported resources borrowed by an export,
+    /// which must outlive the call to the export.
+    borrows: HashMap<TypeId, String>,
+}
+
+impl<'a, 'b> FunctionBindgen<'a, 'b> {
+    fn new(
+        gen: &'a mut InterfaceGenerator<'b>,
+        callee: String,
+        ret: String,
+    ) -> FunctionBindgen<'a, 'b> {
+        FunctionBindgen {
+            gen,
+            locals: Default::default(),
+            src: Default::default(),
+            callee,
+            ret,
+            block_storage: Vec::new(),
+            blocks: Vec::new(),
+            payloads: Vec::new(),
+            params: Vec::new(),
+            import_return_pointer_area: None,
+            import_return_pointer_area_size: 0,
+            import_return_pointer_area_align: 0,
+            cleanup: None,
+            borrows: HashMap::new(),
+        }
+    }
+
+    /// Returns the body of the function, preceded by the declarations of any
+    /// storage it needs.
+    fn finish(self) -> String {
+        let mut decls = String::new();
+        if let Some(ret_area) = &self.import_return_pointer_area {
+            uwriteln!(
+                decls,
+                "alignas({}) uint8_t {ret_area}[{}];",
+                self.import_return_pointer_area_align,
+                self.import_return_pointer_area_size,
+            );
+        }
+        if let Some(cleanup) = &self.cleanup {
+            uwriteln!(decls, "std::vector<void*> {cleanup};");
+        }
+        let mut borrows = self.borrows.iter().collect::<Vec<_>>();
+        borrows.sort_by(|a, b| a.1.cmp(b.1));
+        for (resource, name) in borrows {
+            let class = &self.gen.gen.type_names[resource];
+            uwriteln!(decls, "std::deque<{class}> {name};");
+        }
+        decls.push_str(&self.src);
+        decls
+    }
+
+    fn type_name(&self, ty: &Type) -> String {
+        self.gen.gen.type_name(self.gen.resolve, ty)
+    }
+
+    /// Moves out of `op` if it names a local rather than being a temporary
+    /// or one of the core wasm arguments.
+    fn move_op(&self, op: &str) -> String {
+        let is_local = op.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && op.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+            && !self.params.iter().any(|p| p == op);
+        if is_local {
+            format!("std::move({op})")
+        } else {
+            op.to_string()
+        }
+    }
+
+    fn load(&mut self, ty: &str, offset: i32, operands: &[String], results: &mut Vec<String>) {
+        results.push(format!("*(({}*) ({} + {}))", ty, operands[0], offset));
+    }
+
+    fn load_ext(&mut self, ty: &str, offset: i32, operands: &[String], results: &mut Vec<String>) {
+        self.load(ty, offset, operands, results);
+        let result = results.pop().unwrap();
+        results.push(format!("(int32_t) ({})", result));
+    }
+
+    fn store(&mut self, ty: &str, offset: i32, operands: &[String]) {
+        uwriteln!(
+            self.src,
+            "*(({}*)({} + {})) = {};",
+            ty,
+            operands[1],
+            offset,
+            operands[0]
+        );
+    }
+
+    /// Declares the results of lowering a variant-like type, which are then
+    /// assigned at the end of each of `blocks`.
+    fn variant_results(
+        &mut self,
+        prefix: &str,
+        result_types: &[WasmType],
+        blocks: &mut [(String, Vec<String>)],
+        results: &mut Vec<String>,
+    ) {
+        for (i, ty) in result_types.iter().enumerate() {
+            let name = self.locals.tmp(prefix);
+            uwriteln!(self.src, "{} {name};", wasm_type(*ty));
+            for (block, block_results) in blocks.iter_mut() {
+                uwriteln!(block, "{name} = {};", block_results[i]);
+            }
+            results.push(name);
+        }
+    }
+
+    /// Copies the `len` elements of `size` bytes of the list `op` to a new
+    /// allocation which is owned by the receiver of the list.
+    fn list_copy(&mut self, op: &str, size: usize, results: &mut Vec<String>) {
+        let vec = self.locals.tmp("vec");
+        let ptr = self.locals.tmp("ptr");
+        let len = self.locals.tmp("len");
+        self.src.push_str(&format!(
+            "
+            auto&& {vec} = {op};
+            size_t {len} = {vec}.size();
+            uint8_t *{ptr} = nullptr;
+            if ({len} > 0) {{
+            {ptr} = (uint8_t *) malloc({len} * {size});
+            memcpy({ptr}, {vec}.data(), {len} * {size});
+            }}
+            "
+        ));
+        results.push(ptr);
+        results.push(len);
+    }
+}
+
+impl Bindgen for FunctionBindgen<'_, '_> {
+    type Operand = String;
+
+    fn sizes(&self) -> &SizeAlign {
+        &self.gen.gen.sizes
+    }
+
+    fn push_block(&mut self) {
+        let prev = mem::take(&mut self.src);
+        self.block_storage.push(prev);
+    }
+
+    fn finish_block(&mut self, operands: &mut Vec<String>) {
+        let to_restore = self.block_storage.pop().unwrap();
+        let src = mem::replace(&mut self.src, to_restore);
+        self.blocks.push((src.into(), mem::take(operands)));
+    }
+
+    fn return_pointer(&mut self, size: usize, align: usize) -> String {
+        let ptr = self.locals.tmp("ptr");
+
+        // Use a stack-based return area for imports, because exports need
+        // their return area to be live until the post-return call.
+        if self.gen.in_import {
+            self.import_return_pointer_area_size = self.import_return_pointer_area_size.max(size);
+            self.import_return_pointer_area_align =
+                self.import_return_pointer_area_align.max(align);
+            let ret_area = match &self.import_return_pointer_area {
+                Some(ret_area) => ret_area.clone(),
+                None => {
+                    let ret_area = self.locals.tmp("ret_area");
+                    self.import_return_pointer_area = Some(ret_area.clone());
+                    ret_area
+                }
+            };
+            uwriteln!(self.src, "uint8_t *{ptr} = (uint8_t *) &{ret_area};");
+        } else {
+            self.gen.gen.return_pointer_area_size = self.gen.gen.return_pointer_area_size.max(size);
+            self.gen.gen.return_pointer_area_align =
+                self.gen.gen.return_pointer_area_align.max(align);
+            uwriteln!(self.src, "uint8_t *{ptr} = (uint8_t *) &RET_AREA;");
+        }
+
+        ptr
+    }
+
+    fn is_list_canonical(&self, resolve: &Resolve, ty: &Type) -> bool {
+        match ty {
+            Type::U8
+            | Type::S8
+            | Type::U16
+            | Type::S16
+            | Type::U32
+            | Type::S32
+            | Type::U64
+            | Type::S64
+            | Type::F32
+            | Type::F64 => true,
+            Type::Id(id) => match &resolve.types[*id].kind {
+                TypeDefKind::Type(t) => self.is_list_canonical(resolve, t),
+                _ => false,
+            },
+            Type::Bool | Type::Char | Type::String => false,
+        }
+    }
+
+    fn emit(
+        &mut self,
+        resolve: &Resolve,
+        inst: &Instruction<'_>,
+        operands: &mut Vec<String>,
+        results: &mut Vec<String>,
+    ) {
+        match inst {
+            Instruction::GetArg { nth } => results.push(self.params[*nth].clone()),
+            Instruction::I32Const { val } => results.push(val.to_string()),
+            Instruction::ConstZero { tys } => {
+                for _ in tys.iter() {
+                    results.push("0".to_string());
+                }
+            }
+
+            Instruction::U8FromI32 => results.push(format!("(uint8_t) ({})", operands[0])),
+            Instruction::S8FromI32 => results.push(format!("(int8_t) ({})", operands[0])),
+            Instruction::U16FromI32 => results.push(format!("(uint16_t) ({})", operands[0])),
+            Instruction::S16FromI32 => results.push(format!("(int16_t) ({})", operands[0])),
+            Instruction::U32FromI32 => results.push(format!("(uint32_t) ({})", operands[0])),
+            Instruction::S32FromI32 | Instruction::S64FromI64 => results.push(operands[0].clone()),
+            Instruction::U64FromI64 => results.push(format!("(uint64_t) ({})", operands[0])),
+
+            Instruction::I32FromU8
+            | Instruction::I32FromS8
+            | Instruction::I32FromU16
+            | Instruction::I32FromS16
+            | Instruction::I32FromU32
+            | Instruction::I32FromChar
+            | Instruction::I32FromBool => {
+                results.push(format!("(int32_t) ({})", operands[0]));
+            }
+            Instruction::I32FromS32 | Instruction::I64FromS64 => results.push(operands[0].clone()),
+            Instruction::I64FromU64 => {
+                results.push(format!("(int64_t) ({})", operands[0]));
+            }
+
+            Instruction::CoreF32FromF32
+            | Instruction::CoreF64FromF64
+            | Instruction::F32FromCoreF32
+            | Instruction::F64FromCoreF64 => {
+                results.push(operands[0].clone());
+            }
+
+            Instruction::CharFromI32 => {
+                results.push(format!("(char32_t) ({})", operands[0]));
+            }
+            Instruction::BoolFromI32 => {
+                results.push(format!("(bool) ({})", operands[0]));
+            }
+
+            Instruction::Bitcasts { casts } => {
+                for (cast, op) in casts.iter().zip(operands) {
+                    results.push(perform_cast(op, cast));
+                }
+            }
+
+            Instruction::RecordLower { record, .. } => {
+                let op = &operands[0];
+                for f in record.fields.iter() {
+                    results.push(format!("({}).{}", op, to_c_ident(&f.name)));
+                }
+            }
+            Instruction::RecordLift { ty, .. } => {
+                let name = self.type_name(&Type::Id(*ty));
+                let fields = operands
+                    .iter()
+                    .map(|op| self.move_op(op))
+                    .collect::<Vec<_>>();
+                results.push(format!("{name}{{{}}}", fields.join(", ")));
+            }
+
+            Instruction::TupleLower { tuple, .. } => {
+                let op = &operands[0];
+                for i in 0..tuple.types.len() {
+                    results.push(format!("std::get<{i}>({op})"));
+                }
+            }
+            Instruction::TupleLift { ty, .. } => {
+                let name = self.type_name(&Type::Id(*ty));
+                let fields = operands
+                    .iter()
+                    .map(|op| self.move_op(op))
+                    .collect::<Vec<_>>();
+                results.push(format!("{name}({})", fields.join(", ")));
+            }
+
+            Instruction::HandleLower { handle, .. } => {
+                let op = &operands[0];
+                let resource = match handle {
+                    Handle::Own(id) | Handle::Borrow(id) => dealias(resolve, *id),
+                };
+                let class = &self.gen.gen.type_names[&resource];
+                match (handle, self.gen.gen.resources[&resource]) {
+                    (Handle::Own(_), Direction::Import) => {
+                        results.push(format!("({op}).into_handle()"));
+                    }
+                    (Handle::Own(_), Direction::Export) => {
+                        results.push(format!("{class}::into_handle({})", self.move_op(op)));
+                    }
+                    (Handle::Borrow(_), Direction::Import) => {
+                        results.push(format!("static_cast<{class} const&>({op}).handle()"));
+                    }
+                    // Exports only receive borrows, so they're never lowered.
+                    (Handle::Borrow(_), Direction::Export) => unreachable!(),
+                }
+            }
+
+            Instruction::HandleLift { handle, .. } => {
+                let op = &operands[0];
+                let resource = match handle {
+                    Handle::Own(id) | Handle::Borrow(id) => dealias(resolve, *id),
+                };
+                let class = self.gen.gen.type_names[&resource].clone();
+                match (handle, self.gen.gen.resources[&resource]) {
+                    (Handle::Own(_), _) => {
+                        results.push(format!("{class}::from_handle({op})"));
+                    }
+                    (Handle::Borrow(_), Direction::Import) => {
+                        // The object wrapping the borrowed handle is kept
+                        // alive until the export returns.
+                        let storage = match self.borrows.get(&resource) {
+                            Some(storage) => storage.clone(),
+                            None => {
+                                let storage = self.locals.tmp("borrows");
+                                self.borrows.insert(resource, storage.clone());
+                                storage
+                            }
+                        };
+                        let borrow = self.locals.tmp("borrow");
+                        uwriteln!(
+                            self.src,
+                            "auto& {borrow} = {storage}.emplace_back({class}::from_borrowed_handle({op}));"
+                        );
+                        results.push(format!("std::cref({borrow})"));
+                    }
+                    (Handle::Borrow(_), Direction::Export) => {
+                        // Borrows of resources exported by this component
+                        // are received as their representation.
+                        results.push(format!("std::ref(*({class} *) (uintptr_t) ({op}))"));
+                    }
+                }
+            }
+
+            Instruction::FutureLower { .. } | Instruction::StreamLower { .. } => {
+                results.push(format!("(int32_t) ({})", operands[0]));
+            }
+
+            Instruction::FutureLift { .. } | Instruction::StreamLift { .. } => {
+                results.push(format!("(uint32_t) ({})", operands[0]));
+            }
+
+            Instruction::FlagsLower { flags, .. } => match flags_repr(flags) {
+                Int::U8 | Int::U16 | Int::U32 => {
+                    results.push(format!("(int32_t) ({})", operands[0]));
+                }
+                Int::U64 => {
+                    let op = &operands[0];
+                    results.push(format!("(int32_t) ((uint64_t) ({op}) & 0xffffffff)"));
+                    results.push(format!("(int32_t) ((uint64_t) ({op}) >> 32)"));
+                }
+            },
+
+            Instruction::FlagsLift { flags, ty, .. } => {
+                let name = self.type_name(&Type::Id(*ty));
+                match flags_repr(flags) {
+                    Int::U8 | Int::U16 | Int::U32 => {
+                        results.push(format!("({name}) ({})", operands[0]));
+                    }
+                    Int::U64 => {
+                        let op0 = &operands[0];
+                        let op1 = &operands[1];
+                        results.push(format!(
+                            "({name}) ((uint64_t) (uint32_t) ({op0}) | ((uint64_t) (uint32_t) ({op1}) << 32))"
+                        ));
+                    }
+                }
+            }
+
+            Instruction::VariantPayloadName => {
+                let name = self.locals.tmp("payload");
+                results.push(name.clone());
+                self.payloads.push(name);
+            }
+
+            Instruction::VariantLower {
+                variant,
+                results: result_types,
+                ..
+            } => {
+                let mut blocks = self
+                    .blocks
+                    .drain(self.blocks.len() - variant.cases.len()..)
+                    .collect::<Vec<_>>();
+                let payloads = self
+                    .payloads
+                    .drain(self.payloads.len() - variant.cases.len()..)
+                    .collect::<Vec<_>>();
+                self.variant_results("variant", result_types, &mut blocks, results);
+
+                let op0 = &operands[0];
+                uwriteln!(self.src, "switch (({op0}).variants.index()) {{");
+                for (i, ((case, (block, _)), payload)) in
+                    variant.cases.iter().zip(blocks).zip(payloads).enumerate()
+                {
+                    uwriteln!(self.src, "case {i}: {{");
+                    if case.ty.is_some() {
+                        uwriteln!(
+                            self.src,
+                            "auto& {payload} = std::get<{i}>(({op0}).variants).value;"
+                        );
+                    }
+                    self.src.push_str(&block);
+                    self.src.push_str("break;\n}\n");
+                }
+                self.src.push_str("}\n");
+            }
+
+            Instruction::VariantLift { variant, ty, .. } => {
+                let blocks = self
+                    .blocks
+                    .drain(self.blocks.len() - variant.cases.len()..)
+                    .collect::<Vec<_>>();
+
+                let name = self.type_name(&Type::Id(*ty));
+                let local_name = name.rsplit("::").next().unwrap();
+                let result = self.locals.tmp("variant");
+                uwriteln!(self.src, "std::optional<{name}> {result};");
+                uwriteln!(self.src, "switch ({}) {{", operands[0]);
+                for (i, (case, (block, block_results))) in
+                    variant.cases.iter().zip(blocks).enumerate()
+                {
+                    let case_name = variant_case_name(local_name, &case.name);
+                    uwriteln!(self.src, "case {i}: {{");
+                    self.src.push_str(&block);
+                    let payload = block_results
+                        .first()
+                        .map(|r| self.move_op(r))
+                        .unwrap_or_default();
+                    uwriteln!(
+                        self.src,
+                        "{result}.emplace({name}{{{name}::{case_name}{{{payload}}}}});"
+                    );
+                    self.src.push_str("break;\n}\n");
+                }
+                self.src.push_str("}\n");
+                results.push(format!("std::move(*{result})"));
+            }
+
+            Instruction::OptionLower {
+                results: result_types,
+                ..
+            } => {
+                let some = self.blocks.pop().unwrap();
+                let none = self.blocks.pop().unwrap();
+                let some_payload = self.payloads.pop().unwrap();
+                let _none_payload = self.payloads.pop().unwrap();
+                let mut blocks = [none, some];
+                self.variant_results("option", result_types, &mut blocks, results);
+                let [(none, _), (some, _)] = blocks;
+
+                let op0 = &operands[0];
+                self.src.push_str(&format!(
+                    "\
+                    if (({op0}).has_value()) {{
+                        auto& {some_payload} = *({op0});
+                        {some}}} else {{
+                        {none}}}
+                    "
+                ));
+            }
+
+            Instruction::OptionLift { payload, .. } => {
+                let (some, some_results) = self.blocks.pop().unwrap();
+                let (none, none_results) = self.blocks.pop().unwrap();
+                assert!(none_results.is_empty());
+                assert!(some_results.len() == 1);
+
+                let ty = self.type_name(payload);
+                let result = self.locals.tmp("option");
+                let some_result = self.move_op(&some_results[0]);
+                uwriteln!(self.src, "std::optional<{ty}> {result};");
+                self.src.push_str(&format!(
+                    "\
+                    if (({}) != 0) {{
+                        {some}\
+                        {result}.emplace({some_result});
+                    }}
+                    ",
+                    operands[0]
+                ));
+                if !none.is_empty() {
+                    uwriteln!(self.src, "else {{\n{none}}}");
+                }
+                results.push(result);
+            }
+
+            Instruction::ResultLower {
+                results: result_types,
+                result,
+                ..
+            } => {
+                let err = self.blocks.pop().unwrap();
+                let ok = self.blocks.pop().unwrap();
+                let err_payload = self.payloads.pop().unwrap();
+                let ok_payload = self.payloads.pop().unwrap();
+                let mut blocks = [ok, err];
+                self.variant_results("result", result_types, &mut blocks, results);
+                let [(ok, _), (err, _)] = blocks;
+
+                let op0 = &operands[0];
+                let bind_ok = if result.ok.is_some() {
+                    format!("auto& {ok_payload} = *({op0});\n")
+                } else {
+                    String::new()
+                };
+                let bind_err = if result.err.is_some() {
+                    format!("auto& {err_payload} = ({op0}).error();\n")
+                } else {
+                    String::new()
+                };
+                self.src.push_str(&format!(
+                    "\
+                    if (({op0}).has_value()) {{
+                        {bind_ok}\
+                        {ok}\
+                    }} else {{
+                        {bind_err}\
+                        {err}\
+                    }}
+                    "
+                ));
+            }
+
+            Instruction::ResultLift { result, ty, .. } => {
+                let (err, err_results) = self.blocks.pop().unwrap();
+                assert!(err_results.len() == (result.err.is_some() as usize));
+                let (ok, ok_results) = self.blocks.pop().unwrap();
+                assert!(ok_results.len() == (result.ok.is_some() as usize));
+
+                let ty = self.type_name(&Type::Id(*ty));
+                let result_tmp = self.locals.tmp("result");
+                let ok_value = match ok_results.first() {
+                    Some(value) => format!(", {}", self.move_op(value)),
+                    None => String::new(),
+                };
+                let err_value = match err_results.first() {
+                    Some(value) => format!(", {}", self.move_op(value)),
+                    None => String::new(),
+                };
+                uwriteln!(self.src, "std::optional<{ty}> {result_tmp};");
+                self.src.push_str(&format!(
+                    "\
+                    if (({}) == 0) {{
+                        {ok}\
+                        {result_tmp}.emplace(std::in_place{ok_value});
+                    }} else {{
+                        {err}\
+                        {result_tmp}.emplace(std::unexpect{err_value});
+                    }}
+                    ",
+                    operands[0]
+                ));
+                results.push(format!("std::move(*{result_tmp})"));
+            }
+
+            Instruction::EnumLower { .. } => results.push(format!("(int32_t) ({})", operands[0])),
+            Instruction::EnumLift { ty, .. } => {
+                let name = self.type_name(&Type::Id(*ty));
+                results.push(format!("({name}) ({})", operands[0]));
+            }
+
+            Instruction::ListCanonLower { element, realloc } => match realloc {
+                None => {
+                    results.push(format!("(uint8_t *) ({}).data()", operands[0]));
+                    results.push(format!("({}).size()", operands[0]));
+                }
+                Some(_) => {
+                    let size = self.gen.gen.sizes.size(element);
+                    self.list_copy(&operands[0], size, results);
+                }
+            },
+            Instruction::StringLower { realloc } => match realloc {
+                None => {
+                    results.push(format!("(uint8_t *) ({}).data()", operands[0]));
+                    results.push(format!("({}).size()", operands[0]));
+                }
+                Some(_) => self.list_copy(&operands[0], 1, results),
+            },
+
+            Instruction::ListCanonLift { element, .. } => {
+                let elem = self.type_name(element);
+                let ptr = self.locals.tmp("ptr");
+                let len = self.locals.tmp("len");
+                let list = self.locals.tmp("list");
+                self.src.push_str(&format!(
+                    "
+                    uint8_t *{ptr} = {};
+                    size_t {len} = {};
+                    std::vector<{elem}> {list}(({elem} *) {ptr}, ({elem} *) {ptr} + {len});
+                    if ({len} > 0) {{
+                    free({ptr});
+                    }}
+                    ",
+                    operands[0], operands[1],
+                ));
+                results.push(list);
+            }
+            Instruction::StringLift => {
+                let ptr = self.locals.tmp("ptr");
+                let len = self.locals.tmp("len");
+                let string = self.locals.tmp("string");
+                self.src.push_str(&format!(
+                    "
+                    uint8_t *{ptr} = {};
+                    size_t {len} = {};
+                    std::string {string}((char const *) {ptr}, {len});
+                    if ({len} > 0) {{
+                    free({ptr});
+                    }}
+                    ",
+                    operands[0], operands[1],
+                ));
+                results.push(string);
+            }
+
+            Instruction::ListLower { element, realloc } => {
+                let (body, _) = self.blocks.pop().unwrap();
+                let size = self.gen.gen.sizes.size(element);
+                let vec = self.locals.tmp("vec");
+                let len = self.locals.tmp("len");
+                let result = self.locals.tmp("result");
+                self.src.push_str(&format!(
+                    "
+                    auto&& {vec} = {};
+                    size_t {len} = {vec}.size();
+                    uint8_t *{result} = nullptr;
+                    if ({len} > 0) {{
+                    {result} = (uint8_t *) malloc({len} * {size});
+                    }}
+                    ",
+                    operands[0],
+                ));
+                // Imports don't take ownership of their arguments so the list
+                // is freed once the import returns.
+                if realloc.is_none() {
+                    let cleanup = match &self.cleanup {
+                        Some(cleanup) => cleanup.clone(),
+                        None => {
+                            let cleanup = self.locals.tmp("cleanup");
+                            self.cleanup = Some(cleanup.clone());
+                            cleanup
+                        }
+                    };
+                    uwriteln!(self.src, "{cleanup}.push_back({result});");
+                }
+                self.src.push_str(&format!(
+                    "
+                    for (size_t i = 0; i < {len}; i++) {{
+                    uint8_t *base = {result} + i * {size};
+                    (void) base;
+                    auto&& e = {vec}[i];
+                    (void) e;
+                    {body}\
+                    }}
+                    "
+                ));
+                results.push(result);
+                results.push(len);
+            }
+
+            Instruction::ListLift { element, .. } => {
+                let (body, body_results) = self.blocks.pop().unwrap();
+                let size = self.gen.gen.sizes.size(element);
+                let elem = self.type_name(element);
+                let ptr = self.locals.tmp("ptr");
+                let len = self.locals.tmp("len");
+                let result = self.locals.tmp("result");
+                let value = self.move_op(&body_results[0]);
+                self.src.push_str(&format!(
+                    "
+                    uint8_t *{ptr} = {};
+                    size_t {len} = {};
+                    std::vector<{elem}> {result};
+                    {result}.reserve({len});
+                    for (size_t i = 0; i < {len}; i++) {{
+                    uint8_t *base = {ptr} + i * {size};
+                    (void) base;
+                    {body}\
+                    {result}.push_back({value});
+                    }}
+                    if ({len} > 0) {{
+                    free({ptr});
+                    }}
+                    ",
+                    operands[0], operands[1],
+                ));
+                results.push(result);
+            }
+            Instruction::IterElem { .. } => results.push("e".to_string()),
+            Instruction::IterBasePointer => results.push("base".to_string()),
+
+            Instruction::CallWasm { sig, .. } => {
+                match sig.results.len() {
+                    0 => {}
+                    1 => {
+                        let ret = self.locals.tmp("ret");
+                        uwrite!(self.src, "{} {ret} = ", wasm_type(sig.results[0]));
+                        results.push(ret);
+                    }
+                    _ => unimplemented!("multi-value return not supported"),
+                }
+                uwriteln!(self.src, "{}({});", self.callee, operands.join(", "));
+                if let Some(cleanup) = &self.cleanup {
+                    uwriteln!(self.src, "for (void *ptr : {cleanup}) {{");
+                    uwriteln!(self.src, "free(ptr);");
+                    uwriteln!(self.src, "}}");
+                }
+            }
+
+            Instruction::CallInterface { func } => {
+                let (this, args) = match &func.kind {
+                    FunctionKind::Method(_) => (Some(&operands[0]), &operands[1..]),
+                    _ => (None, &operands[..]),
+                };
+                let args = args.iter().map(|op| self.move_op(op)).collect::<Vec<_>>();
+                let args = args.join(", ");
+                let callee = &self.callee;
+                let call = match &func.kind {
+                    FunctionKind::Freestanding => {
+                        format!("::{}::guest().{callee}({args})", self.gen.namespace)
+                    }
+                    FunctionKind::Method(id) => {
+                        let class = &self.gen.gen.type_names[id];
+                        format!("static_cast<{class}&>({}).{callee}({args})", this.unwrap())
+                    }
+                    FunctionKind::Static(id) | FunctionKind::Constructor(id) => {
+                        let class = &self.gen.gen.type_names[id];
+                        format!("{class}::{callee}({args})")
+                    }
+                };
+                match func.results.len() {
+                    0 => uwriteln!(self.src, "{call};"),
+                    1 => {
+                        let ret = self.locals.tmp("ret");
+                        uwriteln!(self.src, "auto {ret} = {call};");
+                        results.push(ret);
+                    }
+                    n => {
+                        let rets = (0..n).map(|_| self.locals.tmp("ret")).collect::<Vec<_>>();
+                        uwriteln!(self.src, "auto [{}] = {call};", rets.join(", "));
+                        results.extend(rets);
+                    }
+                }
+            }
+
+            Instruction::Return { amt, func } if self.gen.in_import => match amt {
+                0 => {}
+                1 => match func.kind {
+                    FunctionKind::Constructor(_) => {
+                        uwriteln!(self.src, "*this = {};", operands[0]);
+                    }
+                    _ => uwriteln!(self.src, "return {};", operands[0]),
+                },
+                _ => {
+                    let values = operands
+                        .iter()
+                        .map(|op| self.move_op(op))
+                        .collect::<Vec<_>>();
+                    uwriteln!(self.src, "return {}({});", self.ret, values.join(", "));
+                }
+            },
+            Instruction::Return { amt, .. } => {
+                assert!(*amt <= 1);
+                if *amt == 1 {
+                    uwriteln!(self.src, "return {};", operands[0]);
+                }
+            }
+
+            Instruction::I32Load { offset } => self.load("int32_t", *offset, operands, results),
+            Instruction::I64Load { offset } => self.load("int64_t", *offset, operands, results),
+            Instruction::F32Load { offset } => self.load("float", *offset, operands, results),
+            Instruction::F64Load { offset } => self.load("double", *offset, operands, results),
+            Instruction::PointerLoad { offset } => {
+                self.load("uint8_t *", *offset, operands, results)
+            }
+            Instruction::LengthLoad { offset } => self.load("size_t", *offset, operands, results),
+            Instruction::I32Store { offset } => self.store("int32_t", *offset, operands),
+            Instruction::I64Store { offset } => self.store("int64_t", *offset, operands),
+            Instruction::F32Store { offset } => self.store("float", *offset, operands),
+            Instruction::F64Store { offset } => self.store("double", *offset, operands),
+            Instruction::I32Store8 { offset } => self.store("int8_t", *offset, operands),
+            Instruction::I32Store16 { offset } => self.store("int16_t", *offset, operands),
+            Instruction::PointerStore { offset } => self.store("uint8_t *", *offset, operands),
+            Instruction::LengthStore { offset } => self.store("size_t", *offset, operands),
+
+            Instruction::I32Load8U { offset } => {
+                self.load_ext("uint8_t", *offset, operands, results)
+            }
+            Instruction::I32Load8S { offset } => {
+                self.load_ext("int8_t", *offset, operands, results)
+            }
+            Instruction::I32Load16U { offset } => {
+                self.load_ext("uint16_t", *offset, operands, results)
+            }
+            Instruction::I32Load16S { offset } => {
+                self.load_ext("int16_t", *offset, operands, results)
+            }
+
+            Instruction::GuestDeallocate { .. } => {
+                uwriteln!(self.src, "free({});", operands[0]);
+            }
+            Instruction::GuestDeallocateString => {
+                uwriteln!(self.src, "if (({}) > 0) {{", operands[1]);
+                uwriteln!(self.src, "free({});", operands[0]);
+                uwriteln!(self.src, "}}");
+            }
+            Instruction::GuestDeallocateVariant { blocks } => {
+                let blocks = self
+                    .blocks
+                    .drain(self.blocks.len() - blocks..)
+                    .collect::<Vec<_>>();
+
+                uwriteln!(self.src, "switch ((int32_t) {}) {{", operands[0]);
+                for (i, (block, results)) in blocks.into_iter().enumerate() {
+                    assert!(results.is_empty());
+                    uwriteln!(self.src, "case {}: {{", i);
+                    self.src.push_str(&block);
+                    self.src.push_str("break;\n}\n");
+                }
+                self.src.push_str("}\n");
+            }
+            Instruction::GuestDeallocateList { element } => {
+                let (body, results) = self.blocks.pop().unwrap();
+                assert!(results.is_empty());
+                let len = self.locals.tmp("len");
+                uwriteln!(self.src, "size_t {len} = {};", operands[1]);
+                uwriteln!(self.src, "if ({len} > 0) {{");
+                let ptr = self.locals.tmp("ptr");
+                uwriteln!(self.src, "uint8_t *{ptr} = {};", operands[0]);
+                let i = self.locals.tmp("i");
+                uwriteln!(self.src, "for (size_t {i} = 0; {i} < {len}; {i}++) {{");
+                let size = self.gen.gen.sizes.size(element);
+                uwriteln!(self.src, "uint8_t *base = {ptr} + {i} * {size};");
+                uwriteln!(self.src, "(void) base;");
+                uwrite!(self.src, "{body}");
+                uwriteln!(self.src, "}}");
+                uwriteln!(self.src, "free({ptr});");
+                uwriteln!(self.src, "}}");
+            }
+
+            i => unimplemented!("{:?}", i),
+        }
+    }
+}
+
+fn perform_cast(op: &str, cast: &Bitcast) -> String {
+    match cast {
+        Bitcast::I32ToF32 | Bitcast::I64ToF32 => {
+            format!("std::bit_cast<float>((int32_t) ({op}))")
+        }
+        Bitcast::F32ToI32 => format!("std::bit_cast<int32_t>({op})"),
+        Bitcast::F32ToI64 => format!("(int64_t) std::bit_cast<uint32_t>({op})"),
+        Bitcast::I64ToF64 => format!("std::bit_cast<double>((int64_t) ({op}))"),
+        Bitcast::F64ToI64 => format!("std::bit_cast<int64_t>({op})"),
+        Bitcast::I32ToI64 | Bitcast::LToI64 | Bitcast::PToP64 => {
+            format!("(int64_t) ({op})")
+        }
+        Bitcast::I64ToI32 | Bitcast::I64ToL => format!("(int32_t) ({op})"),
+        // P64 is currently represented as int64_t, so no conversion is needed.
+        Bitcast::I64ToP64 | Bitcast::P64ToI64 => op.to_string(),
+        Bitcast::P64ToP | Bitcast::I32ToP | Bitcast::LToP => {
+            format!("(uint8_t *) (uintptr_t) ({op})")
+        }
+        Bitcast::PToI32 | Bitcast::PToL => format!("(uintptr_t) ({op})"),
+        Bitcast::I32ToL | Bitcast::LToI32 | Bitcast::None => op.to_string(),
+        Bitcast::Sequence(sequence) => {
+            let [first, second] = &**sequence;
+            let inner = perform_cast(op, first);
+            perform_cast(&inner, second)
+        }
+    }
+}
+
+fn to_upper_camel_case(name: &str) -> String {
+    match name {
+        // The name "Guest" is reserved for classes generated by exported
+        // interfaces, so remap types defined in wit to something else.
+        "guest" => "Guest_".to_string(),
+        s => s.to_upper_camel_case(),
+    }
+}
+
+/// Writes `docs` to `src` as line comments.
+fn push_docs(src: &mut Source, docs: &Docs) {
+    let docs = match &docs.contents {
+        Some(docs) => docs,
+        None => return,
+    };
+    for line in docs.trim().lines() {
+        src.push_str("// ");
+        src.push_str(line);
+        src.push_str("\n");
+    }
+}
+
+fn wasm_type(ty: WasmType) -> &'static str {
+    match ty {
+        WasmType::I32 => "int32_t",
+        WasmType::I64 => "int64_t",
+        WasmType::F32 => "float",
+        WasmType::F64 => "double",
+        WasmType::Pointer => "uint8_t *",
+        WasmType::PointerOrI64 => "int64_t",
+        WasmType::Length => "size_t",
+    }
+}
+
+fn wasm_results_type(sig: &WasmSignature) -> &'static str {
+    match sig.results.len() {
+        0 => "void",
+        1 => wasm_type(sig.results[0]),
+        _ => unimplemented!("multi-value return not supported"),
+    }
+}
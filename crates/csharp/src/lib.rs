@@ -11,6 +11,7 @@ use std::{
 };
 use wit_bindgen_core::{
     abi::{self, AbiVariant, Bindgen, Bitcast, Instruction, LiftLower, WasmType},
+    deprecation,
     wit_parser::LiveTypes,
     Direction,
 };
@@ -21,7 +22,7 @@ use wit_bindgen_core::{
         Resolve, Result_, SizeAlign, Tuple, Type, TypeDefKind, TypeId, TypeOwner, Variant, WorldId,
         WorldKey,
     },
-    Files, InterfaceGenerator as _, Ns, WorldGenerator,
+    Files, InterfaceGenerator as _, Ns, Types, WorldGenerator,
 };
 use wit_component::{StringEncoding, WitPrinter};
 mod csproj;
@@ -147,6 +148,7 @@ pub struct CSharp {
     interface_fragments: HashMap<String, InterfaceTypeAndFragments>,
     world_fragments: Vec<InterfaceFragment>,
     sizes: SizeAlign,
+    types: Types,
     interface_names: HashMap<InterfaceId, String>,
     anonymous_type_owners: HashMap<TypeId, TypeOwner>,
     all_resources: HashMap<TypeId, ResourceInfo>,
@@ -205,6 +207,7 @@ impl WorldGenerator for CSharp {
         let name = &resolve.worlds[world].name;
         self.name = name.to_string();
         self.sizes.fill(resolve);
+        self.types.analyze(resolve);
     }
 
     fn import_interface(
@@ -400,6 +403,8 @@ impl WorldGenerator for CSharp {
             // Generated by `wit-bindgen` {version}. DO NOT EDIT!
             // <auto-generated />
             #nullable enable
+            // Deprecated items are still used by the bindings themselves.
+            #pragma warning disable CS0612, CS0618
             "
         );
         let mut src = String::new();
@@ -1136,9 +1141,12 @@ impl InterfaceGenerator<'_> {
             "#,
         );
 
+        let obsolete = obsolete_attribute(&func.docs);
+
         uwrite!(
             target,
             r#"
+                {obsolete}
                 internal {extra_modifiers} {modifiers} unsafe {result_type} {camel_name}({params})
                 {{
                     {src}
@@ -1259,6 +1267,7 @@ impl InterfaceGenerator<'_> {
         let core_module_name = interface_name.map(|s| self.resolve.name_world_key(s));
         let export_name = func.core_export_name(core_module_name.as_deref());
         let access = self.gen.access_modifier();
+        let obsolete = obsolete_attribute(&func.docs);
 
         uwrite!(
             self.csharp_interop_src,
@@ -1286,7 +1295,8 @@ impl InterfaceGenerator<'_> {
         if !matches!(&func.kind, FunctionKind::Constructor(_)) {
             uwrite!(
                 self.src,
-                r#"{extra_modifiers} {modifiers} {result_type} {camel_name}({params});
+                r#"{obsolete}
+                {extra_modifiers} {modifiers} {result_type} {camel_name}({params});
 
             "#
             );
@@ -1348,7 +1358,7 @@ impl InterfaceGenerator<'_> {
                 match &ty.kind {
                     TypeDefKind::Type(ty) => self.type_name_with_qualifier(ty, qualifier),
                     TypeDefKind::List(ty) => {
-                        if is_primitive(ty) {
+                        if blittable_element(self.resolve, &self.gen.types, ty).is_some() {
                             format!("{}[]", self.type_name(ty))
                         } else {
                             format!("List<{}>", self.type_name_with_qualifier(ty, qualifier))
@@ -1405,6 +1415,8 @@ impl InterfaceGenerator<'_> {
                         let (Handle::Own(id) | Handle::Borrow(id)) = handle;
                         self.type_name_with_qualifier(&Type::Id(*id), qualifier)
                     }
+                    // Futures and streams are exposed as their raw handles.
+                    TypeDefKind::Future(_) | TypeDefKind::Stream(_) => "int".to_owned(),
                     _ => {
                         if let Some(name) = &ty.name {
                             format!(
@@ -1701,6 +1713,7 @@ impl<'a> wit_bindgen_core::InterfaceGenerator<'a> for InterfaceGenerator<'a> {
         let access = self.gen.access_modifier();
 
         self.print_docs(docs);
+        self.src.push_str(&obsolete_attribute(docs));
 
         let name = name.to_upper_camel_case();
 
@@ -1760,6 +1773,7 @@ impl<'a> wit_bindgen_core::InterfaceGenerator<'a> for InterfaceGenerator<'a> {
 
     fn type_flags(&mut self, _id: TypeId, name: &str, flags: &Flags, docs: &Docs) {
         self.print_docs(docs);
+        self.src.push_str(&obsolete_attribute(docs));
 
         let name = name.to_upper_camel_case();
 
@@ -1804,6 +1818,7 @@ impl<'a> wit_bindgen_core::InterfaceGenerator<'a> for InterfaceGenerator<'a> {
 
     fn type_variant(&mut self, _id: TypeId, name: &str, variant: &Variant, docs: &Docs) {
         self.print_docs(docs);
+        self.src.push_str(&obsolete_attribute(docs));
 
         let name = name.to_upper_camel_case();
         let tag_type = int_type(variant.tag());
@@ -1902,6 +1917,7 @@ impl<'a> wit_bindgen_core::InterfaceGenerator<'a> for InterfaceGenerator<'a> {
 
     fn type_enum(&mut self, _id: TypeId, name: &str, enum_: &Enum, docs: &Docs) {
         self.print_docs(docs);
+        self.src.push_str(&obsolete_attribute(docs));
 
         let name = name.to_upper_camel_case();
 
@@ -2184,7 +2200,7 @@ impl Bindgen for FunctionBindgen<'_, '_> {
 
     fn emit(
         &mut self,
-        _resolve: &Resolve,
+        resolve: &Resolve,
         inst: &Instruction<'_>,
         operands: &mut Vec<String>,
         results: &mut Vec<String>,
@@ -2534,7 +2550,8 @@ impl Bindgen for FunctionBindgen<'_, '_> {
 
             Instruction::ListCanonLower { element, realloc } => {
                 let list = &operands[0];
-                let (_size, ty) = list_element_info(element);
+                let element = blittable_element(resolve, &self.gen.gen.types, element).unwrap();
+                let (_size, ty) = list_element_info(&element);
 
                 match self.gen.direction {
                     Direction::Import => {
@@ -2553,7 +2570,7 @@ impl Bindgen for FunctionBindgen<'_, '_> {
                         let address = self.locals.tmp("address");
                         let buffer = self.locals.tmp("buffer");
                         let gc_handle = self.locals.tmp("gcHandle");
-                        let size = self.gen.gen.sizes.size(element);
+                        let size = self.gen.gen.sizes.size(&element);
                         uwrite!(
                             self.src,
                             "
@@ -2576,7 +2593,8 @@ impl Bindgen for FunctionBindgen<'_, '_> {
             }
 
             Instruction::ListCanonLift { element, .. } => {
-                let (_, ty) = list_element_info(element);
+                let element = blittable_element(resolve, &self.gen.gen.types, element).unwrap();
+                let (_, ty) = list_element_info(&element);
                 let array = self.locals.tmp("array");
                 let address = &operands[0];
                 let length = &operands[1];
@@ -2980,6 +2998,11 @@ impl Bindgen for FunctionBindgen<'_, '_> {
                 results.push(format!("{handle}"));
             }
 
+            Instruction::FutureLower { .. }
+            | Instruction::FutureLift { .. }
+            | Instruction::StreamLower { .. }
+            | Instruction::StreamLift { .. } => results.push(operands[0].clone()),
+
             Instruction::HandleLift {
                 handle,
                 ..
@@ -3120,8 +3143,8 @@ impl Bindgen for FunctionBindgen<'_, '_> {
         &self.gen.gen.sizes
     }
 
-    fn is_list_canonical(&self, _resolve: &Resolve, element: &Type) -> bool {
-        is_primitive(element)
+    fn is_list_canonical(&self, resolve: &Resolve, element: &Type) -> bool {
+        blittable_element(resolve, &self.gen.gen.types, element).is_some()
     }
 }
 
@@ -3366,6 +3389,29 @@ fn by_resource<'a>(
     by_resource
 }
 
+/// Returns the primitive type which lists of `ty` are copied to and from
+/// linear memory as, if any.
+///
+/// Lists of primitives, or of aliases of primitives, are blittable and
+/// represented as arrays which are copied in bulk rather than element by
+/// element.
+fn blittable_element(resolve: &Resolve, types: &Types, ty: &Type) -> Option<Type> {
+    match ty {
+        Type::Id(id) => {
+            let info = types.get(*id);
+            if !info.is_pod || info.max_depth > 0 {
+                return None;
+            }
+            match &resolve.types[dealias(resolve, *id)].kind {
+                TypeDefKind::Type(ty) => Some(*ty),
+                _ => None,
+            }
+        }
+        ty if is_primitive(ty) => Some(*ty),
+        _ => None,
+    }
+}
+
 /// Dereference any number `TypeDefKind::Type` aliases to retrieve the target type.
 fn dealias(resolve: &Resolve, mut id: TypeId) -> TypeId {
     loop {
@@ -3399,6 +3445,15 @@ fn payload_and_results(resolve: &Resolve, ty: Type) -> (Option<Type>, Vec<TypeId
     (payload, results)
 }
 
+/// Returns an `[Obsolete]` attribute if `docs` mark an item as deprecated.
+fn obsolete_attribute(docs: &Docs) -> String {
+    match deprecation(docs) {
+        Some("") => "[Obsolete]".to_string(),
+        Some(note) => format!("[Obsolete({note:?})]"),
+        None => String::new(),
+    }
+}
+
 fn extra_modifiers(func: &Function, name: &str) -> &'static str {
     if let FunctionKind::Method(_) = &func.kind {
         // Avoid warnings about name clashes.
@@ -317,8 +317,13 @@ impl<'a, 'b> FunctionBindgen<'a, 'b> {
                             self.lower_src.push_str("}\n");
                         }
                     }
-                    TypeDefKind::Future(_) => todo!("impl future"),
-                    TypeDefKind::Stream(_) => todo!("impl stream"),
+                    TypeDefKind::Future(_) | TypeDefKind::Stream(_) => {
+                        let c_typedef_target = self.interface.gen.get_c_ty(&Type::Id(*id));
+                        uwriteln!(
+                            self.lower_src,
+                            "var {lower_name} {c_typedef_target} = {c_typedef_target}({param})"
+                        );
+                    }
                     TypeDefKind::Resource => todo!("impl resource"),
                     TypeDefKind::Handle(h) => {
                         match self.interface.direction {
@@ -607,8 +612,13 @@ impl<'a, 'b> FunctionBindgen<'a, 'b> {
                             self.lift_src.push_str("}\n");
                         }
                     }
-                    TypeDefKind::Future(_) => todo!("impl future"),
-                    TypeDefKind::Stream(_) => todo!("impl stream"),
+                    TypeDefKind::Future(_) | TypeDefKind::Stream(_) => {
+                        let ty_name = self.interface.get_ty(&Type::Id(*id));
+                        uwriteln!(
+                            self.lift_src,
+                            "var {lift_name} {ty_name} = {ty_name}({param})"
+                        );
+                    }
                     TypeDefKind::Resource => todo!("impl resource"),
                     TypeDefKind::Handle(h) => {
                         match self.interface.direction {
@@ -676,8 +676,13 @@ impl InterfaceGenerator<'_> {
                 // although handles are anonymous types, they are generated in the
                 // `type_resource` function as part of the resource type generation.
             }
-            TypeDefKind::Future(_) => todo!("anonymous_type for future"),
-            TypeDefKind::Stream(_) => todo!("anonymous_type for stream"),
+            TypeDefKind::Future(_) | TypeDefKind::Stream(_) => {
+                // futures and streams are represented by the raw handle owned
+                // by the host.
+                let ty_name = self.ty_name(&Type::Id(ty));
+                let name = self.type_name(&ty_name, false);
+                self.src.push_str(&format!("type {name} uint32\n\n"));
+            }
             TypeDefKind::Unknown => unreachable!(),
         }
     }
@@ -7,8 +7,9 @@ use syn::parse::{Error, Parse, ParseStream, Result};
 use syn::punctuated::Punctuated;
 use syn::spanned::Spanned;
 use syn::{braced, token, LitStr, Token};
+use wit_bindgen_core::cargo;
 use wit_bindgen_core::wit_parser::{PackageId, Resolve, UnresolvedPackageGroup, WorldId};
-use wit_bindgen_rust::{Opts, Ownership, WithOption};
+use wit_bindgen_rust::{Opts, Ownership, TracingLevel, WithOption};
 
 #[proc_macro]
 pub fn generate(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
@@ -99,6 +100,9 @@ impl Parse for Config {
                     Opt::UseStdFeature => opts.std_feature = true,
                     Opt::RawStrings => opts.raw_strings = true,
                     Opt::Ownership(ownership) => opts.ownership = ownership,
+                    Opt::OwnershipOverrides(overrides) => {
+                        opts.ownership_overrides.extend(overrides)
+                    }
                     Opt::Skip(list) => opts.skip.extend(list.iter().map(|i| i.value())),
                     Opt::RuntimePath(path) => opts.runtime_path = Some(path.value()),
                     Opt::BitflagsPath(path) => opts.bitflags_path = Some(path.value()),
@@ -113,6 +117,8 @@ impl Parse for Config {
                             .collect()
                     }
                     Opt::With(with) => opts.with.extend(with),
+                    Opt::Derives(derives) => opts.derives.extend(derives),
+                    Opt::Attributes(attributes) => opts.attributes.extend(attributes),
                     Opt::GenerateAll => {
                         opts.generate_all = true;
                     }
@@ -134,6 +140,31 @@ impl Parse for Config {
                     Opt::GenerateUnusedTypes(enable) => {
                         opts.generate_unused_types = enable.value();
                     }
+                    Opt::Async(enable) => {
+                        opts.async_ = enable.value();
+                    }
+                    Opt::FeatureGates(enable) => {
+                        opts.feature_gates = enable.value();
+                    }
+                    Opt::AutoDerives(enable) => {
+                        opts.auto_derives = enable.value();
+                    }
+                    Opt::Serde(enable) => {
+                        opts.serde = enable.value();
+                    }
+                    Opt::Mocks(enable) => {
+                        opts.mocks = enable.value();
+                    }
+                    Opt::BorrowedExportParams(enable) => {
+                        opts.borrowed_export_params = enable.value();
+                    }
+                    Opt::Tracing(enable) => {
+                        opts.tracing = enable.value();
+                    }
+                    Opt::TracingLevels(levels) => opts.tracing_levels.extend(levels),
+                    Opt::DynExports(enable) => {
+                        opts.dyn_exports = enable.value();
+                    }
                     Opt::Features(f) => {
                         features.extend(f.into_iter().map(|f| f.value()));
                     }
@@ -147,25 +178,33 @@ impl Parse for Config {
                 )]));
             }
         }
-        let (resolve, pkgs, files) =
+        let parsed =
             parse_source(&source, &features).map_err(|err| anyhow_to_syn(call_site, err))?;
-        let world = resolve
-            .select_world(&pkgs, world.as_deref())
+        let world = parsed
+            .resolve
+            .select_world(&parsed.pkgs, world.or(parsed.world).as_deref())
             .map_err(|e| anyhow_to_syn(call_site, e))?;
         Ok(Config {
             opts,
-            resolve,
+            resolve: parsed.resolve,
             world,
-            files,
+            files: parsed.files,
         })
     }
 }
 
+/// The WIT parsed from a [`Source`].
+struct ParsedSource {
+    resolve: Resolve,
+    pkgs: Vec<PackageId>,
+    /// Files which were read, which the generated bindings depend on.
+    files: Vec<PathBuf>,
+    /// The world named by `Cargo.toml` metadata, if it was used.
+    world: Option<String>,
+}
+
 /// Parse the source
-fn parse_source(
-    source: &Option<Source>,
-    features: &[String],
-) -> anyhow::Result<(Resolve, Vec<PackageId>, Vec<PathBuf>)> {
+fn parse_source(source: &Option<Source>, features: &[String]) -> anyhow::Result<ParsedSource> {
     let mut resolve = Resolve::default();
     resolve.features.extend(features.iter().cloned());
     let mut files = Vec::new();
@@ -195,10 +234,30 @@ fn parse_source(
             pkgs = resolve.push_group(UnresolvedPackageGroup::parse("macro-input", s)?)?;
         }
         Some(Source::Paths(p)) => parse(p)?,
-        None => parse(&vec![root.join("wit")])?,
+        None => {
+            // Dependencies are resolved the same way as `cargo-component` if
+            // the crate is configured for it. The lockfile is only written
+            // by the CLI.
+            let manifest = root.join("Cargo.toml");
+            if cargo::has_local_target(&manifest)? {
+                let target = cargo::push_manifest(&mut resolve, &manifest, cargo::Lock::Verify)?;
+                return Ok(ParsedSource {
+                    resolve,
+                    pkgs: target.packages,
+                    files: target.files,
+                    world: target.world,
+                });
+            }
+            parse(&vec![root.join("wit")])?
+        }
     };
 
-    Ok((resolve, pkgs, files))
+    Ok(ParsedSource {
+        resolve,
+        pkgs,
+        files,
+        world: None,
+    })
 }
 
 impl Config {
@@ -258,6 +317,7 @@ mod kw {
     syn::custom_keyword!(path);
     syn::custom_keyword!(inline);
     syn::custom_keyword!(ownership);
+    syn::custom_keyword!(ownership_overrides);
     syn::custom_keyword!(runtime_path);
     syn::custom_keyword!(bitflags_path);
     syn::custom_keyword!(exports);
@@ -272,6 +332,16 @@ mod kw {
     syn::custom_keyword!(export_macro_name);
     syn::custom_keyword!(pub_export_macro);
     syn::custom_keyword!(generate_unused_types);
+    syn::custom_keyword!(feature_gates);
+    syn::custom_keyword!(auto_derives);
+    syn::custom_keyword!(serde);
+    syn::custom_keyword!(mocks);
+    syn::custom_keyword!(borrowed_export_params);
+    syn::custom_keyword!(tracing);
+    syn::custom_keyword!(tracing_levels);
+    syn::custom_keyword!(dyn_exports);
+    syn::custom_keyword!(derives);
+    syn::custom_keyword!(attributes);
     syn::custom_keyword!(features);
 }
 
@@ -310,6 +380,7 @@ enum Opt {
     RawStrings,
     Skip(Vec<syn::LitStr>),
     Ownership(Ownership),
+    OwnershipOverrides(Vec<(String, Ownership)>),
     RuntimePath(syn::LitStr),
     BitflagsPath(syn::LitStr),
     Stubs,
@@ -317,6 +388,8 @@ enum Opt {
     // Parse as paths so we can take the concrete types/macro names rather than raw strings
     AdditionalDerives(Vec<syn::Path>),
     With(HashMap<String, WithOption>),
+    Derives(Vec<(String, String)>),
+    Attributes(Vec<(String, String)>),
     GenerateAll,
     TypeSectionSuffix(syn::LitStr),
     DisableRunCtorsOnceWorkaround(syn::LitBool),
@@ -324,6 +397,15 @@ enum Opt {
     ExportMacroName(syn::LitStr),
     PubExportMacro(syn::LitBool),
     GenerateUnusedTypes(syn::LitBool),
+    Async(syn::LitBool),
+    FeatureGates(syn::LitBool),
+    AutoDerives(syn::LitBool),
+    Serde(syn::LitBool),
+    Mocks(syn::LitBool),
+    BorrowedExportParams(syn::LitBool),
+    Tracing(syn::LitBool),
+    TracingLevels(Vec<(String, TracingLevel)>),
+    DynExports(syn::LitBool),
     Features(Vec<syn::LitStr>),
 }
 
@@ -362,41 +444,15 @@ impl Parse for Opt {
         } else if l.peek(kw::ownership) {
             input.parse::<kw::ownership>()?;
             input.parse::<Token![:]>()?;
-            let ownership = input.parse::<syn::Ident>()?;
-            Ok(Opt::Ownership(match ownership.to_string().as_str() {
-                "Owning" => Ownership::Owning,
-                "Borrowing" => Ownership::Borrowing {
-                    duplicate_if_necessary: {
-                        let contents;
-                        braced!(contents in input);
-                        let field = contents.parse::<syn::Ident>()?;
-                        match field.to_string().as_str() {
-                            "duplicate_if_necessary" => {
-                                contents.parse::<Token![:]>()?;
-                                contents.parse::<syn::LitBool>()?.value
-                            }
-                            name => {
-                                return Err(Error::new(
-                                    field.span(),
-                                    format!(
-                                        "unrecognized `Ownership::Borrowing` field: `{name}`; \
-                                         expected `duplicate_if_necessary`"
-                                    ),
-                                ));
-                            }
-                        }
-                    },
-                },
-                name => {
-                    return Err(Error::new(
-                        ownership.span(),
-                        format!(
-                            "unrecognized ownership: `{name}`; \
-                             expected `Owning` or `Borrowing`"
-                        ),
-                    ));
-                }
-            }))
+            Ok(Opt::Ownership(parse_ownership(input)?))
+        } else if l.peek(kw::ownership_overrides) {
+            input.parse::<kw::ownership_overrides>()?;
+            input.parse::<Token![:]>()?;
+            let contents;
+            let _lbrace = braced!(contents in input);
+            let fields: Punctuated<_, Token![,]> =
+                contents.parse_terminated(ownership_override_field_parse, Token![,])?;
+            Ok(Opt::OwnershipOverrides(fields.into_iter().collect()))
         } else if l.peek(kw::skip) {
             input.parse::<kw::skip>()?;
             input.parse::<Token![:]>()?;
@@ -434,6 +490,22 @@ impl Parse for Opt {
             let fields: Punctuated<_, Token![,]> =
                 contents.parse_terminated(with_field_parse, Token![,])?;
             Ok(Opt::With(HashMap::from_iter(fields.into_iter())))
+        } else if l.peek(kw::derives) {
+            input.parse::<kw::derives>()?;
+            input.parse::<Token![:]>()?;
+            let contents;
+            let _lbrace = braced!(contents in input);
+            let fields: Punctuated<_, Token![,]> =
+                contents.parse_terminated(derives_field_parse, Token![,])?;
+            Ok(Opt::Derives(fields.into_iter().flatten().collect()))
+        } else if l.peek(kw::attributes) {
+            input.parse::<kw::attributes>()?;
+            input.parse::<Token![:]>()?;
+            let contents;
+            let _lbrace = braced!(contents in input);
+            let fields: Punctuated<_, Token![,]> =
+                contents.parse_terminated(attributes_field_parse, Token![,])?;
+            Ok(Opt::Attributes(fields.into_iter().flatten().collect()))
         } else if l.peek(kw::generate_all) {
             input.parse::<kw::generate_all>()?;
             Ok(Opt::GenerateAll)
@@ -461,6 +533,46 @@ impl Parse for Opt {
             input.parse::<kw::generate_unused_types>()?;
             input.parse::<Token![:]>()?;
             Ok(Opt::GenerateUnusedTypes(input.parse()?))
+        } else if l.peek(Token![async]) {
+            input.parse::<Token![async]>()?;
+            input.parse::<Token![:]>()?;
+            Ok(Opt::Async(input.parse()?))
+        } else if l.peek(kw::feature_gates) {
+            input.parse::<kw::feature_gates>()?;
+            input.parse::<Token![:]>()?;
+            Ok(Opt::FeatureGates(input.parse()?))
+        } else if l.peek(kw::auto_derives) {
+            input.parse::<kw::auto_derives>()?;
+            input.parse::<Token![:]>()?;
+            Ok(Opt::AutoDerives(input.parse()?))
+        } else if l.peek(kw::serde) {
+            input.parse::<kw::serde>()?;
+            input.parse::<Token![:]>()?;
+            Ok(Opt::Serde(input.parse()?))
+        } else if l.peek(kw::mocks) {
+            input.parse::<kw::mocks>()?;
+            input.parse::<Token![:]>()?;
+            Ok(Opt::Mocks(input.parse()?))
+        } else if l.peek(kw::borrowed_export_params) {
+            input.parse::<kw::borrowed_export_params>()?;
+            input.parse::<Token![:]>()?;
+            Ok(Opt::BorrowedExportParams(input.parse()?))
+        } else if l.peek(kw::tracing_levels) {
+            input.parse::<kw::tracing_levels>()?;
+            input.parse::<Token![:]>()?;
+            let contents;
+            let _lbrace = braced!(contents in input);
+            let fields: Punctuated<_, Token![,]> =
+                contents.parse_terminated(tracing_level_field_parse, Token![,])?;
+            Ok(Opt::TracingLevels(fields.into_iter().collect()))
+        } else if l.peek(kw::tracing) {
+            input.parse::<kw::tracing>()?;
+            input.parse::<Token![:]>()?;
+            Ok(Opt::Tracing(input.parse()?))
+        } else if l.peek(kw::dyn_exports) {
+            input.parse::<kw::dyn_exports>()?;
+            input.parse::<Token![:]>()?;
+            Ok(Opt::DynExports(input.parse()?))
         } else if l.peek(kw::features) {
             input.parse::<kw::features>()?;
             input.parse::<Token![:]>()?;
@@ -474,6 +586,92 @@ impl Parse for Opt {
     }
 }
 
+fn parse_ownership(input: ParseStream<'_>) -> Result<Ownership> {
+    let ownership = input.parse::<syn::Ident>()?;
+    Ok(match ownership.to_string().as_str() {
+        "Owning" => Ownership::Owning,
+        "Borrowing" => Ownership::Borrowing {
+            duplicate_if_necessary: {
+                let contents;
+                braced!(contents in input);
+                let field = contents.parse::<syn::Ident>()?;
+                match field.to_string().as_str() {
+                    "duplicate_if_necessary" => {
+                        contents.parse::<Token![:]>()?;
+                        contents.parse::<syn::LitBool>()?.value
+                    }
+                    name => {
+                        return Err(Error::new(
+                            field.span(),
+                            format!(
+                                "unrecognized `Ownership::Borrowing` field: `{name}`; \
+                                 expected `duplicate_if_necessary`"
+                            ),
+                        ));
+                    }
+                }
+            },
+        },
+        name => {
+            return Err(Error::new(
+                ownership.span(),
+                format!(
+                    "unrecognized ownership: `{name}`; \
+                     expected `Owning` or `Borrowing`"
+                ),
+            ));
+        }
+    })
+}
+
+fn ownership_override_field_parse(input: ParseStream<'_>) -> Result<(String, Ownership)> {
+    let key = input.parse::<syn::LitStr>()?.value();
+    input.parse::<Token![:]>()?;
+    Ok((key, parse_ownership(input)?))
+}
+
+fn tracing_level_field_parse(input: ParseStream<'_>) -> Result<(String, TracingLevel)> {
+    let key = input.parse::<syn::LitStr>()?.value();
+    input.parse::<Token![:]>()?;
+    let level = input.parse::<syn::Ident>()?;
+    let level = level
+        .to_string()
+        .parse()
+        .map_err(|e: String| Error::new(level.span(), e))?;
+    Ok((key, level))
+}
+
+fn derives_field_parse(input: ParseStream<'_>) -> Result<Vec<(String, String)>> {
+    let key = input.parse::<syn::LitStr>()?.value();
+    input.parse::<Token![:]>()?;
+    let contents;
+    syn::bracketed!(contents in input);
+    let list = Punctuated::<syn::Path, Token![,]>::parse_terminated(&contents)?;
+    Ok(list
+        .into_iter()
+        .map(|p| (key.clone(), p.into_token_stream().to_string()))
+        .collect())
+}
+
+fn attributes_field_parse(input: ParseStream<'_>) -> Result<Vec<(String, String)>> {
+    let key = input.parse::<syn::LitStr>()?.value();
+    input.parse::<Token![:]>()?;
+    let contents;
+    syn::bracketed!(contents in input);
+    // Attributes may optionally be separated by commas.
+    let mut attributes = Vec::new();
+    while !contents.is_empty() {
+        attributes.extend(contents.call(syn::Attribute::parse_outer)?);
+        if !contents.is_empty() {
+            contents.parse::<Token![,]>()?;
+        }
+    }
+    Ok(attributes
+        .into_iter()
+        .map(|a| (key.clone(), a.into_token_stream().to_string()))
+        .collect())
+}
+
 fn with_field_parse(input: ParseStream<'_>) -> Result<(String, WithOption)> {
     let interface = input.parse::<syn::LitStr>()?.value();
     input.parse::<Token![:]>()?;
@@ -490,16 +688,21 @@ fn with_field_parse(input: ParseStream<'_>) -> Result<(String, WithOption)> {
     }
 
     let mut buf = String::new();
+    // Generic arguments are allowed since types may be remapped to generic
+    // types, and the generator rejects them for interfaces.
     let append = |buf: &mut String, segment: syn::PathSegment| -> Result<()> {
-        if !segment.arguments.is_none() {
-            return Err(Error::new(
-                span,
-                "Module path must not contain angles or parens",
-            ));
-        }
-
         buf.push_str(&segment.ident.to_string());
 
+        match segment.arguments {
+            syn::PathArguments::None => {}
+            syn::PathArguments::AngleBracketed(args) => {
+                buf.push_str(&args.to_token_stream().to_string());
+            }
+            syn::PathArguments::Parenthesized(_) => {
+                return Err(Error::new(span, "Path must not contain parens"));
+            }
+        }
+
         Ok(())
     };
 
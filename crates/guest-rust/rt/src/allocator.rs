@@ -0,0 +1,339 @@
+//! Allocation strategies for the buffers handed out by `cabi_realloc`.
+//!
+//! Hosts call `cabi_realloc` to allocate the memory which arguments to exports
+//! and results of imports are written into. Generated bindings later free
+//! that memory through the global allocator, either directly or by dropping
+//! the `Vec` or `String` it was lifted into, so strategies here are global
+//! allocators installed with `#[global_allocator]`:
+//!
+//! * [`BumpArena`], enabled by the `bump-arena` feature, serves allocations
+//!   made by `cabi_realloc` from a fixed-size arena which is reset once all of
+//!   them are freed. Everything else is allocated by another allocator.
+//!
+//! * [`Pool`], enabled by the `pool` feature, is a complete allocator for
+//!   `no_std` guests backed by a fixed-size static pool of memory.
+//!
+//! Additionally [`set_realloc_hook`] replaces `cabi_realloc` itself at
+//! runtime.
+//!
+//! Component model guests are single-threaded, so none of the allocators
+//! here synchronize access to their state.
+
+#[cfg(any(feature = "bump-arena", feature = "pool"))]
+use core::alloc::{GlobalAlloc, Layout};
+#[cfg(any(feature = "bump-arena", feature = "pool"))]
+use core::cell::{Cell, UnsafeCell};
+#[cfg(any(feature = "bump-arena", feature = "pool"))]
+use core::mem::MaybeUninit;
+use core::ptr;
+#[cfg(feature = "bump-arena")]
+use core::sync::atomic::AtomicBool;
+use core::sync::atomic::{AtomicPtr, Ordering::Relaxed};
+
+/// The signature of `cabi_realloc`, see [`set_realloc_hook`].
+pub type ReallocHook =
+    unsafe fn(old_ptr: *mut u8, old_len: usize, align: usize, new_len: usize) -> *mut u8;
+
+static REALLOC_HOOK: AtomicPtr<()> = AtomicPtr::new(ptr::null_mut());
+
+/// Replaces the implementation of `cabi_realloc` with `hook`, or restores the
+/// default implementation if `hook` is `None`.
+///
+/// This only has an effect when `cabi_realloc` is provided by this crate.
+///
+/// # Safety
+///
+/// Memory returned by `hook` must be valid to reallocate and deallocate
+/// through the global allocator with the size and alignment it was requested
+/// with, since that's how generated bindings free it.
+pub unsafe fn set_realloc_hook(hook: Option<ReallocHook>) {
+    let hook = match hook {
+        Some(hook) => hook as *mut (),
+        None => ptr::null_mut(),
+    };
+    REALLOC_HOOK.store(hook, Relaxed);
+}
+
+/// Returns the hook registered with [`set_realloc_hook`], if any.
+#[cfg(not(target_env = "p2"))]
+pub(crate) fn realloc_hook() -> Option<ReallocHook> {
+    let hook = REALLOC_HOOK.load(Relaxed);
+    if hook.is_null() {
+        return None;
+    }
+    // SAFETY: the only non-null values stored are `ReallocHook`s.
+    Some(unsafe { core::mem::transmute::<*mut (), ReallocHook>(hook) })
+}
+
+/// Whether `cabi_realloc` is currently allocating, in which case
+/// [`BumpArena`] serves allocations from its arena.
+#[cfg(feature = "bump-arena")]
+static IN_REALLOC: AtomicBool = AtomicBool::new(false);
+
+/// Runs `f`, an allocation on behalf of `cabi_realloc`.
+#[cfg(not(target_env = "p2"))]
+pub(crate) fn in_realloc<R>(f: impl FnOnce() -> R) -> R {
+    #[cfg(feature = "bump-arena")]
+    {
+        let prev = IN_REALLOC.swap(true, Relaxed);
+        let ret = f();
+        IN_REALLOC.store(prev, Relaxed);
+        ret
+    }
+    #[cfg(not(feature = "bump-arena"))]
+    f()
+}
+
+/// Returns the address of `ptr` rounded up to a multiple of `align`, which
+/// must be a power of two.
+#[cfg(any(feature = "bump-arena", feature = "pool"))]
+fn align_up(addr: usize, align: usize) -> Option<usize> {
+    Some(addr.checked_add(align - 1)? & !(align - 1))
+}
+
+/// A global allocator serving allocations made by `cabi_realloc` from an
+/// arena of `N` bytes.
+///
+/// Allocations are bumped off the end of the arena and the arena is reset
+/// once all of them have been freed. Allocations made by anything other than
+/// `cabi_realloc`, or which don't fit in the arena, are forwarded to the
+/// allocator `A`.
+///
+/// There is no explicit reset at the end of a call. Arguments to an export
+/// are usually freed before it returns and its results are freed by its
+/// post-return function, which resets the arena after every call. Arguments
+/// which outlive the call however, such as a `String` stored in a `static`,
+/// keep the arena from being reset until they're freed, and in the meantime
+/// later allocations which don't fit in the rest of the arena are forwarded
+/// to `A`.
+///
+/// ```ignore
+/// #[global_allocator]
+/// static ALLOC: wit_bindgen::allocator::BumpArena<std::alloc::System, 65536> =
+///     wit_bindgen::allocator::BumpArena::new(std::alloc::System);
+/// ```
+#[cfg(feature = "bump-arena")]
+pub struct BumpArena<A, const N: usize> {
+    backing: A,
+    arena: UnsafeCell<MaybeUninit<[u8; N]>>,
+    /// The offset in `arena` of the first free byte.
+    top: Cell<usize>,
+    /// The number of allocations in `arena` which haven't been freed.
+    live: Cell<usize>,
+}
+
+// Component model guests are single-threaded, so sharing the arena through a
+// `static` is fine.
+#[cfg(feature = "bump-arena")]
+unsafe impl<A: Sync, const N: usize> Sync for BumpArena<A, N> {}
+
+#[cfg(feature = "bump-arena")]
+impl<A, const N: usize> BumpArena<A, N> {
+    /// Creates an arena forwarding other allocations to `backing`.
+    pub const fn new(backing: A) -> Self {
+        BumpArena {
+            backing,
+            arena: UnsafeCell::new(MaybeUninit::uninit()),
+            top: Cell::new(0),
+            live: Cell::new(0),
+        }
+    }
+
+    /// Returns the number of bytes of the arena in use.
+    pub fn used(&self) -> usize {
+        self.top.get()
+    }
+
+    /// Returns whether `ptr` was allocated from the arena.
+    pub fn contains(&self, ptr: *const u8) -> bool {
+        let base = self.arena.get() as usize;
+        (base..base + N).contains(&(ptr as usize))
+    }
+
+    fn bump(&self, layout: Layout) -> *mut u8 {
+        let base = self.arena.get() as usize;
+        let start = match align_up(base + self.top.get(), layout.align()) {
+            Some(start) => start,
+            None => return ptr::null_mut(),
+        };
+        match start.checked_add(layout.size()) {
+            Some(end) if end <= base + N => {
+                self.top.set(end - base);
+                self.live.set(self.live.get() + 1);
+                start as *mut u8
+            }
+            _ => ptr::null_mut(),
+        }
+    }
+}
+
+#[cfg(feature = "bump-arena")]
+unsafe impl<A: GlobalAlloc, const N: usize> GlobalAlloc for BumpArena<A, N> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if IN_REALLOC.load(Relaxed) {
+            let ptr = self.bump(layout);
+            if !ptr.is_null() {
+                return ptr;
+            }
+        }
+        self.backing.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        if !self.contains(ptr) {
+            return self.backing.dealloc(ptr, layout);
+        }
+        let live = self.live.get() - 1;
+        self.live.set(live);
+        let base = self.arena.get() as usize;
+        if live == 0 {
+            self.top.set(0);
+        } else if ptr as usize + layout.size() == base + self.top.get() {
+            // The most recent allocation can be given back to the arena.
+            self.top.set(ptr as usize - base);
+        }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        if !self.contains(ptr) {
+            return self.backing.realloc(ptr, layout, new_size);
+        }
+        // The most recent allocation can be resized in place.
+        let base = self.arena.get() as usize;
+        if ptr as usize + layout.size() == base + self.top.get()
+            && ptr as usize + new_size <= base + N
+        {
+            self.top.set(ptr as usize + new_size - base);
+            return ptr;
+        }
+        let new_layout = Layout::from_size_align_unchecked(new_size, layout.align());
+        let new_ptr = self.alloc(new_layout);
+        if !new_ptr.is_null() {
+            ptr::copy_nonoverlapping(ptr, new_ptr, layout.size().min(new_size));
+            self.dealloc(ptr, layout);
+        }
+        new_ptr
+    }
+}
+
+/// A global allocator for `no_std` guests serving all allocations from a
+/// static pool of `N` bytes.
+///
+/// Allocations are rounded up to a power of two and freed blocks are reused
+/// for later allocations of the same size. Allocation fails once the pool is
+/// exhausted.
+///
+/// ```ignore
+/// #[global_allocator]
+/// static ALLOC: wit_bindgen::allocator::Pool<{ 1 << 20 }> =
+///     wit_bindgen::allocator::Pool::new();
+/// ```
+#[cfg(feature = "pool")]
+pub struct Pool<const N: usize> {
+    pool: UnsafeCell<MaybeUninit<[u8; N]>>,
+    /// The offset in `pool` of the first byte not yet handed out.
+    top: Cell<usize>,
+    /// Lists of freed blocks, indexed by the log2 of their size, which are
+    /// linked through their first word.
+    free: [Cell<*mut u8>; usize::BITS as usize],
+}
+
+// Component model guests are single-threaded, so sharing the pool through a
+// `static` is fine.
+#[cfg(feature = "pool")]
+unsafe impl<const N: usize> Sync for Pool<N> {}
+
+#[cfg(feature = "pool")]
+impl<const N: usize> Pool<N> {
+    /// Creates an empty pool.
+    pub const fn new() -> Self {
+        #[allow(clippy::declare_interior_mutable_const)]
+        const EMPTY: Cell<*mut u8> = Cell::new(ptr::null_mut());
+        Pool {
+            pool: UnsafeCell::new(MaybeUninit::uninit()),
+            top: Cell::new(0),
+            free: [EMPTY; usize::BITS as usize],
+        }
+    }
+
+    /// Returns the number of bytes of the pool handed out so far, including
+    /// freed blocks available for reuse.
+    pub fn used(&self) -> usize {
+        self.top.get()
+    }
+
+    /// Returns whether `ptr` was allocated from the pool.
+    pub fn contains(&self, ptr: *const u8) -> bool {
+        let base = self.pool.get() as usize;
+        (base..base + N).contains(&(ptr as usize))
+    }
+
+    /// Returns the size class of blocks for `layout`, which is the log2 of
+    /// their size.
+    ///
+    /// Blocks are at least large enough to hold a pointer and are aligned to
+    /// their size, which satisfies the alignment of `layout`.
+    fn class(layout: Layout) -> Option<usize> {
+        let size = layout
+            .size()
+            .max(layout.align())
+            .max(core::mem::size_of::<*mut u8>())
+            .checked_next_power_of_two()?;
+        Some(size.trailing_zeros() as usize)
+    }
+}
+
+#[cfg(feature = "pool")]
+impl<const N: usize> Default for Pool<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "pool")]
+unsafe impl<const N: usize> GlobalAlloc for Pool<N> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let Some(class) = Self::class(layout) else {
+            return ptr::null_mut();
+        };
+        let free = &self.free[class];
+        let block = free.get();
+        if !block.is_null() {
+            free.set(*block.cast::<*mut u8>());
+            return block;
+        }
+        let size = 1 << class;
+        let base = self.pool.get() as usize;
+        let start = match align_up(base + self.top.get(), size) {
+            Some(start) => start,
+            None => return ptr::null_mut(),
+        };
+        match start.checked_add(size) {
+            Some(end) if end <= base + N => {
+                self.top.set(end - base);
+                start as *mut u8
+            }
+            _ => ptr::null_mut(),
+        }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let class = Self::class(layout).unwrap();
+        let free = &self.free[class];
+        *ptr.cast::<*mut u8>() = free.get();
+        free.set(ptr);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_layout = Layout::from_size_align_unchecked(new_size, layout.align());
+        if Self::class(layout) == Self::class(new_layout) {
+            return ptr;
+        }
+        let new_ptr = self.alloc(new_layout);
+        if !new_ptr.is_null() {
+            ptr::copy_nonoverlapping(ptr, new_ptr, layout.size().min(new_size));
+            self.dealloc(ptr, layout);
+        }
+        new_ptr
+    }
+}
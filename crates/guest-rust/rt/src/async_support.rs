@@ -0,0 +1,258 @@
+//! Runtime support for bindings generated with the `async` option.
+//!
+//! This module contains a small single-threaded executor which generated
+//! export functions use to drive `async fn` implementations to completion.
+//! Asynchronous work is tied to the host through `wasi:io/poll` pollables:
+//! a guest awaits [`wait`] for a pollable and when nothing else can make
+//! progress the executor blocks in the `poll` function passed to [`wait`],
+//! which is generated bindings' `wasi:io/poll#poll` import, until at least
+//! one pollable is ready.
+//!
+//! The executor is not reentrant: calling [`block_on`] from within a future
+//! that is itself being driven by [`block_on`] will panic.
+
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use core::future::Future;
+use core::marker::PhantomData;
+use core::pin::{pin, Pin};
+use core::ptr;
+use core::sync::atomic::{AtomicU32, Ordering::Relaxed};
+use core::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+/// Blocks until at least one of the pollables `handles` is ready, returning
+/// the indices of those which are, like `wasi:io/poll#poll`.
+pub type PollFn = fn(handles: &[u32]) -> Vec<u32>;
+
+/// State shared between the executor and the futures returned by [`wait`].
+#[derive(Default)]
+struct Reactor {
+    /// Whether `block_on` is currently running.
+    running: bool,
+    /// Pollables which pending futures are waiting on.
+    waiting: Vec<u32>,
+    /// How to block on `waiting`, set by the futures waiting on them.
+    poll: Option<PollFn>,
+    /// Pollables reported as ready by the last call to `poll`.
+    ready: Vec<u32>,
+}
+
+const EMPTY_REACTOR: Reactor = Reactor {
+    running: false,
+    waiting: Vec::new(),
+    poll: None,
+    ready: Vec::new(),
+};
+
+/// Calls `f` with this thread's reactor.
+#[cfg(target_arch = "wasm32")]
+fn with_reactor<R>(f: impl FnOnce(&mut Reactor) -> R) -> R {
+    struct SyncReactor(RefCell<Reactor>);
+
+    // Component model guests are single-threaded, so sharing the reactor
+    // through a `static` is fine.
+    unsafe impl Sync for SyncReactor {}
+
+    static REACTOR: SyncReactor = SyncReactor(RefCell::new(EMPTY_REACTOR));
+    f(&mut REACTOR.0.borrow_mut())
+}
+
+/// Calls `f` with this thread's reactor.
+///
+/// Bindings are also compiled natively, for example in tests, where they may
+/// run on many threads.
+#[cfg(not(target_arch = "wasm32"))]
+fn with_reactor<R>(f: impl FnOnce(&mut Reactor) -> R) -> R {
+    std::thread_local! {
+        static REACTOR: RefCell<Reactor> = const { RefCell::new(EMPTY_REACTOR) };
+    }
+    REACTOR.with(|reactor| f(&mut reactor.borrow_mut()))
+}
+
+/// Runs `future` to completion, blocking on host pollables whenever it is
+/// pending.
+///
+/// This is called by generated export functions when the `async` option is
+/// enabled, but may also be used directly by guests.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    with_reactor(|reactor| {
+        assert!(!reactor.running, "`block_on` cannot be called recursively");
+        reactor.running = true;
+    });
+    struct Reset;
+    impl Drop for Reset {
+        fn drop(&mut self) {
+            with_reactor(|reactor| *reactor = EMPTY_REACTOR);
+        }
+    }
+    let _reset = Reset;
+
+    let mut future = pin!(future);
+    let waker = noop_waker();
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        let result = future.as_mut().poll(&mut cx);
+        let (waiting, poll) = with_reactor(|reactor| {
+            reactor.ready.clear();
+            (core::mem::take(&mut reactor.waiting), reactor.poll.take())
+        });
+        if let Poll::Ready(result) = result {
+            return result;
+        }
+        let poll = match poll {
+            Some(poll) if !waiting.is_empty() => poll,
+            _ => panic!("future is pending but is not waiting on any pollables"),
+        };
+        let ready = poll(&waiting)
+            .into_iter()
+            .map(|i| waiting[i as usize])
+            .collect();
+        with_reactor(|reactor| reactor.ready = ready);
+    }
+}
+
+/// Returns a future which resolves once the pollable `handle` is ready, as
+/// reported by `poll`.
+///
+/// The `handle` is borrowed for the lifetime of the returned future, and must
+/// stay alive until the future has resolved or been dropped. All pollables
+/// awaited at the same time must be polled by the same `poll` function.
+/// Generated bindings wrap this as an `async fn wait(&self)` method on
+/// `pollable` which uses the `poll` function of its interface.
+pub fn wait(handle: u32, poll: PollFn) -> Wait {
+    Wait { handle, poll }
+}
+
+/// Future returned by [`wait`].
+#[must_use = "futures do nothing unless polled"]
+pub struct Wait {
+    handle: u32,
+    poll: PollFn,
+}
+
+impl Future for Wait {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<()> {
+        with_reactor(|reactor| {
+            assert!(
+                reactor.running,
+                "pollables can only be awaited within `block_on`"
+            );
+            if reactor.ready.contains(&self.handle) {
+                return Poll::Ready(());
+            }
+            reactor.poll = Some(self.poll);
+            if !reactor.waiting.contains(&self.handle) {
+                reactor.waiting.push(self.handle);
+            }
+            Poll::Pending
+        })
+    }
+}
+
+fn noop_waker() -> Waker {
+    const VTABLE: RawWakerVTable = RawWakerVTable::new(
+        // clone
+        |_| RawWaker::new(ptr::null(), &VTABLE),
+        // wake
+        |_| {},
+        // wake_by_ref
+        |_| {},
+        // drop
+        |_| {},
+    );
+    // The executor re-polls the root future after every call to `poll`, so
+    // wakeups don't need to be tracked.
+    unsafe { Waker::from_raw(RawWaker::new(ptr::null(), &VTABLE)) }
+}
+
+/// The readable end of a WIT `future<T>`, represented by its handle.
+#[repr(transparent)]
+pub struct FutureReader<T> {
+    // Like `wit_bindgen::rt::Resource`, the handle can be taken through a
+    // shared reference by generated code, after which it's `u32::MAX`.
+    handle: AtomicU32,
+    _marker: PhantomData<T>,
+}
+
+impl<T> FutureReader<T> {
+    /// Creates a new `FutureReader` from the raw `handle` provided.
+    ///
+    /// # Safety
+    ///
+    /// The `handle` must be an owned handle to a future of `T`.
+    pub unsafe fn from_handle(handle: u32) -> Self {
+        Self {
+            handle: AtomicU32::new(handle),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the raw handle of this future without transferring ownership.
+    pub fn handle(&self) -> u32 {
+        self.handle.load(Relaxed)
+    }
+
+    /// Takes ownership of the raw handle of this future.
+    ///
+    /// This is called by generated code which lowers the future into a call,
+    /// possibly through a reference to an argument it owns.
+    #[doc(hidden)]
+    pub fn take_handle(&self) -> u32 {
+        self.handle.swap(u32::MAX, Relaxed)
+    }
+}
+
+impl<T> core::fmt::Debug for FutureReader<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("FutureReader")
+            .field("handle", &self.handle())
+            .finish()
+    }
+}
+
+/// The readable end of a WIT `stream<T, E>`, represented by its handle.
+#[repr(transparent)]
+pub struct StreamReader<T, E = ()> {
+    // Like `wit_bindgen::rt::Resource`, the handle can be taken through a
+    // shared reference by generated code, after which it's `u32::MAX`.
+    handle: AtomicU32,
+    _marker: PhantomData<(T, E)>,
+}
+
+impl<T, E> StreamReader<T, E> {
+    /// Creates a new `StreamReader` from the raw `handle` provided.
+    ///
+    /// # Safety
+    ///
+    /// The `handle` must be an owned handle to a stream of `T`.
+    pub unsafe fn from_handle(handle: u32) -> Self {
+        Self {
+            handle: AtomicU32::new(handle),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the raw handle of this stream without transferring ownership.
+    pub fn handle(&self) -> u32 {
+        self.handle.load(Relaxed)
+    }
+
+    /// Takes ownership of the raw handle of this stream.
+    ///
+    /// This is called by generated code which lowers the stream into a call,
+    /// possibly through a reference to an argument it owns.
+    #[doc(hidden)]
+    pub fn take_handle(&self) -> u32 {
+        self.handle.swap(u32::MAX, Relaxed)
+    }
+}
+
+impl<T, E> core::fmt::Debug for StreamReader<T, E> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("StreamReader")
+            .field("handle", &self.handle())
+            .finish()
+    }
+}
@@ -2,6 +2,10 @@
 
 extern crate alloc;
 
+// Native builds, such as tests of generated bindings, use thread-local state.
+#[cfg(all(feature = "async", not(target_arch = "wasm32")))]
+extern crate std;
+
 // Re-export `bitflags` so that we can reference it from macros.
 #[cfg(feature = "bitflags")]
 #[doc(hidden)]
@@ -11,6 +15,11 @@ pub use bitflags;
 #[cfg(not(target_env = "p2"))]
 mod cabi_realloc;
 
+#[cfg(feature = "async")]
+pub mod async_support;
+
+pub mod allocator;
+
 /// This function is called from generated bindings and will be deleted by
 /// the linker. The purpose of this function is to force a reference to the
 /// symbol `cabi_realloc` to make its way through to the final linker
@@ -59,17 +68,21 @@ pub unsafe fn cabi_realloc(
 ) -> *mut u8 {
     use self::alloc::alloc::{self, Layout};
 
+    if let Some(hook) = allocator::realloc_hook() {
+        return hook(old_ptr, old_len, align, new_len);
+    }
+
     let layout;
     let ptr = if old_len == 0 {
         if new_len == 0 {
             return align as *mut u8;
         }
         layout = Layout::from_size_align_unchecked(new_len, align);
-        alloc::alloc(layout)
+        allocator::in_realloc(|| alloc::alloc(layout))
     } else {
         debug_assert_ne!(new_len, 0, "non-zero old_len requires non-zero new_len!");
         layout = Layout::from_size_align_unchecked(old_len, align);
-        alloc::realloc(old_ptr, layout, new_len)
+        allocator::in_realloc(|| alloc::realloc(old_ptr, layout, new_len))
     };
     if ptr.is_null() {
         // Print a nice message in debug mode, but in release mode don't
@@ -70,6 +70,33 @@
 /// generate!("a-world" in "../path/to/wit");
 /// ```
 ///
+/// If no path is specified and your `Cargo.toml` has a
+/// `[package.metadata.component.target]` table, as used by
+/// `cargo-component`, then the WIT package and its dependencies are resolved
+/// from it instead:
+///
+/// ```toml
+/// [package.metadata.component.target]
+/// path = "wit"
+/// world = "my-world"
+///
+/// [package.metadata.component.target.dependencies]
+/// "wasi:http" = "0.2"
+/// "my:local" = { path = "../local/wit" }
+///
+/// [package.metadata.wit-bindgen]
+/// vendor-dir = "vendor/wit"
+/// ```
+///
+/// Dependencies with a `path` are read from there, and all others are looked
+/// up in the `deps` folder of the target package and then in the optional
+/// `vendor-dir`, which is laid out the same way. Running `wit-bindgen` on the
+/// `Cargo.toml` with `--update-lockfile` records the packages which are used,
+/// along with a digest of their contents, in a `wit-bindgen.lock` file next to
+/// it. If that file exists it's an error for this macro to resolve to
+/// anything else. Targets naming a registry package aren't supported and the
+/// `wit` folder is used instead.
+///
 /// The full-form of the macro, however, takes a braced structure which is a
 /// "bag of options":
 ///
@@ -606,6 +633,56 @@
 /// # fn main() {}
 /// ```
 ///
+/// ## Exports: Choosing implementations at runtime
+///
+/// With the `dyn_exports` option the methods of `Guest` traits take `&self`
+/// and exported functions dispatch to an implementation registered at runtime
+/// with `set_export_impl` rather than to the type given to `export!`. This
+/// makes it possible to choose an implementation based on configuration, for
+/// example. Exported resources aren't supported in this mode.
+///
+/// ```
+/// wit_bindgen::generate!({
+///     inline: r#"
+///         package example:plugins;
+///
+///         world plugin {
+///             export run: func(input: u32) -> u32;
+///         }
+///     "#,
+///     dyn_exports: true,
+/// });
+///
+/// struct Double;
+///
+/// impl Guest for Double {
+///     fn run(&self, input: u32) -> u32 {
+///         input * 2
+///     }
+/// }
+///
+/// struct Offset(u32);
+///
+/// impl Guest for Offset {
+///     fn run(&self, input: u32) -> u32 {
+///         input + self.0
+///     }
+/// }
+///
+/// // No type is given to `export!` since exports call the implementation
+/// // registered with `set_export_impl`.
+/// export!();
+///
+/// fn init(offset: Option<u32>) {
+///     match offset {
+///         Some(offset) => set_export_impl(Box::new(Offset(offset))),
+///         None => set_export_impl(Box::new(Double)),
+///     }
+/// }
+/// #
+/// # fn main() {}
+/// ```
+///
 /// ## Debugging output to `generate!`
 ///
 /// While `wit-bindgen` is tested to the best of our ability there are
@@ -645,8 +722,9 @@
 ///     // below has a single `world` in it.
 ///     world: "my-world",
 ///
-///     // Path to parse WIT and its dependencies from. Defaults to the `wit`
-///     // folder adjacent to your `Cargo.toml`.
+///     // Path to parse WIT and its dependencies from. Defaults to the
+///     // `[package.metadata.component.target]` of your `Cargo.toml` if it has
+///     // one, or otherwise to the `wit` folder adjacent to it.
 ///     //
 ///     // This parameter also supports the form of a list, such as:
 ///     // ["../path/to/wit1", "../path/to/wit2"]
@@ -686,18 +764,26 @@
 ///     // already generated bindings then you can use the special `generate` value
 ///     // to have those bindings generated.
 ///     //
-///     // The `with` key only supports replacing types at the interface level
-///     // at this time.
-///     //
 ///     // When an interface is specified no bindings will be generated at
 ///     // all. It's assumed bindings are fully generated somewhere else. This is an
 ///     // indicator that any further references to types defined in these
 ///     // interfaces should use the upstream paths specified here instead.
 ///     //
+///     // Individual types can be remapped as well by appending their name to
+///     // the interface. The type is still generated, but functions use the
+///     // Rust type specified here instead and convert it to the generated type
+///     // with `From` and from the generated type with `TryFrom`, panicking if
+///     // that fails. Only records, tuples, flags, variants, enums, options and
+///     // results without resources can be remapped, and the Rust type must
+///     // implement the same traits as the generated type, such as `Clone` and
+///     // `Debug`. Types are always generated, so `generate` can't be used
+///     // with them.
+///     //
 ///     // Any unused keys in this map are considered an error.
 ///     with: {
 ///         "wasi:io/poll": wasi::io::poll,
 ///         "some:package/my-interface": generate,
+///         "wasi:clocks/wall-clock@0.2.0/datetime": chrono::DateTime<chrono::Utc>,
 ///     },
 ///
 ///     // Indicates that all interfaces not present in `with` should be assumed
@@ -763,6 +849,19 @@
 ///     // case above if possible.
 ///     ownership: Borrowing { duplicate_if_necessary: false },
 ///
+///     // The ownership mode above can be overridden for individual imported
+///     // interfaces or functions, with a function's own entry taking
+///     // precedence over its interface's. Functions imported by the world
+///     // itself are named without an interface.
+///     //
+///     // Types used by several imported functions only borrow their contents
+///     // if all of those functions are borrowing, and otherwise borrowing
+///     // functions take a reference to the owning version of the type.
+///     ownership_overrides: {
+///         "wasi:http/outgoing-handler@0.2.0": Borrowing { duplicate_if_necessary: false },
+///         "wasi:http/outgoing-handler@0.2.0#handle": Owning,
+///     },
+///
 ///     // The generated `export!` macro, if any, will by default look for
 ///     // generated types adjacent to where the `export!` macro is invoked
 ///     // through the `self` module. This option can be used to change the
@@ -806,12 +905,83 @@
 ///     // or return value of a function.
 ///     generate_unused_types: false,
 ///
+///     // Generates `async fn` methods in exported traits which are driven to
+///     // completion by a small executor in `wit_bindgen::rt`, and adds an
+///     // `async fn wait` method to imported `wasi:io/poll` pollables. Imported
+///     // functions stay synchronous. This requires the `async` feature of
+///     // this crate.
+///     async: false,
+///
 ///     // A list of "features" which correspond to WIT features to activate
 ///     // when parsing WIT files. This enables `@unstable` annotations showing
 ///     // up and having bindings generated for them.
 ///     //
 ///     // By default this is an empty list.
 ///     features: ["foo", "bar", "baz"],
+///
+///     // Wraps imported functions and interfaces which require a WIT feature
+///     // in `#[cfg(feature = "...")]`, gating them behind a Cargo feature of
+///     // the same name. Functions whose documentation contains a line
+///     // starting with `@deprecated` are always marked `#[deprecated]`.
+///     feature_gates: false,
+///
+///     // Derives `PartialEq`, `PartialOrd`, `Eq`, `Ord` and `Hash` for generated
+///     // types wherever everything they contain supports it. Types containing
+///     // floats only get `PartialEq` and `PartialOrd`, and types containing
+///     // resources get none of these.
+///     auto_derives: false,
+///
+///     // Implements `serde::Serialize` and `serde::Deserialize` for generated
+///     // types using WIT names for fields, cases and flags. Types containing
+///     // resources are skipped. This requires a dependency on `serde` with its
+///     // `derive` feature.
+///     serde: false,
+///
+///     // When not compiling to WebAssembly, routes imported functions through
+///     // a `mock::Mock` trait generated next to them, and implementations of
+///     // it can be installed per-thread with `mock::set`. This makes it
+///     // possible to test guest code natively and requires `std` there.
+///     mocks: false,
+///
+///     // Passes strings and lists of plain-old-data to exported functions as
+///     // `&str` and `&[T]`, borrowing the buffer the caller passed in until
+///     // the function returns, instead of as `String` and `Vec<T>`.
+///     borrowed_export_params: false,
+///
+///     // Wraps every call to an import and every call into an export in a
+///     // `tracing` span named after the function which records its interface
+///     // and primitive arguments. No code is emitted for this by default, and
+///     // enabling it requires a dependency on `tracing`.
+///     tracing: false,
+///
+///     // Levels of the spans emitted by `tracing` for specific interfaces,
+///     // which are `info` by default.
+///     tracing_levels: {
+///         "wasi:filesystem/types@0.2.0": debug,
+///     },
+///
+///     // Makes the methods of `Guest` traits take `&self` and dispatches
+///     // exported functions to the implementations registered with each
+///     // export's `set_export_impl(Box<dyn Guest>)` instead of to the type
+///     // passed to `export!`, which then takes no arguments. This can't be
+///     // used with `async` or with worlds exporting resources.
+///     dyn_exports: false,
+///
+///     // Additional derives for all types of an interface or for a single
+///     // type. Derives which a type of an interface can't support, such as
+///     // `Eq` for a type containing a float, are skipped for that type, but
+///     // they're an error when the type is named itself.
+///     derives: {
+///         "wasi:http/types@0.2.0": [Hash],
+///         "wasi:http/types@0.2.0/method": [serde::Serialize],
+///     },
+///
+///     // Additional attributes for all types of an interface or for a single
+///     // type, keyed like `derives`. Enums, flags and records without lists or
+///     // resources already have a `#[repr]` which can't be changed.
+///     attributes: {
+///         "wasi:http/types@0.2.0/error-code": [#[non_exhaustive]],
+///     },
 /// });
 /// ```
 ///
@@ -824,6 +994,8 @@ pub use wit_bindgen_rust_macro::generate;
 #[doc(hidden)]
 pub use rt::bitflags;
 
+pub use wit_bindgen_rt::allocator;
+
 mod pre_wit_bindgen_0_20_0;
 
 #[cfg(docsrs)]
@@ -845,5 +1017,8 @@ pub mod rt {
     #[cfg(all(feature = "realloc", not(target_env = "p2")))]
     pub use wit_bindgen_rt::cabi_realloc;
 
+    #[cfg(feature = "async")]
+    pub use wit_bindgen_rt::async_support;
+
     pub use crate::pre_wit_bindgen_0_20_0::*;
 }
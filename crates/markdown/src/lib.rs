@@ -4,7 +4,7 @@ use pulldown_cmark::{html, Event, LinkType, Parser, Tag};
 use std::collections::HashMap;
 use std::fmt::Write;
 use wit_bindgen_core::{
-    uwriteln, wit_parser, Files, InterfaceGenerator as _, Source, WorldGenerator,
+    deprecation, uwriteln, wit_parser, Files, InterfaceGenerator as _, Source, WorldGenerator,
 };
 use wit_parser::*;
 
@@ -127,7 +127,9 @@ impl WorldGenerator for Markdown {
         self.hrefs
             .insert(name.to_string(), format!("#{}", name.to_snake_case()));
         let mut gen = self.interface(resolve);
-        gen.docs(&resolve.interfaces[id].docs);
+        let iface = &resolve.interfaces[id];
+        gen.stability(&iface.stability, &iface.docs);
+        gen.docs(&iface.docs);
         gen.push_str("\n");
         gen.types(id);
         gen.funcs(id);
@@ -166,6 +168,8 @@ impl WorldGenerator for Markdown {
         self.hrefs
             .insert(name.to_string(), format!("#{}", name.to_snake_case()));
         let mut gen = self.interface(resolve);
+        let iface = &resolve.interfaces[id];
+        gen.stability(&iface.stability, &iface.docs);
         gen.types(id);
         gen.funcs(id);
         Ok(())
@@ -274,6 +278,7 @@ impl InterfaceGenerator<'_> {
         self.push_str(&func.name);
         self.push_str(": func`");
         self.push_str("\n\n");
+        self.stability(&func.stability, &func.docs);
         self.docs(&func.docs);
 
         if func.params.len() > 0 {
@@ -462,7 +467,35 @@ impl InterfaceGenerator<'_> {
         }
     }
 
-    fn print_type_header(&mut self, type_: &str, name: &str) {
+    /// Prints badges for the `@since`/`@unstable` gates and `@deprecated` doc
+    /// tag of an item, if it has any.
+    fn stability(&mut self, stability: &Stability, docs: &Docs) {
+        let mut badges = Vec::new();
+        match stability {
+            Stability::Stable { since, feature } => {
+                let mut badge = format!("*Since `{since}`*");
+                if let Some(feature) = feature {
+                    badge.push_str(&format!(" (feature `{feature}`)"));
+                }
+                badges.push(badge);
+            }
+            Stability::Unstable { feature } => {
+                badges.push(format!("**Unstable** (feature `{feature}`)"));
+            }
+            Stability::Unknown => {}
+        }
+        match deprecation(docs) {
+            Some("") => badges.push("**Deprecated**".to_string()),
+            Some(note) => badges.push(format!("**Deprecated**: {note}")),
+            None => {}
+        }
+        if !badges.is_empty() {
+            self.push_str(&badges.join(" | "));
+            self.push_str("\n\n");
+        }
+    }
+
+    fn print_type_header(&mut self, id: TypeId, type_: &str, name: &str) {
         if !self.types_header_printed {
             self.push_str("----\n\n");
             self.push_str("### Types\n\n");
@@ -477,6 +510,8 @@ impl InterfaceGenerator<'_> {
         self.gen
             .hrefs
             .insert(name.to_string(), format!("#{}", name.to_snake_case()));
+        let ty = &self.resolve.types[id];
+        self.stability(&ty.stability, &ty.docs);
     }
 }
 
@@ -485,8 +520,8 @@ impl<'a> wit_bindgen_core::InterfaceGenerator<'a> for InterfaceGenerator<'a> {
         self.resolve
     }
 
-    fn type_record(&mut self, _id: TypeId, name: &str, record: &Record, docs: &Docs) {
-        self.print_type_header("record", name);
+    fn type_record(&mut self, id: TypeId, name: &str, record: &Record, docs: &Docs) {
+        self.print_type_header(id, "record", name);
         self.push_str("\n");
         self.docs(docs);
         self.push_str("\n##### Record Fields\n\n");
@@ -512,14 +547,14 @@ impl<'a> wit_bindgen_core::InterfaceGenerator<'a> for InterfaceGenerator<'a> {
         }
     }
 
-    fn type_resource(&mut self, _id: TypeId, name: &str, docs: &Docs) {
-        self.print_type_header("resource", name);
+    fn type_resource(&mut self, id: TypeId, name: &str, docs: &Docs) {
+        self.print_type_header(id, "resource", name);
         self.push_str("\n");
         self.docs(docs);
     }
 
-    fn type_tuple(&mut self, _id: TypeId, name: &str, tuple: &Tuple, docs: &Docs) {
-        self.print_type_header("tuple", name);
+    fn type_tuple(&mut self, id: TypeId, name: &str, tuple: &Tuple, docs: &Docs) {
+        self.print_type_header(id, "tuple", name);
         self.push_str("\n");
         self.docs(docs);
         self.push_str("\n##### Tuple Fields\n\n");
@@ -539,8 +574,8 @@ impl<'a> wit_bindgen_core::InterfaceGenerator<'a> for InterfaceGenerator<'a> {
         }
     }
 
-    fn type_flags(&mut self, _id: TypeId, name: &str, flags: &Flags, docs: &Docs) {
-        self.print_type_header("flags", name);
+    fn type_flags(&mut self, id: TypeId, name: &str, flags: &Flags, docs: &Docs) {
+        self.print_type_header(id, "flags", name);
         self.push_str("\n");
         self.docs(docs);
         self.push_str("\n##### Flags members\n\n");
@@ -565,8 +600,8 @@ impl<'a> wit_bindgen_core::InterfaceGenerator<'a> for InterfaceGenerator<'a> {
         }
     }
 
-    fn type_variant(&mut self, _id: TypeId, name: &str, variant: &Variant, docs: &Docs) {
-        self.print_type_header("variant", name);
+    fn type_variant(&mut self, id: TypeId, name: &str, variant: &Variant, docs: &Docs) {
+        self.print_type_header(id, "variant", name);
         self.push_str("\n");
         self.docs(docs);
         self.push_str("\n##### Variant Cases\n\n");
@@ -595,8 +630,8 @@ impl<'a> wit_bindgen_core::InterfaceGenerator<'a> for InterfaceGenerator<'a> {
         }
     }
 
-    fn type_enum(&mut self, _id: TypeId, name: &str, enum_: &Enum, docs: &Docs) {
-        self.print_type_header("enum", name);
+    fn type_enum(&mut self, id: TypeId, name: &str, enum_: &Enum, docs: &Docs) {
+        self.print_type_header(id, "enum", name);
         self.push_str("\n");
         self.docs(docs);
         self.push_str("\n##### Enum Cases\n\n");
@@ -621,8 +656,8 @@ impl<'a> wit_bindgen_core::InterfaceGenerator<'a> for InterfaceGenerator<'a> {
         }
     }
 
-    fn type_option(&mut self, _id: TypeId, name: &str, payload: &Type, docs: &Docs) {
-        self.print_type_header("type", name);
+    fn type_option(&mut self, id: TypeId, name: &str, payload: &Type, docs: &Docs) {
+        self.print_type_header(id, "type", name);
         self.push_str("option<");
         self.print_ty(payload);
         self.push_str(">");
@@ -630,8 +665,8 @@ impl<'a> wit_bindgen_core::InterfaceGenerator<'a> for InterfaceGenerator<'a> {
         self.docs(docs);
     }
 
-    fn type_result(&mut self, _id: TypeId, name: &str, result: &Result_, docs: &Docs) {
-        self.print_type_header("type", name);
+    fn type_result(&mut self, id: TypeId, name: &str, result: &Result_, docs: &Docs) {
+        self.print_type_header(id, "type", name);
         match (result.ok, result.err) {
             (Some(ok), Some(err)) => {
                 self.push_str("result<");
@@ -658,8 +693,8 @@ impl<'a> wit_bindgen_core::InterfaceGenerator<'a> for InterfaceGenerator<'a> {
         self.docs(docs);
     }
 
-    fn type_alias(&mut self, _id: TypeId, name: &str, ty: &Type, docs: &Docs) {
-        self.print_type_header("type", name);
+    fn type_alias(&mut self, id: TypeId, name: &str, ty: &Type, docs: &Docs) {
+        self.print_type_header(id, "type", name);
         self.print_ty(ty);
         self.push_str("\n<p>");
         self.docs(docs);
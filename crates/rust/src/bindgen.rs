@@ -13,6 +13,7 @@ pub(super) struct FunctionBindgen<'a, 'b> {
     block_storage: Vec<(Source, Vec<(String, String)>)>,
     tmp: usize,
     pub needs_cleanup_list: bool,
+    pub needs_keep_alive: bool,
     cleanup: Vec<(String, String)>,
     pub import_return_pointer_area_size: usize,
     pub import_return_pointer_area_align: usize,
@@ -32,6 +33,7 @@ impl<'a, 'b> FunctionBindgen<'a, 'b> {
             block_storage: Vec::new(),
             tmp: 0,
             needs_cleanup_list: false,
+            needs_keep_alive: false,
             cleanup: Vec::new(),
             import_return_pointer_area_size: 0,
             import_return_pointer_area_align: 0,
@@ -189,12 +191,48 @@ impl<'a, 'b> FunctionBindgen<'a, 'b> {
 
     fn typename_lower(&self, id: TypeId) -> String {
         let owned = match self.lift_lower() {
-            LiftLower::LowerArgsLiftResults => false,
+            LiftLower::LowerArgsLiftResults => self.gen.with_type(id).is_some(),
             LiftLower::LiftArgsLowerResults => true,
         };
         self.gen.type_path(id, owned)
     }
 
+    /// Converts `operand`, a value of the type `path` which the type `id` was
+    /// remapped to with `with`, to the generated type for `id`.
+    fn with_lower(&mut self, id: TypeId, path: &str, operand: &str) -> String {
+        let ty = self.gen.type_path(id, true);
+        if !self.gen.in_import {
+            return format!("{ty}::from({operand})");
+        }
+
+        // Arguments to imports may be borrowed, so they're cloned to be
+        // converted. Lowered lists point into the converted value, which has
+        // to outlive the call as a result.
+        let tmp = self.tmp();
+        let value = format!("{ty}::from(<{path} as ::core::clone::Clone>::clone(&{operand}))");
+        if !self.gen.gen.types.get(id).has_list {
+            uwriteln!(self.src, "let with{tmp} = {value};");
+            return format!("with{tmp}");
+        }
+        self.needs_keep_alive = true;
+        let box_ = self.gen.path_to_box();
+        uwriteln!(self.src, "let with{tmp} = {box_}::new({value});");
+        uwriteln!(self.src, "let with_ptr{tmp}: *const {ty} = &*with{tmp};");
+        uwriteln!(self.src, "keep_alive.push(with{tmp});");
+        format!("(&*with_ptr{tmp})")
+    }
+
+    /// Converts `value`, a lifted value of the generated type for `id`, to
+    /// the type `path` which `id` was remapped to with `with`.
+    fn with_lift(&mut self, id: TypeId, path: &str, value: &str) -> String {
+        let ty = self.gen.type_path(id, true);
+        let name = self.gen.resolve.types[id].name.as_deref().unwrap();
+        format!(
+            "<{path} as ::core::convert::TryFrom<{ty}>>::try_from({value})
+                .unwrap_or_else(|_| panic!(\"failed to convert `{name}` to `{path}`\"))"
+        )
+    }
+
     fn typename_lift(&self, id: TypeId) -> String {
         self.gen.type_path(id, true)
     }
@@ -287,14 +325,8 @@ impl Bindgen for FunctionBindgen<'_, '_> {
         &self.gen.sizes
     }
 
-    fn is_list_canonical(&self, resolve: &Resolve, ty: &Type) -> bool {
-        if !resolve.all_bits_valid(ty) {
-            return false;
-        }
-        match ty {
-            Type::Id(id) => !self.gen.gen.types.get(*id).has_resource,
-            _ => true,
-        }
+    fn is_list_canonical(&self, _resolve: &Resolve, ty: &Type) -> bool {
+        self.gen.is_list_canonical(ty)
     }
 
     fn emit(
@@ -304,6 +336,31 @@ impl Bindgen for FunctionBindgen<'_, '_> {
         operands: &mut Vec<String>,
         results: &mut Vec<String>,
     ) {
+        // Values of types remapped with `with` are converted to the generated
+        // type before they're lowered and from it after they're lifted.
+        let with_lift = match inst {
+            Instruction::RecordLower { ty, .. }
+            | Instruction::TupleLower { ty, .. }
+            | Instruction::FlagsLower { ty, .. }
+            | Instruction::VariantLower { ty, .. }
+            | Instruction::EnumLower { ty, .. }
+            | Instruction::OptionLower { ty, .. }
+            | Instruction::ResultLower { ty, .. } => {
+                if let Some(path) = self.gen.with_type(*ty) {
+                    operands[0] = self.with_lower(*ty, &path, &operands[0]);
+                }
+                None
+            }
+            Instruction::RecordLift { ty, .. }
+            | Instruction::TupleLift { ty, .. }
+            | Instruction::FlagsLift { ty, .. }
+            | Instruction::VariantLift { ty, .. }
+            | Instruction::EnumLift { ty, .. }
+            | Instruction::OptionLift { ty, .. }
+            | Instruction::ResultLift { ty, .. } => self.gen.with_type(*ty).map(|path| (*ty, path)),
+            _ => None,
+        };
+
         let mut top_as = |cvt: &str| {
             let mut s = operands.pop().unwrap();
             s.push_str(" as ");
@@ -423,6 +480,27 @@ impl Bindgen for FunctionBindgen<'_, '_> {
                 results.push(format!("({op}).handle() as i32"))
             }
 
+            Instruction::FutureLower { .. } | Instruction::StreamLower { .. } => {
+                let op = &operands[0];
+                results.push(format!("({op}).take_handle() as i32"))
+            }
+
+            Instruction::FutureLift { .. } => {
+                let async_support = self.gen.path_to_async_support();
+                let op = &operands[0];
+                results.push(format!(
+                    "{async_support}::FutureReader::from_handle({op} as u32)"
+                ))
+            }
+
+            Instruction::StreamLift { .. } => {
+                let async_support = self.gen.path_to_async_support();
+                let op = &operands[0];
+                results.push(format!(
+                    "{async_support}::StreamReader::from_handle({op} as u32)"
+                ))
+            }
+
             Instruction::HandleLift { handle, .. } => {
                 let op = &operands[0];
                 let (is_own, resource) = match handle {
@@ -798,17 +876,36 @@ impl Bindgen for FunctionBindgen<'_, '_> {
             }
 
             Instruction::CallInterface { func, .. } => {
+                let span = self.gen.tracing_span(func, "export", operands);
+                self.push_str(&span);
                 self.let_results(func.results.len(), results);
+                // Async exports are driven to completion by the runtime's
+                // executor before their results are lowered.
+                let async_ = self.gen.gen.opts.async_;
+                let block_on = if async_ {
+                    let async_support = self.gen.path_to_async_support();
+                    format!("{async_support}::block_on(")
+                } else {
+                    String::new()
+                };
                 match &func.kind {
+                    // Exports dispatch to the implementation registered with
+                    // `set_export_impl`.
+                    FunctionKind::Freestanding if self.gen.gen.opts.dyn_exports => {
+                        self.push_str(&format!(
+                            "_with_export_impl(|i| i.get()).{}",
+                            to_rust_ident(&func.name)
+                        ));
+                    }
                     FunctionKind::Freestanding => {
-                        self.push_str(&format!("T::{}", to_rust_ident(&func.name)));
+                        self.push_str(&format!("{block_on}T::{}", to_rust_ident(&func.name)));
                     }
                     FunctionKind::Method(_) | FunctionKind::Static(_) => {
-                        self.push_str(&format!("T::{}", to_rust_ident(func.item_name())));
+                        self.push_str(&format!("{block_on}T::{}", to_rust_ident(func.item_name())));
                     }
                     FunctionKind::Constructor(ty) => {
                         self.push_str(&format!(
-                            "{}::new(T::new",
+                            "{}::new({block_on}T::new",
                             resolve.types[*ty]
                                 .name
                                 .as_deref()
@@ -823,6 +920,11 @@ impl Bindgen for FunctionBindgen<'_, '_> {
                         self.push_str(", ");
                     }
 
+                    // Borrowed parameters are dropped, freeing the buffer
+                    // they were lifted from, once the call returns.
+                    if self.gen.is_borrowed_export_param(&func.params[i].1) {
+                        self.push_str("&");
+                    }
                     self.push_str(operand);
 
                     // Automatically convert `Borrow<'_, AResource>` to
@@ -833,6 +935,9 @@ impl Bindgen for FunctionBindgen<'_, '_> {
                     }
                 }
                 self.push_str(")");
+                if async_ {
+                    self.push_str(")");
+                }
                 if let FunctionKind::Constructor(_) = &func.kind {
                     self.push_str(")");
                 }
@@ -1067,5 +1172,11 @@ impl Bindgen for FunctionBindgen<'_, '_> {
                 self.push_str(&format!("{dealloc}({base}, {len} * {size}, {align});\n",));
             }
         }
+
+        if let Some((ty, path)) = with_lift {
+            let value = results.pop().unwrap();
+            let value = self.with_lift(ty, &path, &value);
+            results.push(value);
+        }
     }
 }
@@ -3,14 +3,15 @@ use crate::{
     int_repr, to_rust_ident, to_upper_camel_case, wasm_type, FnSig, Identifier, InterfaceName,
     Ownership, RuntimeItem, RustFlagsRepr, RustWasm,
 };
-use anyhow::Result;
+use anyhow::{bail, Result};
 use heck::*;
 use std::collections::{BTreeMap, BTreeSet};
 use std::fmt::Write as _;
 use std::mem;
 use wit_bindgen_core::abi::{self, AbiVariant, LiftLower};
 use wit_bindgen_core::{
-    dealias, uwrite, uwriteln, wit_parser::*, AnonymousTypeGenerator, Source, TypeInfo,
+    dealias, deprecation, stability_feature, uwrite, uwriteln, wit_parser::*,
+    AnonymousTypeGenerator, InterfaceGenerator as _, Source, TypeInfo,
 };
 
 pub struct InterfaceGenerator<'a> {
@@ -24,6 +25,9 @@ pub struct InterfaceGenerator<'a> {
     pub return_pointer_area_size: usize,
     pub return_pointer_area_align: usize,
     pub(super) needs_runtime_module: bool,
+    /// Whether code is being generated for the `mock` submodule of imports,
+    /// which is one module deeper than everything else.
+    pub(super) in_mock_module: bool,
 }
 
 /// A description of the "mode" in which a type is printed.
@@ -139,12 +143,24 @@ impl InterfaceGenerator<'_> {
 
         traits.insert(None, ("Guest".to_string(), Vec::new()));
 
-        if let Some((id, _)) = interface {
+        let dyn_exports = self.gen.opts.dyn_exports;
+        if dyn_exports && self.gen.opts.async_ {
+            bail!("`dyn_exports` can't be used with `async`");
+        }
+
+        if let Some((id, key)) = interface {
             for (name, id) in self.resolve.interfaces[id].types.iter() {
                 match self.resolve.types[*id].kind {
                     TypeDefKind::Resource => {}
                     _ => continue,
                 }
+                if dyn_exports {
+                    bail!(
+                        "exported resources aren't supported with `dyn_exports`: \
+                         `{name}` in `{}`",
+                        self.resolve.name_world_key(key)
+                    );
+                }
                 resources_to_drop.push(name);
                 let camel = name.to_upper_camel_case();
                 traits.insert(Some(*id), (format!("Guest{camel}"), Vec::new()));
@@ -169,14 +185,22 @@ impl InterfaceGenerator<'_> {
 
             let prev = mem::take(&mut self.src);
             let mut sig = FnSig {
+                async_: self.gen.opts.async_,
                 use_item_name: true,
                 private: true,
                 ..Default::default()
             };
-            if let FunctionKind::Method(_) = &func.kind {
-                sig.self_arg = Some("&self".into());
-                sig.self_is_first_param = true;
+            match &func.kind {
+                FunctionKind::Method(_) => {
+                    sig.self_arg = Some("&self".into());
+                    sig.self_is_first_param = true;
+                }
+                FunctionKind::Freestanding if dyn_exports => {
+                    sig.self_arg = Some("&self".into());
+                }
+                _ => {}
             }
+            self.print_stability_attrs(&func.stability, &func.docs, false);
             self.print_signature(func, true, &sig);
             self.src.push_str(";\n");
             let trait_method = mem::replace(&mut self.src, prev);
@@ -193,8 +217,14 @@ impl InterfaceGenerator<'_> {
                 }),
             )
         }
+        if dyn_exports && !methods.is_empty() {
+            self.generate_export_impl_registration(&name);
+        }
 
         for (resource, (trait_name, methods)) in traits.iter() {
+            if self.gen.opts.async_ {
+                uwriteln!(self.src, "#[allow(async_fn_in_trait)]");
+            }
             uwriteln!(self.src, "pub trait {trait_name}: 'static {{");
             let resource = resource.unwrap();
             let resource_name = self.resolve.types[resource].name.as_ref().unwrap();
@@ -282,13 +312,14 @@ fn _resource_rep(handle: u32) -> *mut u8
             ("", "pub(crate)")
         };
 
+        let ty_param = if dyn_exports { "" } else { "$ty:ident " };
         uwriteln!(
             self.src,
             "\
 #[doc(hidden)]
 {macro_export}
 macro_rules! {macro_name} {{
-    ($ty:ident with_types_in $($path_to_types:tt)*) => (const _: () = {{
+    ({ty_param}with_types_in $($path_to_types:tt)*) => (const _: () = {{
 "
         );
 
@@ -336,12 +367,55 @@ macro_rules! {macro_name} {{
         Ok(macro_name)
     }
 
+    /// Generates `set_export_impl` along with the storage for the
+    /// implementation of the dyn-compatible `trait_name` it registers, which
+    /// exported functions dispatch to with `dyn_exports`.
+    fn generate_export_impl_registration(&mut self, trait_name: &str) {
+        let export_impl = self.path_from_runtime_module(RuntimeItem::ExportImpl, "ExportImpl");
+        let box_ = self.path_to_box();
+        uwriteln!(
+            self.src,
+            "
+                #[cfg(target_arch = \"wasm32\")]
+                fn _with_export_impl<R>(f: impl FnOnce(&{export_impl}<dyn {trait_name}>) -> R) -> R {{
+                    static EXPORT_IMPL: {export_impl}<dyn {trait_name}> = {export_impl}::new();
+                    f(&EXPORT_IMPL)
+                }}
+
+                // Bindings are also compiled natively, for example in tests, where
+                // they may run on many threads.
+                #[cfg(not(target_arch = \"wasm32\"))]
+                fn _with_export_impl<R>(f: impl FnOnce(&{export_impl}<dyn {trait_name}>) -> R) -> R {{
+                    ::std::thread_local! {{
+                        static EXPORT_IMPL: {export_impl}<dyn {trait_name}> = const {{ {export_impl}::new() }};
+                    }}
+                    EXPORT_IMPL.with(f)
+                }}
+
+                /// Registers the implementation of [`{trait_name}`] which exported
+                /// functions dispatch to.
+                ///
+                /// This must be called before any of the functions are called, and
+                /// replaces the implementation registered previously, if any. Replaced
+                /// implementations are leaked since calls may still be using them.
+                /// When not compiling to wasm the implementation is only registered
+                /// for the current thread.
+                pub fn set_export_impl(val: {box_}<dyn {trait_name}>) {{
+                    _with_export_impl(|i| i.set(val));
+                }}
+            "
+        );
+    }
+
     fn generate_interface_trait<'a>(
         &mut self,
         trait_name: &str,
         methods: &[Source],
         resource_traits: impl Iterator<Item = (TypeId, &'a str)>,
     ) {
+        if self.gen.opts.async_ {
+            uwriteln!(self.src, "#[allow(async_fn_in_trait)]");
+        }
         uwriteln!(self.src, "pub trait {trait_name} {{");
         for (id, trait_name) in resource_traits {
             let name = self.resolve.types[id]
@@ -358,9 +432,101 @@ macro_rules! {macro_name} {{
     }
 
     pub fn generate_imports<'a>(&mut self, funcs: impl Iterator<Item = &'a Function>) {
-        for func in funcs {
+        let funcs = funcs.collect::<Vec<_>>();
+        for func in funcs.iter() {
             self.generate_guest_import(func);
         }
+        if self.gen.opts.mocks {
+            self.generate_import_mocks(&funcs);
+        }
+    }
+
+    /// Generates the `mock` module which imports are routed through when not
+    /// compiling to WebAssembly.
+    fn generate_import_mocks(&mut self, funcs: &[&Function]) {
+        let module = match self.wasm_import_module {
+            Some("$root") | None => self.resolve.worlds[self.gen.world.unwrap()].name.clone(),
+            Some(module) => module.to_string(),
+        };
+        uwriteln!(
+            self.src,
+            "
+                /// Mocks of the functions imported from `{module}`.
+                ///
+                /// When not compiling to WebAssembly the imported functions
+                /// are forwarded to the [`Mock`](mock::Mock) installed for
+                /// the current thread with [`mock::set`].
+                #[cfg(not(target_arch = \"wasm32\"))]
+                pub mod mock {{
+                    #[allow(unused_imports)]
+                    use super::*;
+
+                    /// An implementation of the functions imported from
+                    /// `{module}`.
+                    ///
+                    /// Every function panics by default, so only the functions
+                    /// which are called need to be implemented.
+                    #[allow(unused_variables)]
+                    pub trait Mock {{
+            "
+        );
+        self.in_mock_module = true;
+        for func in funcs {
+            if self.gen.skip.contains(&func.name) {
+                continue;
+            }
+            if self.gen.opts.feature_gates {
+                if let Some(feature) = stability_feature(&func.stability) {
+                    uwriteln!(self.src, "#[cfg(feature = {feature:?})]");
+                }
+            }
+            let sig = FnSig {
+                private: true,
+                self_arg: Some("&self".into()),
+                ..Default::default()
+            };
+            let func = Function {
+                name: mock_method_name(self.resolve, func),
+                kind: FunctionKind::Freestanding,
+                ..(*func).clone()
+            };
+            self.print_signature(&func, false, &sig);
+            uwriteln!(
+                self.src,
+                "{{ unimplemented!(\"`{module}#{}` is not mocked\") }}",
+                func.name,
+            );
+        }
+        self.in_mock_module = false;
+        uwriteln!(
+            self.src,
+            "
+                    }}
+
+                    ::std::thread_local! {{
+                        static MOCK: ::core::cell::RefCell<Option<::std::rc::Rc<dyn Mock>>> =
+                            ::core::cell::RefCell::new(None);
+                    }}
+
+                    /// Installs `mock` for the current thread, returning the
+                    /// previously installed mock, if any.
+                    pub fn set(mock: impl Mock + 'static) -> Option<::std::rc::Rc<dyn Mock>> {{
+                        MOCK.with(|m| m.borrow_mut().replace(::std::rc::Rc::new(mock)))
+                    }}
+
+                    /// Removes the mock installed for the current thread,
+                    /// returning it if there was one.
+                    pub fn clear() -> Option<::std::rc::Rc<dyn Mock>> {{
+                        MOCK.with(|m| m.borrow_mut().take())
+                    }}
+
+                    pub(super) fn get() -> ::std::rc::Rc<dyn Mock> {{
+                        MOCK.with(|m| m.borrow().clone())
+                            .expect(\"no mock of `{module}` is installed on this thread\")
+                    }}
+                }}
+            "
+        );
     }
 
     pub fn finish(&mut self) -> String {
@@ -391,6 +557,10 @@ macro_rules! {macro_name} {{
     fn path_to_root(&self) -> String {
         let mut path_to_root = String::new();
 
+        if self.in_mock_module {
+            path_to_root.push_str("super::");
+        }
+
         if let Identifier::Interface(_, key) = self.identifier {
             // Escape the submodule for this interface
             path_to_root.push_str("super::");
@@ -425,9 +595,19 @@ macro_rules! {macro_name} {{
     pub fn finish_append_submodule(mut self, snake: &str, module_path: Vec<String>) {
         let module = self.finish();
         let path_to_root = self.path_to_root();
+        let mut cfg = String::new();
+        if let (true, Identifier::Interface(id, _)) = (self.in_import, self.identifier) {
+            let stability = self.gen.types.interface_stability(self.resolve, id);
+            if let (true, Some(feature)) =
+                (self.gen.opts.feature_gates, stability_feature(stability))
+            {
+                cfg = format!("#[cfg(feature = {feature:?})]");
+            }
+        }
         let module = format!(
             "\
-                #[allow(dead_code, clippy::all)]
+                {cfg}
+                #[allow(dead_code, deprecated, clippy::all)]
                 pub mod {snake} {{
                     #[used]
                     #[doc(hidden)]
@@ -464,9 +644,15 @@ macro_rules! {macro_name} {{
                 }
             }
         }
+        self.print_stability_attrs(&func.stability, &func.docs, true);
+        if self.gen.opts.mocks {
+            self.src.push_str("#[cfg(target_arch = \"wasm32\")]\n");
+        }
         self.src.push_str("#[allow(unused_unsafe, clippy::all)]\n");
         let params = self.print_signature(func, false, &sig);
         self.src.push_str("{\n");
+        let span = self.tracing_span(func, "import", &params);
+        self.src.push_str(&span);
         self.src.push_str("unsafe {\n");
 
         let mut f = FunctionBindgen::new(self, params);
@@ -479,6 +665,7 @@ macro_rules! {macro_name} {{
         );
         let FunctionBindgen {
             needs_cleanup_list,
+            needs_keep_alive,
             src,
             import_return_pointer_area_size,
             import_return_pointer_area_align,
@@ -490,6 +677,14 @@ macro_rules! {macro_name} {{
             let vec = self.path_to_vec();
             uwriteln!(self.src, "let mut cleanup_list = {vec}::new();");
         }
+        if needs_keep_alive {
+            let vec = self.path_to_vec();
+            let box_ = self.path_to_box();
+            uwriteln!(
+                self.src,
+                "let mut keep_alive = {vec}::<{box_}<dyn ::core::any::Any>>::new();"
+            );
+        }
         assert!(handle_decls.is_empty());
         if import_return_pointer_area_size > 0 {
             uwrite!(
@@ -506,6 +701,21 @@ macro_rules! {macro_name} {{
         self.src.push_str("}\n");
         self.src.push_str("}\n");
 
+        if self.gen.opts.mocks {
+            self.print_stability_attrs(&func.stability, &func.docs, true);
+            self.src.push_str("#[cfg(not(target_arch = \"wasm32\"))]\n");
+            let params = self.print_signature(func, false, &sig);
+            // Arguments are passed to the mock exactly as they were given.
+            let args = params
+                .iter()
+                .map(|param| param.trim_start_matches('&'))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let method = to_rust_ident(&mock_method_name(self.resolve, func));
+            let span = self.tracing_span(func, "import", &params);
+            uwriteln!(self.src, "{{ {span}mock::get().{method}({args}) }}");
+        }
+
         match func.kind {
             FunctionKind::Freestanding => {}
             FunctionKind::Method(_) | FunctionKind::Static(_) | FunctionKind::Constructor(_) => {
@@ -516,12 +726,24 @@ macro_rules! {macro_name} {{
 
     fn generate_guest_export(&mut self, func: &Function, trait_name: &str) {
         let name_snake = func.name.to_snake_case().replace('.', "_");
+        // The trait method may be `#[deprecated]`, but calling it from the
+        // bindings shouldn't warn.
+        if deprecation(&func.docs).is_some() {
+            uwriteln!(self.src, "#[allow(deprecated)]");
+        }
+        // Implementations registered with `set_export_impl` are called
+        // rather than `T`.
+        let generics = if self.gen.opts.dyn_exports {
+            String::new()
+        } else {
+            format!("<T: {trait_name}>")
+        };
         uwrite!(
             self.src,
             "\
                 #[doc(hidden)]
                 #[allow(non_snake_case)]
-                pub unsafe fn _export_{name_snake}_cabi<T: {trait_name}>\
+                pub unsafe fn _export_{name_snake}_cabi{generics}\
 ",
         );
         let params = self.print_export_sig(func);
@@ -571,7 +793,7 @@ macro_rules! {macro_name} {{
                 "\
                     #[doc(hidden)]
                     #[allow(non_snake_case)]
-                    pub unsafe fn __post_return_{name_snake}<T: {trait_name}>\
+                    pub unsafe fn __post_return_{name_snake}{generics}\
 "
             );
             let params = self.print_post_return_sig(func);
@@ -592,6 +814,15 @@ macro_rules! {macro_name} {{
         }
     }
 
+    /// Returns the ownership mode of `func`, an imported function.
+    fn func_ownership(&self, func: &Function) -> Ownership {
+        let interface = match self.identifier {
+            Identifier::Interface(_, key) => Some(self.resolve.name_world_key(key)),
+            Identifier::World(_) => None,
+        };
+        self.gen.func_ownership(interface.as_deref(), func)
+    }
+
     fn generate_raw_cabi_export(&mut self, func: &Function, ty: &str, path_to_self: &str) {
         let name_snake = func.name.to_snake_case().replace('.', "_");
         let wasm_module_export_name = match self.identifier {
@@ -610,9 +841,14 @@ macro_rules! {macro_name} {{
 
         let params = self.print_export_sig(func);
         self.push_str(" {\n");
+        let turbofish = if self.gen.opts.dyn_exports {
+            String::new()
+        } else {
+            format!("::<{ty}>")
+        };
         uwriteln!(
             self.src,
-            "{path_to_self}::_export_{name_snake}_cabi::<{ty}>({})",
+            "{path_to_self}::_export_{name_snake}_cabi{turbofish}({})",
             params.join(", ")
         );
         self.push_str("}\n");
@@ -630,7 +866,7 @@ macro_rules! {macro_name} {{
             self.src.push_str("{\n");
             uwriteln!(
                 self.src,
-                "{path_to_self}::__post_return_{name_snake}::<{ty}>({})",
+                "{path_to_self}::__post_return_{name_snake}{turbofish}({})",
                 params.join(", ")
             );
             self.src.push_str("}\n");
@@ -715,6 +951,8 @@ macro_rules! {macro_name} {{
         extra_trait_items: &str,
         funcs: &[&Function],
     ) {
+        // Stubs implement deprecated items as well, which shouldn't warn.
+        uwriteln!(self.src, "#[allow(deprecated)]");
         uwriteln!(self.src, "impl {trait_name} for Stub {{");
         self.src.push_str(extra_trait_items);
 
@@ -723,13 +961,20 @@ macro_rules! {macro_name} {{
                 continue;
             }
             let mut sig = FnSig {
+                async_: self.gen.opts.async_,
                 use_item_name: true,
                 private: true,
                 ..Default::default()
             };
-            if let FunctionKind::Method(_) = &func.kind {
-                sig.self_arg = Some("&self".into());
-                sig.self_is_first_param = true;
+            match &func.kind {
+                FunctionKind::Method(_) => {
+                    sig.self_arg = Some("&self".into());
+                    sig.self_is_first_param = true;
+                }
+                FunctionKind::Freestanding if self.gen.opts.dyn_exports => {
+                    sig.self_arg = Some("&self".into());
+                }
+                _ => {}
             }
             self.print_signature(func, true, &sig);
             self.src.push_str("{ unreachable!() }\n");
@@ -738,6 +983,48 @@ macro_rules! {macro_name} {{
         self.src.push_str("}\n");
     }
 
+    /// Prints `#[deprecated]` for deprecated items and, if `gate` is set and
+    /// feature gates are enabled, `#[cfg(feature = "...")]` for items which
+    /// require a WIT feature.
+    fn print_stability_attrs(&mut self, stability: &Stability, docs: &Docs, gate: bool) {
+        self.print_deprecation(docs);
+        if gate && self.gen.opts.feature_gates {
+            if let Some(feature) = stability_feature(stability) {
+                uwriteln!(self.src, "#[cfg(feature = {feature:?})]");
+            }
+        }
+    }
+
+    fn print_deprecation(&mut self, docs: &Docs) {
+        match deprecation(docs) {
+            Some("") => self.push_str("#[deprecated]\n"),
+            Some(note) => uwriteln!(self.src, "#[deprecated(note = {note:?})]"),
+            None => {}
+        }
+    }
+
+    /// Defines the type `id`, wrapping all items generated for it in
+    /// `#[cfg(feature = "...")]` if it requires a WIT feature and feature
+    /// gates are enabled.
+    ///
+    /// Like functions, only imported types are gated.
+    pub fn define_stable_type(&mut self, name: &str, id: TypeId) {
+        let feature = match stability_feature(&self.resolve.types[id].stability) {
+            Some(feature) if self.in_import && self.gen.opts.feature_gates => feature,
+            _ => return self.define_type(name, id),
+        };
+        let prev = mem::take(&mut self.src);
+        self.define_type(name, id);
+        let items = mem::replace(&mut self.src, prev);
+        if items.as_str().trim().is_empty() {
+            return;
+        }
+        let cfg_feature = self.path_from_runtime_module(RuntimeItem::CfgFeature, "cfg_feature");
+        uwriteln!(self.src, "{cfg_feature}! {{ {feature:?};");
+        self.src.push_str(&items);
+        uwriteln!(self.src, "}}");
+    }
+
     fn rustdoc(&mut self, docs: &Docs) {
         let docs = match &docs.contents {
             Some(docs) => docs,
@@ -848,6 +1135,27 @@ macro_rules! {macro_name} {{
             self.push_str(&name);
             self.push_str(": ");
 
+            if params_owned && self.is_borrowed_export_param(param) {
+                let element = match param {
+                    Type::Id(id) => match &self.resolve.types[dealias(self.resolve, *id)].kind {
+                        TypeDefKind::List(element) => Some(element),
+                        _ => None,
+                    },
+                    _ => None,
+                };
+                match element {
+                    Some(element) => {
+                        self.push_str("&[");
+                        self.print_ty(element, TypeMode::owned());
+                        self.push_str("]");
+                    }
+                    None => self.print_borrowed_str("'_"),
+                }
+                self.push_str(",");
+                params.push(name);
+                continue;
+            }
+
             // Select the "style" of mode that the parameter's type will be
             // rendered as. Owned parameters are always owned, that's the easy
             // case. Otherwise it means that we're rendering the arguments to an
@@ -865,7 +1173,7 @@ macro_rules! {macro_name} {{
             let style = if params_owned {
                 TypeOwnershipStyle::Owned
             } else {
-                match self.gen.opts.ownership {
+                match self.func_ownership(func) {
                     Ownership::Owning => TypeOwnershipStyle::OnlyTopBorrowed,
                     Ownership::Borrowing { .. } => TypeOwnershipStyle::Borrowed,
                 }
@@ -984,6 +1292,30 @@ macro_rules! {macro_name} {{
         // type parameter.
 
         let info = self.info(ty);
+
+        // Types remapped with `with` have no lifetime parameters of their own
+        // and are borrowed as a whole when they contain lists.
+        if self.with_type(ty).is_some() {
+            if style == TypeOwnershipStyle::Owned || !info.has_list {
+                return TypeMode::owned();
+            }
+            return TypeMode {
+                lifetime: Some(lt),
+                lists_borrowed: true,
+                style: TypeOwnershipStyle::OnlyTopBorrowed,
+            };
+        }
+
+        // Types whose ownership is `Owning` because an owning import uses
+        // them only have an owned version, so borrowing imports only borrow
+        // their top layer.
+        let style = match (style, self.gen.type_ownership.get(&ty)) {
+            (TypeOwnershipStyle::Borrowed, Some(Ownership::Owning)) => {
+                TypeOwnershipStyle::OnlyTopBorrowed
+            }
+            _ => style,
+        };
+
         let lifetime = if info.has_borrow_handle {
             // Borrowed handles always have a lifetime associated with them so
             // thread it through.
@@ -1007,7 +1339,7 @@ macro_rules! {macro_name} {{
             //   be used for anything because there's no borrows or lists, so
             //   it's skipped.
             None
-        } else if !info.owned || self.uses_two_names(&info) {
+        } else if !info.owned || self.uses_two_names(ty) {
             // This next layer things get a little more interesting. To recap,
             // so far we know that there's no borrowed handles, a borrowed mode
             // is requested, there's no own handles, and there's a list. In that
@@ -1041,7 +1373,7 @@ macro_rules! {macro_name} {{
             // The only possibility at that point is to borrow it at the root
             // but everything else internally is required to be owned from then
             // on.
-            match self.gen.opts.ownership {
+            match self.gen.type_ownership(ty) {
                 Ownership::Owning => Some(lt),
                 Ownership::Borrowing { .. } => {
                     return TypeMode {
@@ -1188,6 +1520,10 @@ macro_rules! {macro_name} {{
             } else {
                 mode
             };
+            if let Some(path) = self.with_type(id) {
+                self.push_str(&path);
+                return;
+            }
             let name = self.type_path(
                 id,
                 match mode.style {
@@ -1253,12 +1589,18 @@ macro_rules! {macro_name} {{
                 return result;
             }
         }
+        // Types remapped with `with` are converted to and from their owned
+        // version only.
+        if self.with_type(ty).is_some() {
+            result.push((self.result_name(ty), TypeMode::owned()));
+            return result;
+        }
         // Generate one mode for when the type is owned and another for when
         // it's borrowed.
         let a = self.type_mode_for_id(ty, TypeOwnershipStyle::Owned, "'a");
         let b = self.type_mode_for_id(ty, TypeOwnershipStyle::Borrowed, "'a");
 
-        if self.uses_two_names(&info) {
+        if self.uses_two_names(ty) {
             // If this type uses two names then, well, it uses two names. In
             // this situation both modes are returned.
             assert!(a != b);
@@ -1267,7 +1609,7 @@ macro_rules! {macro_name} {{
         } else if a == b {
             // If the modes are the same then there's only one result.
             result.push((self.result_name(ty), a));
-        } else if info.owned || matches!(self.gen.opts.ownership, Ownership::Owning) {
+        } else if info.owned || matches!(self.gen.type_ownership(ty), Ownership::Owning) {
             // If this type is owned or if ownership is preferred then the owned
             // variant is used as a priority. This is where the generator's
             // configuration comes into play.
@@ -1292,7 +1634,11 @@ macro_rules! {macro_name} {{
             .collect();
         for (name, mode) in self.modes_of(id) {
             self.rustdoc(docs);
+            self.print_deprecation(docs);
             let mut derives = additional_derives.clone();
+            self.type_derives(id, &mut derives);
+            self.auto_derives(&info, &mut derives);
+            let serde = self.serde_derives(&info, mode, &mut derives);
             if info.is_copy() {
                 self.push_str("#[repr(C)]\n");
                 derives.extend(["Copy", "Clone"].into_iter().map(|s| s.to_string()));
@@ -1304,11 +1650,15 @@ macro_rules! {macro_name} {{
                 self.push_str(&derives.into_iter().collect::<Vec<_>>().join(", "));
                 self.push_str(")]\n")
             }
+            self.print_type_attributes(id);
             self.push_str(&format!("pub struct {}", name));
             self.print_generics(mode.lifetime);
             self.push_str(" {\n");
             for field in record.fields.iter() {
                 self.rustdoc(&field.docs);
+                if serde {
+                    self.push_str(&format!("#[serde(rename = \"{}\")]\n", field.name));
+                }
                 self.push_str("pub ");
                 self.push_str(&to_rust_ident(&field.name));
                 self.push_str(": ");
@@ -1368,10 +1718,14 @@ macro_rules! {macro_name} {{
     {
         self.print_rust_enum(
             id,
-            variant
-                .cases
-                .iter()
-                .map(|c| (c.name.to_upper_camel_case(), &c.docs, c.ty.as_ref())),
+            variant.cases.iter().map(|c| {
+                (
+                    c.name.to_upper_camel_case(),
+                    &c.name[..],
+                    &c.docs,
+                    c.ty.as_ref(),
+                )
+            }),
             docs,
         );
     }
@@ -1379,7 +1733,7 @@ macro_rules! {macro_name} {{
     fn print_rust_enum<'b>(
         &mut self,
         id: TypeId,
-        cases: impl IntoIterator<Item = (String, &'b Docs, Option<&'b Type>)> + Clone,
+        cases: impl IntoIterator<Item = (String, &'b str, &'b Docs, Option<&'b Type>)> + Clone,
         docs: &Docs,
     ) where
         Self: Sized,
@@ -1395,7 +1749,11 @@ macro_rules! {macro_name} {{
             .collect();
         for (name, mode) in self.modes_of(id) {
             self.rustdoc(docs);
+            self.print_deprecation(docs);
             let mut derives = additional_derives.clone();
+            self.type_derives(id, &mut derives);
+            self.auto_derives(&info, &mut derives);
+            let serde = self.serde_derives(&info, mode, &mut derives);
             if info.is_copy() {
                 derives.extend(["Copy", "Clone"].into_iter().map(|s| s.to_string()));
             } else if info.is_clone() {
@@ -1406,11 +1764,15 @@ macro_rules! {macro_name} {{
                 self.push_str(&derives.into_iter().collect::<Vec<_>>().join(", "));
                 self.push_str(")]\n")
             }
+            self.print_type_attributes(id);
             self.push_str(&format!("pub enum {name}"));
             self.print_generics(mode.lifetime);
             self.push_str(" {\n");
-            for (case_name, docs, payload) in cases.clone() {
+            for (case_name, wit_name, docs, payload) in cases.clone() {
                 self.rustdoc(docs);
+                if serde {
+                    self.push_str(&format!("#[serde(rename = \"{wit_name}\")]\n"));
+                }
                 self.push_str(&case_name);
                 if let Some(ty) = payload {
                     self.push_str("(");
@@ -1428,7 +1790,7 @@ macro_rules! {macro_name} {{
                 cases
                     .clone()
                     .into_iter()
-                    .map(|(name, _docs, ty)| (name, ty)),
+                    .map(|(name, _wit_name, _docs, ty)| (name, ty)),
             );
 
             if info.error {
@@ -1500,6 +1862,7 @@ macro_rules! {macro_name} {{
     fn print_typedef_option(&mut self, id: TypeId, payload: &Type, docs: &Docs) {
         for (name, mode) in self.modes_of(id) {
             self.rustdoc(docs);
+            self.print_deprecation(docs);
             self.push_str(&format!("pub type {}", name));
             self.print_generics(mode.lifetime);
             self.push_str("= Option<");
@@ -1511,6 +1874,7 @@ macro_rules! {macro_name} {{
     fn print_typedef_result(&mut self, id: TypeId, result: &Result_, docs: &Docs) {
         for (name, mode) in self.modes_of(id) {
             self.rustdoc(docs);
+            self.print_deprecation(docs);
             self.push_str(&format!("pub type {}", name));
             self.print_generics(mode.lifetime);
             self.push_str("= Result<");
@@ -1536,6 +1900,7 @@ macro_rules! {macro_name} {{
 
         let name = to_upper_camel_case(name);
         self.rustdoc(docs);
+        self.print_deprecation(docs);
         for attr in attrs {
             self.push_str(&format!("{}\n", attr));
         }
@@ -1555,12 +1920,19 @@ macro_rules! {macro_name} {{
                 .into_iter()
                 .map(|s| s.to_string()),
         );
+        self.type_derives(id, &mut derives);
+        self.auto_derives(&info, &mut derives);
+        let serde = self.serde_derives(&info, TypeMode::owned(), &mut derives);
         self.push_str("#[derive(");
         self.push_str(&derives.into_iter().collect::<Vec<_>>().join(", "));
         self.push_str(")]\n");
+        self.print_type_attributes(id);
         self.push_str(&format!("pub enum {name} {{\n"));
         for case in enum_.cases.iter() {
             self.rustdoc(&case.docs);
+            if serde {
+                self.push_str(&format!("#[serde(rename = \"{}\")]\n", case.name));
+            }
             self.push_str(&case_attr(case));
             self.push_str(&case.name.to_upper_camel_case());
             self.push_str(",\n");
@@ -1649,6 +2021,7 @@ macro_rules! {macro_name} {{
     fn print_typedef_alias(&mut self, id: TypeId, ty: &Type, docs: &Docs) {
         for (name, mode) in self.modes_of(id) {
             self.rustdoc(docs);
+            self.print_deprecation(docs);
             self.push_str(&format!("pub type {name}"));
             self.print_generics(mode.lifetime);
             self.push_str(" = ");
@@ -1658,6 +2031,7 @@ macro_rules! {macro_name} {{
 
         if self.is_exported_resource(id) {
             self.rustdoc(docs);
+            self.print_deprecation(docs);
             let name = self.resolve.types[id].name.as_ref().unwrap();
             let name = name.to_upper_camel_case();
             self.push_str(&format!("pub type {name}Borrow<'a>"));
@@ -1669,9 +2043,8 @@ macro_rules! {macro_name} {{
     }
 
     fn param_name(&self, ty: TypeId) -> String {
-        let info = self.info(ty);
         let name = to_upper_camel_case(self.resolve.types[ty].name.as_ref().unwrap());
-        if self.uses_two_names(&info) {
+        if self.uses_two_names(ty) {
             format!("{}Param", name)
         } else {
             name
@@ -1679,19 +2052,19 @@ macro_rules! {macro_name} {{
     }
 
     fn result_name(&self, ty: TypeId) -> String {
-        let info = self.info(ty);
         let name = to_upper_camel_case(self.resolve.types[ty].name.as_ref().unwrap());
-        if self.uses_two_names(&info) {
+        if self.uses_two_names(ty) {
             format!("{}Result", name)
         } else {
             name
         }
     }
 
-    fn uses_two_names(&self, info: &TypeInfo) -> bool {
+    fn uses_two_names(&self, ty: TypeId) -> bool {
+        let info = self.info(ty);
         // Types are only duplicated if explicitly requested ...
         matches!(
-            self.gen.opts.ownership,
+            self.gen.type_ownership(ty),
             Ownership::Borrowing {
                 duplicate_if_necessary: true
             }
@@ -1714,6 +2087,9 @@ macro_rules! {macro_name} {{
             return Some(path_to_root);
         } else {
             let mut full_path = String::new();
+            if self.in_mock_module {
+                full_path.push_str("super::");
+            }
             if let Identifier::Interface(cur, name) = self.identifier {
                 if cur == interface {
                     return None;
@@ -1774,6 +2150,214 @@ macro_rules! {macro_name} {{
         self.gen.types.get(ty)
     }
 
+    /// Returns the Rust type that `ty` was remapped to with `with`, if any.
+    pub fn with_type(&self, ty: TypeId) -> Option<String> {
+        self.gen.with_types.get(&dealias(self.resolve, ty)).cloned()
+    }
+
+    /// Returns whether `ty` contains a type remapped with `with`, meaning that
+    /// its Rust representation differs from its canonical ABI representation.
+    pub fn has_with_type(&self, ty: &Type) -> bool {
+        let Type::Id(id) = ty else {
+            return false;
+        };
+        if self.with_type(*id).is_some() {
+            return true;
+        }
+        match &self.resolve.types[*id].kind {
+            TypeDefKind::Record(r) => r.fields.iter().any(|f| self.has_with_type(&f.ty)),
+            TypeDefKind::Tuple(t) => t.types.iter().any(|t| self.has_with_type(t)),
+            TypeDefKind::Variant(v) => v
+                .cases
+                .iter()
+                .any(|c| c.ty.as_ref().is_some_and(|t| self.has_with_type(t))),
+            TypeDefKind::Option(t) | TypeDefKind::List(t) | TypeDefKind::Type(t) => {
+                self.has_with_type(t)
+            }
+            TypeDefKind::Result(r) => [&r.ok, &r.err]
+                .iter()
+                .any(|t| t.as_ref().is_some_and(|t| self.has_with_type(t))),
+            _ => false,
+        }
+    }
+
+    /// Returns whether a list of `ty` has the same representation in Rust as
+    /// in the canonical ABI, so it can be passed without conversion.
+    pub fn is_list_canonical(&self, ty: &Type) -> bool {
+        match ty {
+            Type::Id(id) => self.gen.types.get(*id).is_pod && !self.has_with_type(ty),
+            _ => self.resolve.all_bits_valid(ty),
+        }
+    }
+
+    /// Returns whether a parameter of type `ty` to an exported function is
+    /// passed as `&str` or `&[T]` because of `borrowed_export_params`.
+    ///
+    /// Only strings and lists of canonical elements are borrowed, and they
+    /// borrow the buffer they were lifted from until the call returns.
+    pub fn is_borrowed_export_param(&self, ty: &Type) -> bool {
+        if !self.gen.opts.borrowed_export_params {
+            return false;
+        }
+        match ty {
+            Type::String => true,
+            Type::Id(id) => match &self.resolve.types[dealias(self.resolve, *id)].kind {
+                TypeDefKind::List(element) => self.is_list_canonical(element),
+                TypeDefKind::Type(Type::String) => true,
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
+    /// Returns a statement entering a `tracing` span for a call to `func`,
+    /// or nothing if `tracing` is disabled.
+    ///
+    /// `kind` is either `import` or `export`, and `args` are expressions for
+    /// the arguments of the call, of which primitives are recorded in the
+    /// span.
+    pub fn tracing_span(&self, func: &Function, kind: &str, args: &[String]) -> String {
+        if !self.gen.opts.tracing {
+            return String::new();
+        }
+        let interface = match self.identifier {
+            Identifier::Interface(_, key) => Some(self.resolve.name_world_key(key)),
+            Identifier::World(_) => None,
+        };
+        let level = self.gen.tracing_level(interface.as_deref()).constant();
+        let mut fields = format!("wit.kind = {kind:?}");
+        if let Some(interface) = &interface {
+            uwrite!(fields, ", wit.interface = {interface:?}");
+        }
+        for ((name, ty), arg) in func.params.iter().zip(args) {
+            // Parameters of imports may be passed on by reference.
+            let arg = arg.trim_start_matches('&');
+            let mut ty = *ty;
+            while let Type::Id(id) = ty {
+                match &self.resolve.types[id].kind {
+                    TypeDefKind::Type(t) => ty = *t,
+                    _ => break,
+                }
+            }
+            match ty {
+                Type::String | Type::Id(_) => {}
+                // `char` doesn't implement `tracing::Value`.
+                Type::Char => uwrite!(fields, ", {name:?} = %{arg}"),
+                _ => uwrite!(fields, ", {name:?} = {arg}"),
+            }
+        }
+        format!(
+            "let _span = ::tracing::span!(::tracing::Level::{level}, {:?}, {fields}).entered();\n",
+            func.name
+        )
+    }
+
+    /// Adds the `serde` traits which a type described by `info` can derive
+    /// when printed in `mode` to `derives`, if enabled.
+    ///
+    /// Types containing resources can't be serialized and are skipped, and
+    /// types borrowing from their input can only be serialized. Returns
+    /// whether any traits were added, in which case fields and cases need
+    /// `#[serde(rename)]` attributes with their WIT names.
+    fn serde_derives(
+        &self,
+        info: &TypeInfo,
+        mode: TypeMode,
+        derives: &mut BTreeSet<String>,
+    ) -> bool {
+        if !self.gen.opts.serde || info.has_resource {
+            return false;
+        }
+        derives.insert("::serde::Serialize".to_string());
+        if mode.lifetime.is_none() {
+            derives.insert("::serde::Deserialize".to_string());
+        }
+        true
+    }
+
+    /// Prints `serde` impls for the flags type `name`, which serialize it as
+    /// a list of the WIT names of the flags which are set.
+    fn print_flags_serde(&mut self, name: &str, flags: &Flags) {
+        let name = name.to_upper_camel_case();
+        let vec = self.path_to_vec();
+        let string = self.path_to_string();
+        let names = flags
+            .flags
+            .iter()
+            .map(|flag| format!("\"{}\"", flag.name))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let mut serialize = String::new();
+        let mut deserialize = String::new();
+        for flag in flags.flags.iter() {
+            let wit_name = &flag.name;
+            let rust_name = flag.name.to_shouty_snake_case();
+            uwriteln!(
+                serialize,
+                "if self.contains(Self::{rust_name}) {{ seq.serialize_element(\"{wit_name}\")?; }}"
+            );
+            uwriteln!(deserialize, "\"{wit_name}\" => Self::{rust_name},");
+        }
+        uwriteln!(
+            self.src,
+            "
+                impl ::serde::Serialize for {name} {{
+                    fn serialize<S: ::serde::Serializer>(&self, serializer: S) -> ::core::result::Result<S::Ok, S::Error> {{
+                        use ::serde::ser::SerializeSeq;
+                        let mut seq = serializer.serialize_seq(None)?;
+                        {serialize}
+                        seq.end()
+                    }}
+                }}
+
+                impl<'de> ::serde::Deserialize<'de> for {name} {{
+                    fn deserialize<D: ::serde::Deserializer<'de>>(deserializer: D) -> ::core::result::Result<Self, D::Error> {{
+                        const NAMES: &[&str] = &[{names}];
+                        let mut flags = Self::empty();
+                        for name in <{vec}<{string}> as ::serde::Deserialize>::deserialize(deserializer)? {{
+                            flags |= match name.as_str() {{
+                                {deserialize}
+                                other => return ::core::result::Result::Err(::serde::de::Error::unknown_variant(other, NAMES)),
+                            }};
+                        }}
+                        ::core::result::Result::Ok(flags)
+                    }}
+                }}
+            "
+        );
+    }
+
+    /// Adds the comparison and hashing traits which a type described by
+    /// `info` can derive to `derives`, if enabled.
+    fn auto_derives(&self, info: &TypeInfo, derives: &mut BTreeSet<String>) {
+        if !self.gen.opts.auto_derives || info.has_resource {
+            return;
+        }
+        derives.extend(["PartialEq", "PartialOrd"].map(String::from));
+        if info.is_eq() {
+            derives.extend(["Eq", "Ord", "Hash"].map(String::from));
+        }
+    }
+
+    /// Adds the derives configured for `id` through `derives` to `derives`.
+    fn type_derives(&self, id: TypeId, derives: &mut BTreeSet<String>) {
+        if let Some(extras) = self.gen.type_extras.get(&id) {
+            derives.extend(extras.derives.iter().cloned());
+        }
+    }
+
+    /// Prints the attributes configured for `id` through `attributes`.
+    fn print_type_attributes(&mut self, id: TypeId) {
+        let attributes = match self.gen.type_extras.get(&id) {
+            Some(extras) => extras.attributes.join("\n"),
+            None => return,
+        };
+        if !attributes.is_empty() {
+            self.push_str(&attributes);
+            self.push_str("\n");
+        }
+    }
+
     fn print_borrowed_str(&mut self, lifetime: &'static str) {
         self.push_str("&");
         if lifetime != "'_" {
@@ -1834,6 +2418,10 @@ macro_rules! {macro_name} {{
         self.path_from_runtime_module(RuntimeItem::BoolLift, "bool_lift")
     }
 
+    pub fn path_to_async_support(&mut self) -> String {
+        self.path_from_runtime_module(RuntimeItem::AsyncSupport, "async_support")
+    }
+
     fn path_to_run_ctors_once(&mut self) -> String {
         self.path_from_runtime_module(RuntimeItem::RunCtorsOnce, "run_ctors_once")
     }
@@ -1870,12 +2458,19 @@ impl<'a> wit_bindgen_core::InterfaceGenerator<'a> for InterfaceGenerator<'a> {
         self.resolve
     }
 
+    fn types(&mut self, iface: InterfaceId) {
+        for (name, id) in self.resolve.interfaces[iface].types.iter() {
+            self.define_stable_type(name, *id);
+        }
+    }
+
     fn type_record(&mut self, id: TypeId, _name: &str, record: &Record, docs: &Docs) {
         self.print_typedef_record(id, record, docs);
     }
 
     fn type_resource(&mut self, _id: TypeId, name: &str, docs: &Docs) {
         self.rustdoc(docs);
+        self.print_deprecation(docs);
         let camel = to_upper_camel_case(name);
         let resource = self.path_to_resource();
 
@@ -1911,11 +2506,52 @@ impl<'a> wit_bindgen_core::InterfaceGenerator<'a> for InterfaceGenerator<'a> {
                     }}
                 "#
             );
-            self.wasm_import_module.unwrap().to_string()
+            let module = self.wasm_import_module.unwrap();
+            // Pollables are awaited with the `poll` function of the version of
+            // `wasi:io/poll` they're imported from.
+            let has_poll = match self.identifier {
+                Identifier::Interface(id, _) => {
+                    self.resolve.interfaces[id].functions.contains_key("poll")
+                }
+                Identifier::World(_) => false,
+            };
+            let is_pollable = name == "pollable"
+                && has_poll
+                && (module == "wasi:io/poll" || module.starts_with("wasi:io/poll@"));
+            if self.gen.opts.async_ && is_pollable {
+                let async_support = self.path_to_async_support();
+                let vec = self.path_to_vec();
+                uwriteln!(
+                    self.src,
+                    r#"
+                        impl {camel} {{
+                            /// Waits for this pollable to become ready without
+                            /// blocking other tasks driven by the executor.
+                            pub async fn wait(&self) {{
+                                fn poll_handles(handles: &[u32]) -> {vec}<u32> {{
+                                    // The pollables are only borrowed here.
+                                    let pollables = handles
+                                        .iter()
+                                        .map(|handle| unsafe {{
+                                            ::core::mem::ManuallyDrop::new({camel}::from_handle(*handle))
+                                        }})
+                                        .collect::<{vec}<_>>();
+                                    let pollables = pollables.iter().map(|p| &**p).collect::<{vec}<_>>();
+                                    poll(&pollables)
+                                }}
+                                {async_support}::wait(self.handle(), poll_handles).await
+                            }}
+                        }}
+                    "#
+                );
+            }
+            module.to_string()
         } else {
             let module = match self.identifier {
                 Identifier::Interface(_, key) => self.resolve.name_world_key(key),
-                Identifier::World(_) => unimplemented!("resource exports from worlds"),
+                // Worlds can't export types, so resources defined in a world
+                // are always imported, even when used by exported functions.
+                Identifier::World(_) => unreachable!("worlds can't export resources"),
             };
             let box_path = self.path_to_box();
             uwriteln!(
@@ -2047,6 +2683,13 @@ impl<'a> {camel}Borrow<'a>{{
         };
 
         let wasm_resource = self.path_to_wasm_resource();
+        // Handles of mocked imports are made up by mocks and there's nothing
+        // to drop.
+        let native_drop = if self.in_import && self.gen.opts.mocks {
+            "{}"
+        } else {
+            "unreachable!();"
+        };
         uwriteln!(
             self.src,
             r#"
@@ -2054,7 +2697,7 @@ impl<'a> {camel}Borrow<'a>{{
                      #[inline]
                      unsafe fn drop(_handle: u32) {{
                          #[cfg(not(target_arch = "wasm32"))]
-                         unreachable!();
+                         {native_drop}
 
                          #[cfg(target_arch = "wasm32")]
                          {{
@@ -2075,6 +2718,7 @@ impl<'a> {camel}Borrow<'a>{{
     fn type_tuple(&mut self, id: TypeId, _name: &str, tuple: &Tuple, docs: &Docs) {
         for (name, mode) in self.modes_of(id) {
             self.rustdoc(docs);
+            self.print_deprecation(docs);
             self.push_str(&format!("pub type {}", name));
             self.print_generics(mode.lifetime);
             self.push_str(" = (");
@@ -2087,15 +2731,33 @@ impl<'a> {camel}Borrow<'a>{{
         }
     }
 
-    fn type_flags(&mut self, _id: TypeId, name: &str, flags: &Flags, docs: &Docs) {
+    fn type_flags(&mut self, id: TypeId, name: &str, flags: &Flags, docs: &Docs) {
         self.src.push_str(&format!(
             "{bitflags}::bitflags! {{\n",
             bitflags = self.gen.bitflags_path()
         ));
         self.rustdoc(docs);
+        self.print_deprecation(docs);
         let repr = RustFlagsRepr::new(flags);
+        let mut derives: BTreeSet<String> = [
+            "PartialEq",
+            "Eq",
+            "PartialOrd",
+            "Ord",
+            "Hash",
+            "Debug",
+            "Clone",
+            "Copy",
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect();
+        self.type_derives(id, &mut derives);
+        let derives = derives.into_iter().collect::<Vec<_>>().join(", ");
+        self.src.push_str(&format!("#[derive({derives})]\n"));
+        self.print_type_attributes(id);
         self.src.push_str(&format!(
-            "#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone, Copy)]\npub struct {}: {repr} {{\n",
+            "pub struct {}: {repr} {{\n",
             name.to_upper_camel_case(),
         ));
         for (i, flag) in flags.flags.iter().enumerate() {
@@ -2108,6 +2770,9 @@ impl<'a> {camel}Borrow<'a>{{
         }
         self.src.push_str("}\n");
         self.src.push_str("}\n");
+        if self.gen.opts.serde {
+            self.print_flags_serde(name, flags);
+        }
     }
 
     fn type_variant(&mut self, id: TypeId, _name: &str, variant: &Variant, docs: &Docs) {
@@ -2159,6 +2824,7 @@ impl<'a> {camel}Borrow<'a>{{
     fn type_list(&mut self, id: TypeId, _name: &str, ty: &Type, docs: &Docs) {
         for (name, mode) in self.modes_of(id) {
             self.rustdoc(docs);
+            self.print_deprecation(docs);
             self.push_str(&format!("pub type {}", name));
             self.print_generics(mode.lifetime);
             self.push_str(" = ");
@@ -2169,6 +2835,7 @@ impl<'a> {camel}Borrow<'a>{{
 
     fn type_builtin(&mut self, _id: TypeId, name: &str, ty: &Type, docs: &Docs) {
         self.rustdoc(docs);
+        self.print_deprecation(docs);
         self.src
             .push_str(&format!("pub type {}", name.to_upper_camel_case()));
         self.src.push_str(" = ");
@@ -2256,13 +2923,17 @@ impl<'a, 'b> wit_bindgen_core::AnonymousTypeGenerator<'a> for AnonTypeGenerator<
     }
 
     fn anonymous_type_future(&mut self, _id: TypeId, ty: &Option<Type>, _docs: &Docs) {
-        self.interface.push_str("Future<");
+        let async_support = self.interface.path_to_async_support();
+        self.interface
+            .push_str(&format!("{async_support}::FutureReader<"));
         self.interface.print_optional_ty(ty.as_ref(), self.mode);
         self.interface.push_str(">");
     }
 
     fn anonymous_type_stream(&mut self, _id: TypeId, stream: &Stream, _docs: &Docs) {
-        self.interface.push_str("Stream<");
+        let async_support = self.interface.path_to_async_support();
+        self.interface
+            .push_str(&format!("{async_support}::StreamReader<"));
         self.interface
             .print_optional_ty(stream.element.as_ref(), self.mode);
         self.interface.push_str(",");
@@ -2271,3 +2942,19 @@ impl<'a, 'b> wit_bindgen_core::AnonymousTypeGenerator<'a> for AnonTypeGenerator<
         self.interface.push_str(">");
     }
 }
+
+/// Returns the name of the method of a `mock::Mock` trait corresponding to the
+/// imported function `func`.
+fn mock_method_name(resolve: &Resolve, func: &Function) -> String {
+    match func.kind {
+        FunctionKind::Freestanding => func.name.clone(),
+        FunctionKind::Method(id) | FunctionKind::Static(id) | FunctionKind::Constructor(id) => {
+            let resource = resolve.types[id].name.as_ref().unwrap();
+            let item = match func.kind {
+                FunctionKind::Constructor(_) => "new",
+                _ => func.item_name(),
+            };
+            format!("{resource}-{item}")
+        }
+    }
+}
@@ -8,8 +8,8 @@ use std::mem;
 use std::str::FromStr;
 use wit_bindgen_core::abi::{Bitcast, WasmType};
 use wit_bindgen_core::{
-    name_package_module, uwrite, uwriteln, wit_parser::*, Files, InterfaceGenerator as _, Source,
-    Types, WorldGenerator,
+    dealias, name_package_module, uwrite, uwriteln, wit_parser::*, Files, InterfaceGenerator as _,
+    Source, TypeInfo, Types, WorldGenerator,
 };
 
 mod bindgen;
@@ -46,6 +46,35 @@ struct RustWasm {
     export_macros: Vec<(String, String)>,
     /// Interface names to how they should be generated
     with: GenerationConfiguration,
+    /// Types remapped with `with` to the Rust type they're represented with.
+    with_types: HashMap<TypeId, String>,
+    /// Keys of `with` naming types which can't be remapped.
+    invalid_with_types: Vec<String>,
+    /// Keys of `with` naming types with the `generate` option, which only
+    /// applies to interfaces.
+    generated_with_types: Vec<String>,
+    /// Derives and attributes added to types through `derives` and
+    /// `attributes`.
+    type_extras: HashMap<TypeId, TypeExtras>,
+    /// Errors found while resolving `derives` and `attributes`.
+    invalid_type_extras: Vec<String>,
+    /// Ownership of types used by imported functions when
+    /// `ownership_overrides` is in use, derived from the ownership of each
+    /// function using them.
+    type_ownership: HashMap<TypeId, Ownership>,
+    /// Keys of `ownership_overrides` which don't name an imported interface
+    /// or function.
+    unused_ownership_overrides: Vec<String>,
+    /// Keys of `tracing_levels` which don't name an imported or exported
+    /// interface.
+    unused_tracing_levels: Vec<String>,
+}
+
+/// Extra derives and attributes for a generated type.
+#[derive(Default)]
+struct TypeExtras {
+    derives: Vec<String>,
+    attributes: Vec<String>,
 }
 
 #[derive(Default)]
@@ -97,6 +126,9 @@ enum RuntimeItem {
     AsF64,
     ResourceType,
     BoxType,
+    AsyncSupport,
+    ExportImpl,
+    CfgFeature,
 }
 
 #[derive(Debug, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
@@ -117,6 +149,30 @@ fn parse_with(s: &str) -> Result<(String, WithOption), String> {
     Ok((k.to_string(), v))
 }
 
+#[cfg(feature = "clap")]
+fn parse_ownership_override(s: &str) -> Result<(String, Ownership), String> {
+    let (k, v) = s.split_once('=').ok_or_else(|| {
+        format!("expected string of form `<key>=<ownership>[,<key>=<ownership>...]`; got `{s}`")
+    })?;
+    Ok((k.to_string(), v.parse()?))
+}
+
+#[cfg(feature = "clap")]
+fn parse_tracing_level(s: &str) -> Result<(String, TracingLevel), String> {
+    let (k, v) = s.split_once('=').ok_or_else(|| {
+        format!("expected string of form `<interface>=<level>[,<interface>=<level>...]`; got `{s}`")
+    })?;
+    Ok((k.to_string(), v.parse()?))
+}
+
+#[cfg(feature = "clap")]
+fn parse_type_extra(s: &str) -> Result<(String, String), String> {
+    let (k, v) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected string of form `<path>=<value>`; got `{s}`"))?;
+    Ok((k.to_string(), v.to_string()))
+}
+
 #[derive(Default, Debug, Clone)]
 #[cfg_attr(feature = "clap", derive(clap::Args))]
 pub struct Opts {
@@ -167,6 +223,21 @@ pub struct Opts {
     #[cfg_attr(feature = "clap", arg(long, default_value_t = Ownership::Owning))]
     pub ownership: Ownership,
 
+    /// Ownership modes for specific imported interfaces or functions,
+    /// overriding `ownership`.
+    ///
+    /// Argument must be of the form `k=v` and this option can be passed
+    /// multiple times or one option can be comma separated. Keys are
+    /// interfaces such as `ns:pkg/iface`, functions within them such as
+    /// `ns:pkg/iface#f`, or functions imported by the world itself such as
+    /// `f`, and a function's own key takes precedence over its interface's.
+    ///
+    /// A type used by several imported functions is only generated with
+    /// borrowing fields if all of them are borrowing, otherwise functions
+    /// which borrow it take a reference to its owning version.
+    #[cfg_attr(feature = "clap", arg(long, value_parser = parse_ownership_override, value_delimiter = ','))]
+    pub ownership_overrides: Vec<(String, Ownership)>,
+
     /// The optional path to the wit-bindgen runtime module to use.
     ///
     /// This defaults to `wit_bindgen::rt`.
@@ -186,14 +257,45 @@ pub struct Opts {
     #[cfg_attr(feature = "clap", arg(long = "additional_derive_attribute", short = 'd', default_values_t = Vec::<String>::new()))]
     pub additional_derive_attributes: Vec<String>,
 
-    /// Remapping of interface names to rust module names.
+    /// Remapping of interface names to rust module names, or of type names to
+    /// Rust types.
     ///
     /// Argument must be of the form `k=v` and this option can be passed
     /// multiple times or one option can be comma separated, for example
     /// `k1=v1,k2=v2`.
+    ///
+    /// A key of the form `ns:pkg/iface/ty` remaps the type `ty` within
+    /// `ns:pkg/iface`. The type is still generated but is converted to and
+    /// from the Rust type with `From` and `TryFrom` wherever it's passed to
+    /// or from functions. Only records, tuples, flags, variants, enums,
+    /// options and results without resources can be remapped, and the Rust
+    /// type must implement the same traits as the generated type. Types
+    /// can't use `generate`.
     #[cfg_attr(feature = "clap", arg(long, value_parser = parse_with, value_delimiter = ','))]
     pub with: Vec<(String, WithOption)>,
 
+    /// Additional derives for the types of a WIT interface or for a single
+    /// WIT type.
+    ///
+    /// Argument must be of the form `path=Derive`, where `path` is either an
+    /// interface such as `ns:pkg/iface` or a type such as `ns:pkg/iface/ty`,
+    /// and this option can be passed multiple times. Derives which a type of
+    /// an interface can't support, such as `Eq` for a type containing a
+    /// float, are skipped for that type, but they're an error when the type
+    /// is named itself.
+    #[cfg_attr(feature = "clap", arg(long = "derive", value_parser = parse_type_extra))]
+    pub derives: Vec<(String, String)>,
+
+    /// Additional attributes, such as `#[non_exhaustive]`, for the types of a
+    /// WIT interface or for a single WIT type.
+    ///
+    /// Argument must be of the form `path=#[attr]` with paths as for
+    /// `derives`, and this option can be passed multiple times. Enums, flags
+    /// and records without lists or resources already have a `#[repr]`, so
+    /// another one can't be added to them.
+    #[cfg_attr(feature = "clap", arg(long = "attribute", value_parser = parse_type_extra))]
+    pub attributes: Vec<(String, String)>,
+
     /// Indicates that all interfaces not specified in `with` should be
     /// generated.
     #[cfg_attr(feature = "clap", arg(long))]
@@ -226,6 +328,95 @@ pub struct Opts {
     /// Whether to generate unused structures, not generated by default (false)
     #[cfg_attr(feature = "clap", arg(long))]
     pub generate_unused_types: bool,
+
+    /// Generate async bindings.
+    ///
+    /// Exported functions become `async fn` trait methods which are driven to
+    /// completion by the executor in `wit-bindgen-rt`, and imported
+    /// `wasi:io/poll` pollables gain an `async fn wait` method. Imported
+    /// functions themselves stay synchronous, with futures and streams passed
+    /// as handles. This requires the `async` feature of the `wit-bindgen`
+    /// crate.
+    #[cfg_attr(feature = "clap", arg(long = "async"))]
+    pub async_: bool,
+
+    /// Wrap imported functions and interfaces annotated with
+    /// `@unstable(feature = x)` or `@since(..., feature = x)` in
+    /// `#[cfg(feature = "x")]` so they're gated behind a Cargo feature of the
+    /// same name.
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub feature_gates: bool,
+
+    /// Derive `PartialEq`, `PartialOrd`, `Eq`, `Ord` and `Hash` for generated
+    /// types wherever all of their contents support it.
+    ///
+    /// Types containing resources get none of these, and types containing
+    /// floats only get `PartialEq` and `PartialOrd`. Types remapped with
+    /// `with` are assumed to implement the same traits as the types they
+    /// replace.
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub auto_derives: bool,
+
+    /// Implement `serde::Serialize` and `serde::Deserialize` for generated
+    /// types, using WIT names for fields, cases and flags.
+    ///
+    /// Records and tuples serialize as maps and sequences, variants and enums
+    /// as externally tagged enums, and flags as a list of the names of the
+    /// flags which are set. Types containing resources are skipped, and types
+    /// which borrow their contents only implement `Serialize`. This requires
+    /// the `serde` crate with its `derive` feature as a dependency.
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub serde: bool,
+
+    /// Pass strings and lists of plain-old-data to exported functions as
+    /// `&str` and `&[T]` rather than `String` and `Vec<T>`.
+    ///
+    /// These borrow the buffer which the caller passed in for the duration
+    /// of the call, and it's freed once the call returns.
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub borrowed_export_params: bool,
+
+    /// Route imported functions through mocks when not compiling to
+    /// WebAssembly.
+    ///
+    /// Each set of imports gets a `mock` module with a `Mock` trait which has
+    /// a method per imported function. On native targets imports call the
+    /// implementation installed for the current thread with `mock::set`,
+    /// which allows testing guest code with `cargo test`. This requires `std`
+    /// on native targets and has no effect on WebAssembly.
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub mocks: bool,
+
+    /// Wrap every call to an imported function and every call into an
+    /// exported function in a `tracing` span.
+    ///
+    /// Spans are named after the WIT function and record the interface it's
+    /// in, whether it's an import or an export, and the values of arguments
+    /// which are primitives. Subscribers measure the duration of a call from
+    /// when its span is entered until it's exited. This requires the
+    /// `tracing` crate as a dependency.
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub tracing: bool,
+
+    /// Levels of the spans emitted by `tracing` for specific imported or
+    /// exported interfaces, which are `info` otherwise.
+    ///
+    /// Argument must be of the form `ns:pkg/iface=level`, where `level` is
+    /// one of `trace`, `debug`, `info`, `warn` or `error`, and this option
+    /// can be passed multiple times or one option can be comma separated.
+    #[cfg_attr(feature = "clap", arg(long, value_parser = parse_tracing_level, value_delimiter = ','))]
+    pub tracing_levels: Vec<(String, TracingLevel)>,
+
+    /// Dispatch exported functions to implementations registered at runtime
+    /// rather than to a type named in the `export!` macro.
+    ///
+    /// Methods of the `Guest` traits take `&self` so they're dyn-compatible,
+    /// and each exported interface, as well as the world itself if it
+    /// exports functions, gets a `set_export_impl` function taking a
+    /// `Box<dyn Guest>`. The `export!` macro then takes no arguments. This
+    /// can't be used with `async` or with worlds exporting resources.
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub dyn_exports: bool,
 }
 
 impl Opts {
@@ -263,6 +454,7 @@ impl RustWasm {
             return_pointer_area_size: 0,
             return_pointer_area_align: 0,
             needs_runtime_module: false,
+            in_mock_module: false,
         }
     }
 
@@ -326,9 +518,12 @@ impl RustWasm {
         let Some(remapping) = self.with.get(&with_name) else {
             bail!(MissingWith(with_name));
         };
-        self.generated_interfaces.insert(with_name);
+        self.generated_interfaces.insert(with_name.clone());
         let entry = match remapping {
             InterfaceGeneration::Remap(remapped_path) => {
+                if remapped_path.contains('<') {
+                    bail!("interface `{with_name}` must be remapped to a module, not `{remapped_path}`");
+                }
                 let name = format!("__with_name{}", self.with_name_counter);
                 self.with_name_counter += 1;
                 uwriteln!(self.src, "use {remapped_path} as {name};");
@@ -467,6 +662,58 @@ pub unsafe fn bool_lift(val: u8) -> bool {
                 );
             }
 
+            RuntimeItem::AsyncSupport => {
+                let rt = self.runtime_path().to_string();
+                uwriteln!(self.src, "pub use {rt}::async_support;");
+            }
+
+            RuntimeItem::CfgFeature => {
+                self.src.push_str(
+                    r#"
+/// Applies `#[cfg(feature = ...)]` to each of the given items.
+macro_rules! cfg_feature {
+    ($feature:tt; $($item:item)*) => {
+        $(#[cfg(feature = $feature)] $item)*
+    };
+}
+pub(crate) use cfg_feature;
+                    "#,
+                );
+            }
+
+            RuntimeItem::ExportImpl => {
+                self.rt_module.insert(RuntimeItem::BoxType);
+                self.src.push_str(
+                    r#"
+/// The implementation of exported functions registered with
+/// `set_export_impl`.
+pub struct ExportImpl<T: ?Sized + 'static>(::core::cell::Cell<Option<&'static T>>);
+
+// Components are single-threaded, so sharing the implementation through a
+// `static` is fine. Native builds keep one per thread instead.
+#[cfg(target_arch = "wasm32")]
+unsafe impl<T: ?Sized> Sync for ExportImpl<T> {}
+
+impl<T: ?Sized> ExportImpl<T> {
+    pub const fn new() -> Self {
+        Self(::core::cell::Cell::new(None))
+    }
+
+    pub fn set(&self, val: Box<T>) {
+        self.0.set(Some(Box::leak(val)));
+    }
+
+    pub fn get(&self) -> &'static T {
+        match self.0.get() {
+            Some(val) => val,
+            None => panic!("no implementation registered with `set_export_impl`"),
+        }
+    }
+}
+                    "#,
+                );
+            }
+
             RuntimeItem::RunCtorsOnce => {
                 let rt = self.runtime_path();
                 self.src.push_str(&format!(
@@ -668,9 +915,31 @@ impl As{upcase} for {to_convert} {{
             .as_deref()
             .unwrap_or("export")
             .to_string();
-        uwriteln!(
-            self.src,
-            r#"
+        // With `dyn_exports` exports dispatch to the implementations
+        // registered at runtime, so no type is passed to the macro.
+        let (ty_param, ty_arg) = if self.opts.dyn_exports {
+            ("", "")
+        } else {
+            ("$ty:ident ", "$ty ")
+        };
+        if self.opts.dyn_exports {
+            uwriteln!(
+                self.src,
+                r#"
+/// Generates `#[no_mangle]` functions for all exports which dispatch to the
+/// implementations registered with `set_export_impl`.
+///
+/// For more information see the documentation of `wit_bindgen::generate!`.
+///
+/// ```rust
+/// # macro_rules! {export_macro_name} {{ ($($t:tt)*) => (); }}
+/// {export_macro_name}!();
+/// ```"#
+            );
+        } else {
+            uwriteln!(
+                self.src,
+                r#"
 /// Generates `#[no_mangle]` functions to export the specified type as the
 /// root implementation of all generated traits.
 ///
@@ -686,13 +955,18 @@ impl As{upcase} for {to_convert} {{
 /// }}
 ///
 /// {export_macro_name}!(MyType);
-/// ```
-#[allow(unused_macros)]
+/// ```"#
+            );
+        }
+        uwriteln!(
+            self.src,
+            r#"#[allow(unused_macros)]
 #[doc(hidden)]
 {macro_export}
 macro_rules! __export_{world_name}_impl {{
-    ($ty:ident) => ({default_bindings_module}::{export_macro_name}!($ty with_types_in {default_bindings_module}););
-    ($ty:ident with_types_in $($path_to_types_root:tt)*) => ("#
+    ({}) => ({default_bindings_module}::{export_macro_name}!({ty_arg}with_types_in {default_bindings_module}););
+    ({ty_param}with_types_in $($path_to_types_root:tt)*) => ("#,
+            ty_param.trim_end()
         );
         for (name, path_to_types) in self.export_macros.iter() {
             let mut path = "$($path_to_types_root)*".to_string();
@@ -700,7 +974,7 @@ macro_rules! __export_{world_name}_impl {{
                 path.push_str("::");
                 path.push_str(path_to_types)
             }
-            uwriteln!(self.src, "{path}::{name}!($ty with_types_in {path});");
+            uwriteln!(self.src, "{path}::{name}!({ty_arg}with_types_in {path});");
         }
 
         // See comments in `finish` for why this conditionally happens here.
@@ -718,7 +992,9 @@ macro_rules! __export_{world_name}_impl {{
             {use_vis} use __export_{world_name}_impl as {export_macro_name};"
         );
 
-        if self.opts.stubs {
+        if self.opts.stubs && self.opts.dyn_exports {
+            uwriteln!(self.src, "export!();");
+        } else if self.opts.stubs {
             uwriteln!(self.src, "export!(Stub);");
         }
     }
@@ -740,6 +1016,42 @@ macro_rules! __export_{world_name}_impl {{
         section_suffix: &str,
         func_name: Option<&str>,
     ) {
+        // `wit-component` can't encode `future` and `stream` types yet, so the
+        // component type of worlds using them has to be embedded separately,
+        // for example with `wasm-tools component embed`.
+        let mut live = LiveTypes::default();
+        live.add_world(resolve, world);
+        let uses_async_types = live.iter().any(|id| {
+            matches!(
+                resolve.types[id].kind,
+                TypeDefKind::Future(_) | TypeDefKind::Stream(_)
+            )
+        });
+        if uses_async_types {
+            self.src.push_str(
+                "\n// The component type isn't embedded as it uses futures or streams.\n",
+            );
+        } else {
+            self.emit_component_type(resolve, world, section_suffix);
+        }
+
+        if let Some(func_name) = func_name {
+            let rt = self.runtime_path().to_string();
+            uwriteln!(
+                self.src,
+                "
+                #[inline(never)]
+                #[doc(hidden)]
+                #[cfg(target_arch = \"wasm32\")]
+                pub fn {func_name}() {{
+                    {rt}::maybe_link_cabi_realloc();
+                }}
+            ",
+            );
+        }
+    }
+
+    fn emit_component_type(&mut self, resolve: &Resolve, world: WorldId, section_suffix: &str) {
         self.src.push_str("\n#[cfg(target_arch = \"wasm32\")]\n");
 
         // The custom section name here must start with "component-type" but
@@ -808,21 +1120,6 @@ macro_rules! __export_{world_name}_impl {{
 
         self.src.push_str("\";\n");
         self.src.set_indent(old_indent);
-
-        if let Some(func_name) = func_name {
-            let rt = self.runtime_path().to_string();
-            uwriteln!(
-                self.src,
-                "
-                #[inline(never)]
-                #[doc(hidden)]
-                #[cfg(target_arch = \"wasm32\")]
-                pub fn {func_name}() {{
-                    {rt}::maybe_link_cabi_realloc();
-                }}
-            ",
-            );
-        }
     }
 }
 
@@ -845,6 +1142,9 @@ impl WorldGenerator for RustWasm {
         if !matches!(self.opts.ownership, Ownership::Owning) {
             uwriteln!(self.src, "//   * ownership: {:?}", self.opts.ownership);
         }
+        for (k, v) in self.opts.ownership_overrides.iter() {
+            uwriteln!(self.src, "//   * ownership {k:?} = {v}");
+        }
         if !self.opts.additional_derive_attributes.is_empty() {
             uwriteln!(
                 self.src,
@@ -855,6 +1155,12 @@ impl WorldGenerator for RustWasm {
         for (k, v) in self.opts.with.iter() {
             uwriteln!(self.src, "//   * with {k:?} = {v}");
         }
+        for (k, v) in self.opts.derives.iter() {
+            uwriteln!(self.src, "//   * derive {k:?} = {v}");
+        }
+        for (k, v) in self.opts.attributes.iter() {
+            uwriteln!(self.src, "//   * attribute {k:?} = {v}");
+        }
         if let Some(default) = &self.opts.default_bindings_module {
             uwriteln!(self.src, "//   * default-bindings-module: {default:?}");
         }
@@ -867,7 +1173,36 @@ impl WorldGenerator for RustWasm {
         if self.opts.pub_export_macro {
             uwriteln!(self.src, "//   * pub-export-macro");
         }
+        if self.opts.async_ {
+            uwriteln!(self.src, "//   * async");
+        }
+        if self.opts.feature_gates {
+            uwriteln!(self.src, "//   * feature-gates");
+        }
+        if self.opts.auto_derives {
+            uwriteln!(self.src, "//   * auto-derives");
+        }
+        if self.opts.serde {
+            uwriteln!(self.src, "//   * serde");
+        }
+        if self.opts.mocks {
+            uwriteln!(self.src, "//   * mocks");
+        }
+        if self.opts.borrowed_export_params {
+            uwriteln!(self.src, "//   * borrowed-export-params");
+        }
+        if self.opts.tracing {
+            uwriteln!(self.src, "//   * tracing");
+        }
+        for (k, v) in self.opts.tracing_levels.iter() {
+            uwriteln!(self.src, "//   * tracing level {k:?} = {v}");
+        }
+        if self.opts.dyn_exports {
+            uwriteln!(self.src, "//   * dyn-exports");
+        }
         self.types.analyze(resolve);
+        self.resolve_ownership(resolve, world);
+        self.check_tracing_levels(resolve, world);
         self.world = Some(world);
 
         let world = &resolve.worlds[world];
@@ -884,9 +1219,31 @@ impl WorldGenerator for RustWasm {
         }
 
         for (k, v) in self.opts.with.iter() {
+            if let Some(id) = find_type(resolve, k) {
+                let id = dealias(resolve, id);
+                let remappable = match resolve.types[id].kind {
+                    TypeDefKind::Record(_)
+                    | TypeDefKind::Tuple(_)
+                    | TypeDefKind::Flags(_)
+                    | TypeDefKind::Variant(_)
+                    | TypeDefKind::Enum(_)
+                    | TypeDefKind::Option(_)
+                    | TypeDefKind::Result(_) => !self.types.get(id).has_resource,
+                    _ => false,
+                };
+                match v {
+                    WithOption::Generate => self.generated_with_types.push(k.clone()),
+                    WithOption::Path(_) if !remappable => self.invalid_with_types.push(k.clone()),
+                    WithOption::Path(path) => {
+                        self.with_types.insert(id, path.clone());
+                    }
+                }
+                continue;
+            }
             self.with.insert(k.clone(), v.clone().into());
         }
         self.with.generate_by_default = self.opts.generate_all;
+        self.resolve_type_extras(resolve);
     }
 
     fn import_interface(
@@ -995,7 +1352,7 @@ impl WorldGenerator for RustWasm {
     ) {
         let mut gen = self.interface(Identifier::World(world), Some("$root"), resolve, true);
         for (name, ty) in types {
-            gen.define_type(name, *ty);
+            gen.define_stable_type(name, *ty);
         }
         let src = gen.finish();
         self.src.push_str(&src);
@@ -1108,10 +1465,281 @@ impl WorldGenerator for RustWasm {
             bail!("unused remappings provided via `with`: {unused_keys:?}");
         }
 
+        if !self.generated_with_types.is_empty() {
+            bail!(
+                "types are always generated and can only be remapped to a Rust type \
+                 via `with`, not `generate`: {:?}",
+                self.generated_with_types
+            );
+        }
+
+        if !self.invalid_with_types.is_empty() {
+            bail!(
+                "only records, tuples, flags, variants, enums, options and results \
+                 without resources can be remapped via `with`: {:?}",
+                self.invalid_with_types
+            );
+        }
+
+        if !self.unused_ownership_overrides.is_empty() {
+            bail!(
+                "`ownership_overrides` which don't name an imported interface or function: {:?}",
+                self.unused_ownership_overrides
+            );
+        }
+
+        if !self.invalid_type_extras.is_empty() {
+            bail!(
+                "invalid `derives` or `attributes`:\n{}",
+                self.invalid_type_extras.join("\n")
+            );
+        }
+
+        if !self.unused_tracing_levels.is_empty() {
+            bail!(
+                "`tracing_levels` which don't name an imported or exported interface: {:?}",
+                self.unused_tracing_levels
+            );
+        }
+
         Ok(())
     }
 }
 
+impl RustWasm {
+    /// Returns the ownership mode of the imported function `func`, which is
+    /// within `interface` unless it's imported by the world itself.
+    fn func_ownership(&self, interface: Option<&str>, func: &Function) -> Ownership {
+        let find = |key: &str| {
+            self.opts
+                .ownership_overrides
+                .iter()
+                .rev()
+                .find(|(k, _)| k == key)
+                .map(|(_, ownership)| *ownership)
+        };
+        find(&func.core_export_name(interface))
+            .or_else(|| interface.and_then(find))
+            .unwrap_or(self.opts.ownership)
+    }
+
+    /// Returns the level of `tracing` spans for functions within
+    /// `interface`, or imported or exported by the world itself if `None`.
+    fn tracing_level(&self, interface: Option<&str>) -> TracingLevel {
+        interface
+            .and_then(|interface| {
+                self.opts
+                    .tracing_levels
+                    .iter()
+                    .rev()
+                    .find(|(k, _)| k == interface)
+            })
+            .map(|(_, level)| *level)
+            .unwrap_or_default()
+    }
+
+    /// Records the keys of `tracing_levels` which don't name an interface
+    /// of `world`.
+    fn check_tracing_levels(&mut self, resolve: &Resolve, world: WorldId) {
+        let world = &resolve.worlds[world];
+        let keys = world
+            .imports
+            .iter()
+            .chain(world.exports.iter())
+            .filter(|(_, item)| matches!(item, WorldItem::Interface { .. }))
+            .map(|(key, _)| resolve.name_world_key(key))
+            .collect::<HashSet<_>>();
+        for (k, _) in self.opts.tracing_levels.iter() {
+            if !keys.contains(k) && !self.unused_tracing_levels.contains(k) {
+                self.unused_tracing_levels.push(k.clone());
+            }
+        }
+    }
+
+    /// Returns the ownership mode which the type `id` is generated with.
+    fn type_ownership(&self, id: TypeId) -> Ownership {
+        self.type_ownership
+            .get(&id)
+            .copied()
+            .unwrap_or(self.opts.ownership)
+    }
+
+    /// Determines the ownership of types used by imported functions from the
+    /// ownership of those functions.
+    ///
+    /// Types are only borrowing if all functions using them are, since
+    /// owning functions need their owning version.
+    fn resolve_ownership(&mut self, resolve: &Resolve, world: WorldId) {
+        if self.opts.ownership_overrides.is_empty() {
+            return;
+        }
+        let mut keys = HashSet::new();
+        let mut users = HashMap::<TypeId, Vec<Ownership>>::new();
+        for (key, item) in resolve.worlds[world].imports.iter() {
+            let (interface, funcs) = match item {
+                WorldItem::Function(func) => (None, vec![func]),
+                WorldItem::Interface { id, .. } => {
+                    let name = resolve.name_world_key(key);
+                    keys.insert(name.clone());
+                    (
+                        Some(name),
+                        resolve.interfaces[*id].functions.values().collect(),
+                    )
+                }
+                WorldItem::Type(_) => continue,
+            };
+            for func in funcs {
+                keys.insert(func.core_export_name(interface.as_deref()).into_owned());
+                let ownership = self.func_ownership(interface.as_deref(), func);
+                let mut live = LiveTypes::default();
+                for (_, ty) in func.params.iter() {
+                    live.add_type(resolve, ty);
+                }
+                for id in live.iter() {
+                    if resolve.types[id].name.is_some() {
+                        users.entry(id).or_default().push(ownership);
+                    }
+                }
+            }
+        }
+        for (id, ownerships) in users {
+            let mut ownership = Ownership::Borrowing {
+                duplicate_if_necessary: false,
+            };
+            for o in ownerships {
+                ownership = match (ownership, o) {
+                    (_, Ownership::Owning) | (Ownership::Owning, _) => Ownership::Owning,
+                    (
+                        Ownership::Borrowing {
+                            duplicate_if_necessary: a,
+                        },
+                        Ownership::Borrowing {
+                            duplicate_if_necessary: b,
+                        },
+                    ) => Ownership::Borrowing {
+                        duplicate_if_necessary: a || b,
+                    },
+                };
+            }
+            self.type_ownership.insert(id, ownership);
+        }
+        for (k, _) in self.opts.ownership_overrides.iter() {
+            if !keys.contains(k) && !self.unused_ownership_overrides.contains(k) {
+                self.unused_ownership_overrides.push(k.clone());
+            }
+        }
+    }
+
+    /// Resolves the keys of `derives` and `attributes` to the types they
+    /// apply to, checking them against what each type supports.
+    ///
+    /// Interface keys silently skip types which can't support a derive or
+    /// attribute while type keys report an error.
+    fn resolve_type_extras(&mut self, resolve: &Resolve) {
+        let derives = self.opts.derives.iter().map(|(k, v)| (k, v, true));
+        let attributes = self.opts.attributes.iter().map(|(k, v)| (k, v, false));
+        for (key, value, is_derive) in derives.chain(attributes) {
+            let option = if is_derive { "derives" } else { "attributes" };
+            let (ids, named) = match find_type(resolve, key) {
+                Some(id) => (vec![dealias(resolve, id)], true),
+                None => match resolve
+                    .interfaces
+                    .iter()
+                    .find(|(id, _)| resolve.id_of(*id).as_deref() == Some(key.as_str()))
+                {
+                    Some((_, iface)) => (iface.types.values().copied().collect(), false),
+                    None => {
+                        self.invalid_type_extras.push(format!(
+                            "`{key}` in `{option}` doesn't name a WIT interface or type"
+                        ));
+                        continue;
+                    }
+                },
+            };
+            let is_attribute = value.starts_with("#[") && value.ends_with(']');
+            if !is_derive && !is_attribute {
+                self.invalid_type_extras
+                    .push(format!("`{value}` for `{key}` isn't an attribute"));
+                continue;
+            }
+            for id in ids {
+                let info = self.types.get(id);
+                let error = match &resolve.types[id].kind {
+                    TypeDefKind::Record(_)
+                    | TypeDefKind::Variant(_)
+                    | TypeDefKind::Enum(_)
+                    | TypeDefKind::Flags(_) => {
+                        if is_derive {
+                            derive_error(value, &info)
+                        } else {
+                            repr_error(value, &resolve.types[id].kind, &info)
+                        }
+                    }
+                    _ => Some("it isn't a record, variant, enum or flags"),
+                };
+                match error {
+                    Some(reason) if named => {
+                        let what = if is_derive { "derive" } else { "add" };
+                        self.invalid_type_extras
+                            .push(format!("`{key}` can't {what} `{value}` since {reason}"));
+                    }
+                    Some(_) => {}
+                    None => {
+                        let extras = self.type_extras.entry(id).or_default();
+                        let list = if is_derive {
+                            &mut extras.derives
+                        } else {
+                            &mut extras.attributes
+                        };
+                        if !list.contains(value) {
+                            list.push(value.clone());
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Returns why a type described by `info` can't derive `derive`, if it can't.
+fn derive_error(derive: &str, info: &TypeInfo) -> Option<&'static str> {
+    let name = derive.rsplit("::").next().unwrap().trim();
+    match name {
+        "Copy" if !info.is_copy() => Some("it contains a list or resource"),
+        "Clone" | "PartialEq" | "PartialOrd" | "Serialize" | "Deserialize" if info.has_resource => {
+            Some("it contains a resource")
+        }
+        "Eq" | "Ord" | "Hash" if !info.is_eq() => Some("it contains a float or resource"),
+        _ => None,
+    }
+}
+
+/// Returns why `attribute` can't be added to a type, if it can't.
+///
+/// The generator picks the representation of enums, flags and records which
+/// are `Copy` itself, so those can't be given another `#[repr]`.
+fn repr_error(attribute: &str, kind: &TypeDefKind, info: &TypeInfo) -> Option<&'static str> {
+    let attribute: String = attribute.chars().filter(|c| !c.is_whitespace()).collect();
+    if !attribute.starts_with("#[repr(") {
+        return None;
+    }
+    match kind {
+        TypeDefKind::Enum(_) | TypeDefKind::Flags(_) => Some("its `#[repr]` is fixed"),
+        TypeDefKind::Record(_) if info.is_copy() => Some("its `#[repr]` is fixed"),
+        _ => None,
+    }
+}
+
+/// Finds the type named by a `with` key of the form `ns:pkg/iface/ty`.
+fn find_type(resolve: &Resolve, key: &str) -> Option<TypeId> {
+    let (interface, name) = key.rsplit_once('/')?;
+    resolve
+        .interfaces
+        .iter()
+        .find(|(id, _)| resolve.id_of(*id).as_deref() == Some(interface))
+        .and_then(|(_, iface)| iface.types.get(name).copied())
+}
+
 fn compute_module_path(name: &WorldKey, resolve: &Resolve, is_export: bool) -> Vec<String> {
     let mut path = Vec::new();
     if is_export {
@@ -1206,6 +1834,60 @@ impl fmt::Display for Ownership {
     }
 }
 
+/// The level of the `tracing` spans emitted for an interface.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TracingLevel {
+    Trace,
+    Debug,
+    #[default]
+    Info,
+    Warn,
+    Error,
+}
+
+impl TracingLevel {
+    /// Returns the name of the `tracing::Level` constant for this level.
+    fn constant(&self) -> &'static str {
+        match self {
+            TracingLevel::Trace => "TRACE",
+            TracingLevel::Debug => "DEBUG",
+            TracingLevel::Info => "INFO",
+            TracingLevel::Warn => "WARN",
+            TracingLevel::Error => "ERROR",
+        }
+    }
+}
+
+impl FromStr for TracingLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "trace" => Ok(Self::Trace),
+            "debug" => Ok(Self::Debug),
+            "info" => Ok(Self::Info),
+            "warn" => Ok(Self::Warn),
+            "error" => Ok(Self::Error),
+            _ => Err(format!(
+                "unrecognized tracing level: `{s}`; \
+                 expected `trace`, `debug`, `info`, `warn`, or `error`"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for TracingLevel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            TracingLevel::Trace => "trace",
+            TracingLevel::Debug => "debug",
+            TracingLevel::Info => "info",
+            TracingLevel::Warn => "warn",
+            TracingLevel::Error => "error",
+        })
+    }
+}
+
 /// Options for with "with" remappings.
 #[derive(Debug, Clone)]
 pub enum WithOption {
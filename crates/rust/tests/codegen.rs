@@ -51,6 +51,61 @@ mod codegen_tests {
                     #[test]
                     fn works() {}
                 }
+
+                mod serde {
+                    wit_bindgen::generate!({
+                        path: $test,
+                        ownership: Borrowing {
+                            duplicate_if_necessary: true
+                        },
+                        stubs,
+                        export_prefix: "[serde]",
+                        generate_all,
+                        serde: true,
+                    });
+
+                    #[test]
+                    fn works() {}
+                }
+
+                mod mocks {
+                    wit_bindgen::generate!({
+                        path: $test,
+                        stubs,
+                        export_prefix: "[mocks]",
+                        generate_all,
+                        mocks: true,
+                    });
+
+                    #[test]
+                    fn works() {}
+                }
+
+                mod borrowed_export_params {
+                    wit_bindgen::generate!({
+                        path: $test,
+                        stubs,
+                        export_prefix: "[borrowed_export_params]",
+                        generate_all,
+                        borrowed_export_params: true,
+                    });
+
+                    #[test]
+                    fn works() {}
+                }
+
+                mod tracing {
+                    wit_bindgen::generate!({
+                        path: $test,
+                        stubs,
+                        export_prefix: "[tracing]",
+                        generate_all,
+                        tracing: true,
+                    });
+
+                    #[test]
+                    fn works() {}
+                }
             }
 
         };
@@ -514,6 +569,194 @@ mod gated_features {
     }
 }
 
+// The `gated` feature doesn't exist, so items gated on it are compiled out.
+#[allow(unused, unexpected_cfgs)]
+mod stability {
+    wit_bindgen::generate!({
+        inline: r#"
+            package foo:bar;
+
+            interface i {
+                /// @deprecated use `y` instead
+                x: func();
+                y: func();
+            }
+
+            interface t {
+                get-point: func() -> point;
+                get-point2: func() -> point2;
+
+                /// @deprecated use `point2` instead
+                record point { x: u32 }
+                /// @deprecatedfoo isn't the tag.
+                record point2 { x: u32 }
+                @unstable(feature = gated)
+                record gated-point { x: u32 }
+                @unstable(feature = gated)
+                take-gated-point: func(p: gated-point);
+            }
+
+            world bindings {
+                import i;
+                import t;
+                @unstable(feature = gated)
+                import gated: func();
+                @unstable(feature = gated)
+                type gated-alias = u32;
+                export i;
+            }
+        "#,
+        features: ["gated"],
+        feature_gates: true,
+    });
+
+    struct Component;
+
+    export!(Component);
+
+    impl exports::foo::bar::i::Guest for Component {
+        fn x() {}
+        fn y() {}
+    }
+
+    #[allow(deprecated)]
+    fn _foo() {
+        foo::bar::i::x();
+        foo::bar::i::y();
+        let _ = foo::bar::t::Point { x: 0 };
+    }
+
+    #[deny(deprecated)]
+    fn _bar() -> foo::bar::t::Point2 {
+        foo::bar::t::get_point2()
+    }
+
+    // `gated` and `GatedAlias` are only available with the `gated` Cargo
+    // feature.
+    fn gated() {}
+    struct GatedAlias;
+}
+
+#[allow(unused)]
+mod auto_derives {
+    wit_bindgen::generate!({
+        inline: r#"
+            package foo:bar;
+
+            interface i {
+                resource r;
+                enum e { a, b }
+                flags f { a, b }
+                record exact { a: u32, b: string, c: list<tuple<s8, e, f>> }
+                record inexact { a: f32, b: option<exact> }
+                record handles { a: r }
+                variant v { a(exact), b(list<inexact>), c }
+                variant w { a(handles), b }
+
+                x: func(a: exact, b: inexact, c: handles, d: v, e: w);
+            }
+
+            world bindings {
+                import i;
+            }
+        "#,
+        auto_derives: true,
+    });
+
+    use foo::bar::i::*;
+
+    fn eq<T: Eq + Ord + std::hash::Hash>() {}
+    fn partial_eq<T: PartialEq + PartialOrd>() {}
+
+    fn _test() {
+        eq::<E>();
+        eq::<Exact>();
+        partial_eq::<Inexact>();
+        partial_eq::<V>();
+    }
+}
+
+mod serde {
+    wit_bindgen::generate!({
+        inline: r#"
+            package foo:bar;
+
+            interface i {
+                resource r;
+                enum color { dark-red, blue }
+                flags perms { read, write, exec }
+                record point { x: s32, y: f32 }
+                record item {
+                    %type: color,
+                    points: list<point>,
+                    perms: perms,
+                    pair: tuple<string, option<u8>>,
+                }
+                record handles { a: r }
+                variant shape { empty, line(tuple<point, point>), named(item) }
+
+                x: func(a: item, b: shape, c: handles);
+                y: func() -> shape;
+            }
+
+            world bindings {
+                import i;
+            }
+        "#,
+        ownership: Borrowing {
+            duplicate_if_necessary: true
+        },
+        serde: true,
+    });
+
+    use foo::bar::i::*;
+
+    fn item() -> ItemResult {
+        ItemResult {
+            type_: Color::DarkRed,
+            points: vec![Point { x: 1, y: 0.5 }],
+            perms: Perms::READ | Perms::EXEC,
+            pair: ("a".to_string(), Some(2)),
+        }
+    }
+
+    #[test]
+    fn json() {
+        let shape = ShapeResult::Named(item());
+        let json = serde_json::to_string(&shape).unwrap();
+        assert_eq!(
+            json,
+            r#"{"named":{"type":"dark-red","points":[{"x":1,"y":0.5}],"perms":["read","exec"],"pair":["a",2]}}"#
+        );
+        let ShapeResult::Named(parsed) = serde_json::from_str(&json).unwrap() else {
+            panic!()
+        };
+        assert_eq!(
+            serde_json::to_string(&parsed).unwrap(),
+            serde_json::to_string(&item()).unwrap()
+        );
+        assert_eq!(
+            serde_json::to_string(&ShapeResult::Empty).unwrap(),
+            r#""empty""#
+        );
+
+        // Borrowed variants of types can only be serialized.
+        let points = [Point { x: 1, y: 0.5 }];
+        let param = ItemParam {
+            type_: Color::DarkRed,
+            points: &points,
+            perms: Perms::READ | Perms::EXEC,
+            pair: ("a", Some(2)),
+        };
+        assert_eq!(
+            serde_json::to_string(&param).unwrap(),
+            serde_json::to_string(&item()).unwrap()
+        );
+
+        assert!(serde_json::from_str::<Perms>(r#"["read","other"]"#).is_err());
+    }
+}
+
 #[allow(unused)]
 mod simple_with_option {
     mod a {
@@ -572,3 +815,891 @@ mod multiple_paths {
         generate_all,
     });
 }
+
+#[allow(unused)]
+mod with_types {
+    wit_bindgen::generate!({
+        inline: r#"
+            package foo:bar;
+
+            interface types {
+                record datetime { seconds: u64, nanoseconds: u32 }
+                record person { name: string, age: u8 }
+                enum level { low, high }
+            }
+
+            interface i {
+                use types.{datetime, person, level};
+
+                record event { when: datetime, who: list<person>, level: level }
+
+                now: func() -> datetime;
+                log: func(e: event, at: option<datetime>) -> list<person>;
+                oldest: func(people: list<person>) -> result<person, level>;
+            }
+
+            world bindings {
+                import i;
+                export i;
+            }
+        "#,
+        with: {
+            "foo:bar/types/datetime": crate::with_types::Timestamp,
+            "foo:bar/types/person": crate::with_types::Human,
+            "foo:bar/i/level": crate::with_types::Severity,
+        },
+    });
+
+    use foo::bar::types::{Datetime, Level, Person};
+
+    #[derive(Debug, Clone, Copy)]
+    pub struct Timestamp(u128);
+
+    impl From<Timestamp> for Datetime {
+        fn from(t: Timestamp) -> Datetime {
+            Datetime {
+                seconds: (t.0 / 1_000_000_000) as u64,
+                nanoseconds: (t.0 % 1_000_000_000) as u32,
+            }
+        }
+    }
+
+    impl From<Datetime> for Timestamp {
+        fn from(d: Datetime) -> Timestamp {
+            Timestamp(u128::from(d.seconds) * 1_000_000_000 + u128::from(d.nanoseconds))
+        }
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct Human {
+        name: String,
+        age: u32,
+    }
+
+    impl From<Human> for Person {
+        fn from(h: Human) -> Person {
+            Person {
+                name: h.name,
+                age: h.age.min(255) as u8,
+            }
+        }
+    }
+
+    impl TryFrom<Person> for Human {
+        type Error = ();
+
+        fn try_from(p: Person) -> Result<Human, ()> {
+            if p.name.is_empty() {
+                return Err(());
+            }
+            Ok(Human {
+                name: p.name,
+                age: p.age.into(),
+            })
+        }
+    }
+
+    #[derive(Debug, Clone, Copy)]
+    pub enum Severity {
+        Info,
+        Error,
+    }
+
+    impl From<Severity> for Level {
+        fn from(s: Severity) -> Level {
+            match s {
+                Severity::Info => Level::Low,
+                Severity::Error => Level::High,
+            }
+        }
+    }
+
+    impl From<Level> for Severity {
+        fn from(l: Level) -> Severity {
+            match l {
+                Level::Low => Severity::Info,
+                Level::High => Severity::Error,
+            }
+        }
+    }
+
+    fn call_imports() {
+        use foo::bar::i::*;
+
+        let now: Timestamp = now();
+        let who = vec![Human {
+            name: "a".to_string(),
+            age: 1,
+        }];
+        let event = Event {
+            when: now,
+            who: who.clone(),
+            level: Severity::Info,
+        };
+        let _: Vec<Human> = log(&event, Some(now));
+        let _: Result<Human, Severity> = oldest(&who);
+    }
+
+    struct Component;
+
+    impl exports::foo::bar::i::Guest for Component {
+        fn now() -> Timestamp {
+            Timestamp(0)
+        }
+
+        fn log(e: exports::foo::bar::i::Event, at: Option<Timestamp>) -> Vec<Human> {
+            e.who
+        }
+
+        fn oldest(people: Vec<Human>) -> Result<Human, Severity> {
+            people
+                .into_iter()
+                .max_by_key(|h| h.age)
+                .ok_or(Severity::Error)
+        }
+    }
+
+    export!(Component);
+
+    #[test]
+    fn generate_is_rejected() {
+        use wit_bindgen_core::wit_parser::Resolve;
+        use wit_bindgen_core::{Files, WorldGenerator as _};
+        use wit_bindgen_rust::{Opts, WithOption};
+
+        let mut resolve = Resolve::default();
+        let pkgs = resolve
+            .push_str(
+                "test.wit",
+                "package foo:bar;
+                 interface types { record r { x: u32 } }
+                 world w { import types; }",
+            )
+            .unwrap();
+        let world = resolve.select_world(&pkgs, None).unwrap();
+        let opts = Opts {
+            with: vec![("foo:bar/types/r".to_string(), WithOption::Generate)],
+            ..Opts::default()
+        };
+        let err = opts
+            .build()
+            .generate(&resolve, world, &mut Files::default())
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "types are always generated and can only be remapped to a Rust type \
+             via `with`, not `generate`: [\"foo:bar/types/r\"]"
+        );
+    }
+}
+
+mod async_exports {
+    wit_bindgen::generate!({
+        inline: r#"
+            package wasi:io@0.2.0 {
+                interface poll {
+                    resource pollable {
+                        ready: func() -> bool;
+                        block: func();
+                    }
+
+                    poll: func(in: list<borrow<pollable>>) -> list<u32>;
+                }
+            }
+
+            package my:async-exports {
+                interface foo {
+                    use wasi:io/poll@0.2.0.{pollable};
+
+                    resource bar {
+                        constructor(data: u32);
+                        get-data: func() -> u32;
+                    }
+
+                    wait: func(p: borrow<pollable>);
+                }
+
+                world baz {
+                    import wasi:io/poll@0.2.0;
+                    export foo;
+                    export run: func() -> string;
+                }
+            }
+        "#,
+        world: "my:async-exports/baz",
+        generate_all,
+        async: true,
+    });
+
+    use exports::my::async_exports::foo;
+    use wasi::io::poll::Pollable;
+
+    struct Component;
+
+    export!(Component);
+
+    impl Guest for Component {
+        async fn run() -> String {
+            String::from("done")
+        }
+    }
+
+    impl foo::Guest for Component {
+        type Bar = MyBar;
+
+        async fn wait(p: &Pollable) {
+            p.wait().await;
+        }
+    }
+
+    struct MyBar(u32);
+
+    impl foo::GuestBar for MyBar {
+        async fn new(data: u32) -> Self {
+            MyBar(data)
+        }
+
+        async fn get_data(&self) -> u32 {
+            self.0
+        }
+    }
+
+    #[test]
+    fn block_on_ready() {
+        let result = wit_bindgen::rt::async_support::block_on(<Component as Guest>::run());
+        assert_eq!(result, "done");
+    }
+}
+
+mod async_pollables {
+    wit_bindgen::generate!({
+        inline: r#"
+            package wasi:io@0.2.1 {
+                interface poll {
+                    resource pollable;
+
+                    poll: func(in: list<borrow<pollable>>) -> list<u32>;
+                }
+            }
+
+            package my:async-pollables {
+                interface timer {
+                    use wasi:io/poll@0.2.1.{pollable};
+
+                    subscribe: func(id: u32) -> pollable;
+                }
+
+                world w {
+                    import timer;
+                    export step: func(n: u32) -> u32;
+                }
+            }
+        "#,
+        world: "my:async-pollables/w",
+        generate_all,
+        async: true,
+        mocks: true,
+    });
+
+    use my::async_pollables::timer;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use wasi::io::poll::{self, Pollable};
+
+    struct Component;
+
+    export!(Component);
+
+    impl Guest for Component {
+        async fn step(n: u32) -> u32 {
+            timer::subscribe(1).wait().await;
+            timer::subscribe(2).wait().await;
+            n + 1
+        }
+    }
+
+    struct MockTimer;
+
+    impl timer::mock::Mock for MockTimer {
+        fn subscribe(&self, id: u32) -> Pollable {
+            unsafe { Pollable::from_handle(id) }
+        }
+    }
+
+    struct MockPoll {
+        polled: Rc<RefCell<Vec<Vec<u32>>>>,
+    }
+
+    impl poll::mock::Mock for MockPoll {
+        fn poll(&self, in_: &[&Pollable]) -> Vec<u32> {
+            let handles = in_.iter().map(|p| p.handle()).collect();
+            self.polled.borrow_mut().push(handles);
+            vec![0]
+        }
+    }
+
+    #[test]
+    fn export_awaits_pollables() {
+        let polled = Rc::default();
+        timer::mock::set(MockTimer);
+        poll::mock::set(MockPoll {
+            polled: Rc::clone(&polled),
+        });
+        let result = unsafe { _export_step_cabi::<Component>(41) };
+        assert_eq!(result, 42);
+        assert_eq!(*polled.borrow(), [vec![1], vec![2]]);
+        timer::mock::clear();
+        poll::mock::clear();
+    }
+}
+
+#[allow(unused)]
+mod futures_in_aggregates {
+    wit_bindgen::generate!({
+        inline: r#"
+            package foo:bar;
+
+            interface i {
+                record r {
+                    f: future<u32>,
+                    s: stream<u8>,
+                }
+
+                take: func(a: list<future<u32>>, b: r, c: option<stream<u8>>);
+                give: func() -> list<r>;
+            }
+
+            world bindings {
+                import i;
+                export i;
+            }
+        "#,
+    });
+
+    use wit_bindgen::rt::async_support::{FutureReader, StreamReader};
+
+    struct Component;
+
+    export!(Component);
+
+    impl exports::foo::bar::i::Guest for Component {
+        fn take(
+            a: Vec<FutureReader<u32>>,
+            b: exports::foo::bar::i::R,
+            c: Option<StreamReader<u8>>,
+        ) {
+        }
+
+        fn give() -> Vec<exports::foo::bar::i::R> {
+            Vec::new()
+        }
+    }
+
+    // Futures and streams are moved into imports as they're consumed.
+    fn _take(a: Vec<FutureReader<u32>>, b: foo::bar::i::R, c: Option<StreamReader<u8>>) {
+        foo::bar::i::take(a, b, c);
+    }
+
+    fn _give() -> Vec<foo::bar::i::R> {
+        foo::bar::i::give()
+    }
+}
+
+mod mocks {
+    wit_bindgen::generate!({
+        inline: r#"
+            package foo:bar;
+
+            interface i {
+                resource counter {
+                    constructor(start: u32);
+                    add: func(n: u32) -> u32;
+                }
+                record point { x: s32, y: s32 }
+
+                move: func(p: point, by: list<point>) -> point;
+                name: func() -> string;
+            }
+
+            world bindings {
+                import i;
+                import log: func(msg: string);
+            }
+        "#,
+        mocks: true,
+    });
+
+    use foo::bar::i::{self, *};
+    use std::cell::{Cell, RefCell};
+
+    #[derive(Default)]
+    struct MockI {
+        counter: Cell<u32>,
+    }
+
+    impl i::mock::Mock for MockI {
+        fn move_(&self, p: Point, by: &[Point]) -> Point {
+            by.iter().fold(p, |p, by| Point {
+                x: p.x + by.x,
+                y: p.y + by.y,
+            })
+        }
+
+        fn counter_new(&self, start: u32) -> Counter {
+            self.counter.set(start);
+            unsafe { Counter::from_handle(1) }
+        }
+
+        fn counter_add(&self, counter: &Counter, n: u32) -> u32 {
+            assert_eq!(counter.handle(), 1);
+            self.counter.set(self.counter.get() + n);
+            self.counter.get()
+        }
+    }
+
+    #[derive(Default)]
+    struct MockRoot {
+        logged: RefCell<Vec<String>>,
+    }
+
+    impl mock::Mock for MockRoot {
+        fn log(&self, msg: &str) {
+            self.logged.borrow_mut().push(msg.to_string());
+        }
+    }
+
+    #[test]
+    fn imports_call_mocks() {
+        assert!(i::mock::set(MockI::default()).is_none());
+        let p = move_(Point { x: 1, y: 2 }, &[Point { x: 3, y: 4 }]);
+        assert_eq!((p.x, p.y), (4, 6));
+
+        let counter = Counter::new(10);
+        assert_eq!(counter.add(5), 15);
+        drop(counter);
+
+        mock::set(MockRoot::default());
+        log("hello");
+        let root = mock::clear().unwrap();
+        assert!(mock::clear().is_none());
+        drop(root);
+        i::mock::clear();
+    }
+
+    #[test]
+    #[should_panic(expected = "`foo:bar/i#name` is not mocked")]
+    fn unmocked_function_panics() {
+        i::mock::set(MockI::default());
+        name();
+    }
+
+    #[test]
+    #[should_panic(expected = "no mock of `foo:bar/i` is installed on this thread")]
+    fn missing_mock_panics() {
+        name();
+    }
+}
+
+#[allow(unused)]
+mod type_extras {
+    wit_bindgen::generate!({
+        inline: r#"
+            package foo:bar;
+
+            interface i {
+                resource r;
+
+                record point { x: f64, y: f64 }
+                record name { first: string, last: string }
+                record holder { r: own<r> }
+                enum color { red, green }
+                flags perms { read, write }
+                variant shape { circle(f64), square(u32) }
+
+                f: func(p: point, n: name, h: holder, c: color, m: perms, s: shape);
+            }
+
+            world bindings {
+                import i;
+            }
+        "#,
+        derives: {
+            "foo:bar/i": [PartialEq, Eq, Hash],
+            "foo:bar/i/perms": [Default],
+        },
+        attributes: {
+            "foo:bar/i/color": [#[non_exhaustive]],
+            "foo:bar/i/shape": [#[repr(u8)], #[cfg_attr(all(), must_use)]],
+        },
+    });
+
+    use foo::bar::i::*;
+
+    fn eq<T: Eq + core::hash::Hash>() {}
+    fn partial_eq<T: PartialEq>() {}
+
+    #[test]
+    fn derives() {
+        eq::<Name>();
+        eq::<Color>();
+        partial_eq::<Point>();
+        partial_eq::<Shape>();
+        assert_eq!(Perms::default(), Perms::empty());
+        assert_ne!(Shape::Circle(1.0), Shape::Square(1));
+    }
+}
+
+#[allow(unused)]
+mod ownership_overrides {
+    wit_bindgen::generate!({
+        inline: r#"
+            package foo:bar;
+
+            interface hot {
+                record request { path: string, headers: list<string> }
+                record shared { name: string }
+                record chunk { data: list<u8> }
+
+                send: func(r: request, s: shared);
+                write: func(c: chunk);
+                send-owned: func(r: request);
+            }
+
+            interface cold {
+                use hot.{shared};
+
+                record config { name: string }
+
+                set: func(c: config, s: list<shared>);
+            }
+
+            world bindings {
+                import hot;
+                import cold;
+                import f: func(c: list<string>);
+            }
+        "#,
+        ownership_overrides: {
+            "foo:bar/hot": Borrowing { duplicate_if_necessary: false },
+            "foo:bar/hot#send-owned": Owning,
+            "f": Borrowing { duplicate_if_necessary: false },
+        },
+    });
+
+    use foo::bar::{cold, hot};
+
+    // Only used to check the signatures of the generated functions.
+    fn signatures() {
+        let name = String::from("x");
+        let headers = ["a", "b"];
+        // `request` is used by an owning function so it only has an owning
+        // version, which the borrowing function takes by reference.
+        let request = hot::Request {
+            path: name.clone(),
+            headers: vec![name.clone()],
+        };
+        let shared = hot::Shared { name: name.clone() };
+        hot::send(&request, &shared);
+        hot::send_owned(&request);
+        // `chunk` is only used by borrowing functions so it borrows its
+        // contents.
+        hot::write(hot::Chunk {
+            data: name.as_bytes(),
+        });
+        cold::set(&cold::Config { name: name.clone() }, &[shared]);
+        f(&headers);
+    }
+}
+
+#[allow(unused)]
+mod borrowed_export_params {
+    wit_bindgen::generate!({
+        inline: r#"
+            package foo:bar;
+
+            interface i {
+                type bytes = list<u8>;
+                type name = string;
+                record point { x: f32, y: f32 }
+
+                sum: func(a: list<u32>, b: bytes) -> u64;
+                greet: func(who: string, alias: name) -> string;
+                plot: func(points: list<point>, labels: list<string>);
+            }
+
+            world bindings {
+                export i;
+            }
+        "#,
+        borrowed_export_params: true,
+    });
+
+    use exports::foo::bar::i::{Guest, Point};
+
+    struct Component;
+
+    export!(Component);
+
+    impl Guest for Component {
+        fn sum(a: &[u32], b: &[u8]) -> u64 {
+            a.iter().map(|a| u64::from(*a)).sum::<u64>() + b.len() as u64
+        }
+
+        fn greet(who: &str, alias: &str) -> String {
+            format!("hello {who} ({alias})")
+        }
+
+        // Lists of strings aren't plain-old-data, so they're still owned.
+        fn plot(points: &[Point], labels: Vec<String>) {}
+    }
+}
+
+#[allow(unused)]
+mod tracing {
+    wit_bindgen::generate!({
+        inline: r#"
+            package foo:bar;
+
+            interface i {
+                type count = u32;
+                resource r {
+                    constructor(seed: u64);
+                    get: func(offset: count) -> u64;
+                }
+
+                f: func(a: bool, b: char, c: f32, d: count, e: string, g: list<u8>);
+            }
+
+            interface e {
+                g: func(a: s8, b: string) -> u32;
+            }
+
+            world bindings {
+                import i;
+                import h: func(x: u16);
+                export e;
+                export k: func(y: f64);
+            }
+        "#,
+        tracing: true,
+        tracing_levels: {
+            "foo:bar/i": debug,
+            "foo:bar/e": trace,
+        },
+        mocks: true,
+    });
+
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Level, Metadata};
+
+    struct Component;
+
+    export!(Component);
+
+    impl Guest for Component {
+        fn k(y: f64) {}
+    }
+
+    impl exports::foo::bar::e::Guest for Component {
+        fn g(a: i8, b: String) -> u32 {
+            foo::bar::i::f(true, 'x', 1.0, 2, &b, &[]);
+            h(3);
+            foo::bar::i::R::new(4).get(5) as u32
+        }
+    }
+
+    struct MockI;
+
+    impl foo::bar::i::mock::Mock for MockI {
+        fn f(&self, a: bool, b: char, c: f32, d: u32, e: &str, g: &[u8]) {}
+
+        fn r_new(&self, seed: u64) -> foo::bar::i::R {
+            unsafe { foo::bar::i::R::from_handle(1) }
+        }
+
+        fn r_get(&self, r: &foo::bar::i::R, offset: u32) -> u64 {
+            6
+        }
+    }
+
+    struct MockRoot;
+
+    impl mock::Mock for MockRoot {
+        fn h(&self, x: u16) {}
+    }
+
+    /// A span entered by the bindings: its name, level and fields.
+    type Span = (&'static str, Level, Vec<(&'static str, String)>);
+
+    /// A subscriber recording every span created.
+    #[derive(Clone, Default)]
+    struct Spans(Arc<Mutex<Vec<Span>>>);
+
+    impl tracing::Subscriber for Spans {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &Attributes<'_>) -> Id {
+            struct Fields(Vec<(&'static str, String)>);
+
+            impl Visit for Fields {
+                fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+                    self.0.push((field.name(), format!("{value:?}")));
+                }
+            }
+
+            let mut fields = Fields(Vec::new());
+            span.record(&mut fields);
+            let metadata = span.metadata();
+            let mut spans = self.0.lock().unwrap();
+            spans.push((metadata.name(), *metadata.level(), fields.0));
+            Id::from_u64(spans.len() as u64)
+        }
+
+        fn record(&self, _: &Id, _: &Record<'_>) {}
+
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+        fn event(&self, _: &Event<'_>) {}
+
+        fn enter(&self, _: &Id) {}
+
+        fn exit(&self, _: &Id) {}
+    }
+
+    #[test]
+    fn spans() {
+        foo::bar::i::mock::set(MockI);
+        mock::set(MockRoot);
+        let spans = Spans::default();
+        tracing::subscriber::with_default(spans.clone(), || unsafe {
+            let b = "hi".to_string().into_bytes().into_boxed_slice();
+            let len = b.len();
+            let ptr = Box::into_raw(b).cast::<u8>();
+            let ret = exports::foo::bar::e::_export_g_cabi::<Component>(-1, ptr, len);
+            assert_eq!(ret, 6);
+            _export_k_cabi::<Component>(1.5);
+        });
+        foo::bar::i::mock::clear();
+        mock::clear();
+
+        let kind = |kind: &str| ("wit.kind", format!("{kind:?}"));
+        let interface = |name: &str| ("wit.interface", format!("{name:?}"));
+        let spans = spans.0.lock().unwrap();
+        assert_eq!(
+            *spans,
+            [
+                (
+                    "g",
+                    Level::TRACE,
+                    vec![kind("export"), interface("foo:bar/e"), ("a", "-1".into())],
+                ),
+                (
+                    "f",
+                    Level::DEBUG,
+                    vec![
+                        kind("import"),
+                        interface("foo:bar/i"),
+                        ("a", "true".into()),
+                        ("b", "x".into()),
+                        ("c", "1.0".into()),
+                        ("d", "2".into()),
+                    ],
+                ),
+                ("h", Level::INFO, vec![kind("import"), ("x", "3".into())]),
+                (
+                    "[constructor]r",
+                    Level::DEBUG,
+                    vec![kind("import"), interface("foo:bar/i"), ("seed", "4".into()),],
+                ),
+                (
+                    "[method]r.get",
+                    Level::DEBUG,
+                    vec![
+                        kind("import"),
+                        interface("foo:bar/i"),
+                        ("offset", "5".into()),
+                    ],
+                ),
+                ("k", Level::INFO, vec![kind("export"), ("y", "1.5".into())]),
+            ]
+        );
+    }
+}
+
+#[allow(unused)]
+mod dyn_exports {
+    wit_bindgen::generate!({
+        inline: r#"
+            package foo:bar;
+
+            interface i {
+                record point { x: f32, y: f32 }
+
+                scale: func(p: point, by: f32) -> point;
+                names: func() -> list<string>;
+            }
+
+            world bindings {
+                export i;
+                export offset-by: func(input: u32) -> u32;
+            }
+        "#,
+        dyn_exports: true,
+    });
+
+    use exports::foo::bar::i::{self, Point};
+
+    struct Scaler {
+        names: Vec<String>,
+    }
+
+    impl i::Guest for Scaler {
+        fn scale(&self, p: Point, by: f32) -> Point {
+            Point {
+                x: p.x * by,
+                y: p.y * by,
+            }
+        }
+
+        fn names(&self) -> Vec<String> {
+            self.names.clone()
+        }
+    }
+
+    struct Offset(u32);
+
+    impl Guest for Offset {
+        fn offset_by(&self, input: u32) -> u32 {
+            input + self.0
+        }
+    }
+
+    export!();
+
+    fn init() {
+        i::set_export_impl(Box::new(Scaler {
+            names: vec!["a".to_string()],
+        }));
+        set_export_impl(Box::new(Offset(1)));
+    }
+
+    #[test]
+    fn dispatches_to_registered_impl() {
+        set_export_impl(Box::new(Offset(1)));
+        assert_eq!(unsafe { _export_offset_by_cabi(2) }, 3);
+        set_export_impl(Box::new(Offset(10)));
+        assert_eq!(unsafe { _export_offset_by_cabi(2) }, 12);
+
+        // Natively each thread registers its own implementation.
+        let other = std::thread::spawn(|| {
+            set_export_impl(Box::new(Offset(100)));
+            unsafe { _export_offset_by_cabi(2) }
+        });
+        assert_eq!(other.join().unwrap(), 102);
+        assert_eq!(unsafe { _export_offset_by_cabi(2) }, 12);
+    }
+}
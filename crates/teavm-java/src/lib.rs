@@ -8,7 +8,7 @@ use std::{
 };
 use wit_bindgen_core::{
     abi::{self, AbiVariant, Bindgen, Bitcast, Instruction, LiftLower, WasmType},
-    uwrite, uwriteln,
+    deprecation, uwrite, uwriteln,
     wit_parser::{
         Docs, Enum, Flags, FlagsRepr, Function, FunctionKind, Int, InterfaceId, Record, Resolve,
         Result_, SizeAlign, Tuple, Type, TypeDef, TypeDefKind, TypeId, TypeOwner, Variant, WorldId,
@@ -540,13 +540,14 @@ impl InterfaceGenerator<'_> {
             .join(", ");
 
         let sig = self.sig_string(func, false);
+        let deprecated = deprecated_annotation(&func.docs);
 
         uwrite!(
             self.src,
             r#"@Import(name = "{name}", module = "{module}")
                private static native {result_type} wasmImport{camel_name}({params});
 
-               {sig} {{
+               {deprecated}{sig} {{
                    {cleanup_list} {src}
                }}
             "#
@@ -640,11 +641,12 @@ impl InterfaceGenerator<'_> {
 
         if self.gen.opts.generate_stub {
             let sig = self.sig_string(func, true);
+            let deprecated = deprecated_annotation(&func.docs);
 
             uwrite!(
                 self.stub,
                 r#"
-                {sig} {{
+                {deprecated}{sig} {{
                     throw new RuntimeException("todo");
                 }}
                 "#
@@ -714,6 +716,8 @@ impl InterfaceGenerator<'_> {
 
                         format!("{}Result<{ok}, {err}>", self.gen.qualifier())
                     }
+                    // Futures and streams are exposed as their raw handles.
+                    TypeDefKind::Future(_) | TypeDefKind::Stream(_) => "int".into(),
                     _ => {
                         if let Some(name) = &ty.name {
                             format!(
@@ -743,6 +747,7 @@ impl InterfaceGenerator<'_> {
                 let def = &self.resolve.types[*id];
                 match &def.kind {
                     TypeDefKind::Type(ty) => self.type_name_boxed(ty, qualifier),
+                    TypeDefKind::Future(_) | TypeDefKind::Stream(_) => "Integer".into(),
                     _ => self.type_name_with_qualifier(ty, qualifier),
                 }
             }
@@ -831,6 +836,7 @@ impl<'a> wit_bindgen_core::InterfaceGenerator<'a> for InterfaceGenerator<'a> {
 
     fn type_record(&mut self, _id: TypeId, name: &str, record: &Record, docs: &Docs) {
         self.print_docs(docs);
+        self.src.push_str(deprecated_annotation(docs));
 
         let name = name.to_upper_camel_case();
 
@@ -895,6 +901,7 @@ impl<'a> wit_bindgen_core::InterfaceGenerator<'a> for InterfaceGenerator<'a> {
 
     fn type_flags(&mut self, _id: TypeId, name: &str, flags: &Flags, docs: &Docs) {
         self.print_docs(docs);
+        self.src.push_str(deprecated_annotation(docs));
 
         let name = name.to_upper_camel_case();
 
@@ -946,6 +953,7 @@ impl<'a> wit_bindgen_core::InterfaceGenerator<'a> for InterfaceGenerator<'a> {
 
     fn type_variant(&mut self, _id: TypeId, name: &str, variant: &Variant, docs: &Docs) {
         self.print_docs(docs);
+        self.src.push_str(deprecated_annotation(docs));
 
         let name = name.to_upper_camel_case();
         let tag_type = int_type(variant.tag());
@@ -1040,6 +1048,7 @@ impl<'a> wit_bindgen_core::InterfaceGenerator<'a> for InterfaceGenerator<'a> {
 
     fn type_enum(&mut self, _id: TypeId, name: &str, enum_: &Enum, docs: &Docs) {
         self.print_docs(docs);
+        self.src.push_str(deprecated_annotation(docs));
 
         let name = name.to_upper_camel_case();
 
@@ -1376,6 +1385,11 @@ impl Bindgen for FunctionBindgen<'_, '_> {
 
             Instruction::HandleLower { .. } | Instruction::HandleLift { .. } => todo!(),
 
+            Instruction::FutureLower { .. }
+            | Instruction::FutureLift { .. }
+            | Instruction::StreamLower { .. }
+            | Instruction::StreamLift { .. } => results.push(operands[0].clone()),
+
             Instruction::RecordLower { record, .. } => {
                 let op = &operands[0];
                 for field in record.fields.iter() {
@@ -2090,6 +2104,17 @@ impl Bindgen for FunctionBindgen<'_, '_> {
     }
 }
 
+/// Returns a `@Deprecated` annotation if `docs` mark an item as deprecated.
+///
+/// Any deprecation note is already part of the Javadoc as a `@deprecated` tag.
+fn deprecated_annotation(docs: &Docs) -> &'static str {
+    if deprecation(docs).is_some() {
+        "@Deprecated\n"
+    } else {
+        ""
+    }
+}
+
 fn perform_cast(op: &str, cast: &Bitcast) -> String {
     match cast {
         Bitcast::I32ToF32 => {
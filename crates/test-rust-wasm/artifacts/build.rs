@@ -15,15 +15,25 @@ fn main() {
         Err(_) => "wasm32-wasip1".to_string(),
     };
 
-    let mut cmd = Command::new("cargo");
-    cmd.arg("build")
-        .current_dir("../../test-rust-wasm")
-        .arg("--target")
-        .arg(&target_to_test)
-        .env("CARGO_TARGET_DIR", &out_dir)
-        .env("CARGO_PROFILE_DEV_DEBUG", "1");
-    let status = cmd.status().unwrap();
-    assert!(status.success());
+    // Binaries testing an allocation strategy are built on their own so that
+    // the feature enabling it doesn't affect any other binary.
+    let builds: &[&[&str]] = &[
+        &[],
+        &["--bin", "allocator-bump-arena", "--features", "bump-arena"],
+        &["--bin", "allocator-pool", "--features", "pool"],
+    ];
+    for args in builds {
+        let mut cmd = Command::new("cargo");
+        cmd.arg("build")
+            .current_dir("../../test-rust-wasm")
+            .arg("--target")
+            .arg(&target_to_test)
+            .args(*args)
+            .env("CARGO_TARGET_DIR", &out_dir)
+            .env("CARGO_PROFILE_DEV_DEBUG", "1");
+        let status = cmd.status().unwrap();
+        assert!(status.success());
+    }
 
     let mut wasms = Vec::new();
     for file in out_dir
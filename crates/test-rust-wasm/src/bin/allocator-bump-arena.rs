@@ -0,0 +1,3 @@
+include!("../../../../tests/runtime/allocator/allocator-bump-arena.rs");
+
+fn main() {}
@@ -0,0 +1,3 @@
+include!("../../../../tests/runtime/allocator/allocator-pool.rs");
+
+fn main() {}
@@ -0,0 +1,3 @@
+include!("../../../../tests/runtime/allocator/allocator-realloc-hook.rs");
+
+fn main() {}
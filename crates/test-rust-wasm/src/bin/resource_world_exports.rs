@@ -0,0 +1,3 @@
+include!("../../../../tests/runtime/resource_world_exports/wasm.rs");
+
+fn main() {}
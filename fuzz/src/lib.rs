@@ -0,0 +1,313 @@
+//! Fuzzing support for `wit-bindgen` generators.
+//!
+//! The fuzz target here uses `wit-smith` to synthesize arbitrary WIT packages
+//! and then runs every language generator over each world in them. Generators
+//! are allowed to return an error for WIT they don't support, but they must
+//! never panic. When one does the world is minimized to a smaller WIT document
+//! which still triggers the same panic, and that document is reported as a
+//! reproducer.
+
+use arbitrary::{Result, Unstructured};
+use std::panic::{self, AssertUnwindSafe};
+use wit_bindgen_core::wit_parser::{PackageId, Resolve, WorldId};
+use wit_bindgen_core::{Files, WorldGenerator};
+use wit_component::{DecodedWasm, WitPrinter};
+
+/// The generators which are fuzzed, along with their names.
+pub const GENERATORS: &[(&str, fn() -> Box<dyn WorldGenerator>)] = &[
+    ("rust", || {
+        wit_bindgen_rust::Opts {
+            generate_all: true,
+            ..Default::default()
+        }
+        .build()
+    }),
+    ("c", || wit_bindgen_c::Opts::default().build()),
+    ("cpp", || wit_bindgen_cpp::Opts::default().build()),
+    ("csharp", || wit_bindgen_csharp::Opts::default().build()),
+    ("go", || {
+        wit_bindgen_go::Opts {
+            gofmt: false,
+            ..Default::default()
+        }
+        .build()
+    }),
+    ("teavm-java", || {
+        wit_bindgen_teavm_java::Opts::default().build()
+    }),
+    ("markdown", || wit_bindgen_markdown::Opts::default().build()),
+];
+
+/// A generator which panicked on a world.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Panic {
+    pub generator: &'static str,
+    pub message: String,
+}
+
+/// Runs all generators over all worlds of a WIT package generated from `u`,
+/// panicking with a minimized reproducer if any generator panics.
+pub fn run(u: &mut Unstructured<'_>) -> Result<()> {
+    let mut config: wit_smith::Config = u.arbitrary()?;
+    // Keep the number of packages small since each world is run through
+    // every generator.
+    config.max_packages = config.max_packages.min(4);
+    let wasm = wit_smith::smith(&config, u)?;
+    let (resolve, pkgs) = match wit_component::decode(&wasm).unwrap() {
+        DecodedWasm::WitPackages(resolve, pkgs) => (resolve, pkgs),
+        DecodedWasm::Component(..) => unreachable!(),
+    };
+
+    for pkg in pkgs {
+        for (_, world) in resolve.packages[pkg].worlds.iter() {
+            for (generator, _) in GENERATORS {
+                let Some(panic) = check(generator, &resolve, *world) else {
+                    continue;
+                };
+                let wit = minimize(&resolve, *world, |resolve, world| {
+                    check(generator, resolve, world).as_ref() == Some(&panic)
+                });
+                panic!(
+                    "generator `{}` panicked: {}\n\nminimized reproducer for world `{}`:\n\n{wit}",
+                    panic.generator, panic.message, resolve.worlds[*world].name,
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Runs `generator` over `world`, returning the panic it raised, if any.
+pub fn check(generator: &str, resolve: &Resolve, world: WorldId) -> Option<Panic> {
+    let (name, build) = GENERATORS.iter().find(|(name, _)| *name == generator)?;
+    catch(|| {
+        let mut files = Files::default();
+        // Errors are how generators report unsupported WIT, so only panics
+        // are interesting here.
+        let _ = build().generate(resolve, world, &mut files);
+    })
+    .map(|message| Panic {
+        generator: name,
+        message,
+    })
+}
+
+/// Minimizes the WIT which `world` is defined in while `reproduces` holds for
+/// it, returning the minimized WIT source.
+///
+/// Items are removed one at a time from a copy of `resolve`, and a removal is
+/// kept if the remaining packages still parse and `reproduces` still holds.
+/// This is repeated until no more items can be removed.
+pub fn minimize(
+    resolve: &Resolve,
+    world: WorldId,
+    reproduces: impl Fn(&Resolve, WorldId) -> bool,
+) -> String {
+    let mut resolve = resolve.clone();
+    let mut world = world;
+    let mut wit = print(&resolve).expect("failed to print original WIT");
+    loop {
+        let mut progress = false;
+        for candidate in candidates(&resolve, world) {
+            let Some(wit_candidate) = print(&candidate) else {
+                continue;
+            };
+            let Some((parsed, parsed_world)) = reparse(&wit_candidate, &resolve, world) else {
+                continue;
+            };
+            if reproduces(&parsed, parsed_world) {
+                resolve = parsed;
+                world = parsed_world;
+                wit = wit_candidate;
+                progress = true;
+                break;
+            }
+        }
+        if !progress {
+            return wit;
+        }
+    }
+}
+
+/// Returns copies of `resolve` with one item removed which might be irrelevant
+/// to a panic in `world`.
+fn candidates(resolve: &Resolve, world: WorldId) -> Vec<Resolve> {
+    let mut ret = Vec::new();
+
+    // Other worlds.
+    for (id, other) in resolve.worlds.iter() {
+        if id == world {
+            continue;
+        }
+        let mut candidate = resolve.clone();
+        let pkg = other.package.unwrap();
+        candidate.packages[pkg].worlds.shift_remove(&other.name);
+        ret.push(candidate);
+    }
+
+    // Imports and exports of the world.
+    let items = &resolve.worlds[world];
+    for key in items.imports.keys() {
+        let mut candidate = resolve.clone();
+        candidate.worlds[world].imports.shift_remove(key);
+        ret.push(candidate);
+    }
+    for key in items.exports.keys() {
+        let mut candidate = resolve.clone();
+        candidate.worlds[world].exports.shift_remove(key);
+        ret.push(candidate);
+    }
+
+    // Interfaces, and their functions and types.
+    for (id, iface) in resolve.interfaces.iter() {
+        if let (Some(pkg), Some(name)) = (iface.package, &iface.name) {
+            let mut candidate = resolve.clone();
+            candidate.packages[pkg].interfaces.shift_remove(name);
+            ret.push(candidate);
+        }
+        for name in iface.functions.keys() {
+            let mut candidate = resolve.clone();
+            candidate.interfaces[id].functions.shift_remove(name);
+            ret.push(candidate);
+        }
+        for name in iface.types.keys() {
+            let mut candidate = resolve.clone();
+            candidate.interfaces[id].types.shift_remove(name);
+            ret.push(candidate);
+        }
+    }
+    ret
+}
+
+/// Prints all packages in `resolve` as a single WIT document.
+fn print(resolve: &Resolve) -> Option<String> {
+    let pkgs = resolve.topological_packages();
+    // Removing items may leave dangling references behind which the printer
+    // isn't prepared for, and such candidates are simply skipped.
+    let mut ret = None;
+    catch(|| ret = WitPrinter::default().print(resolve, &pkgs, true).ok());
+    ret
+}
+
+/// Parses `wit` and returns the world in it corresponding to `world` in the
+/// `original` resolve.
+fn reparse(wit: &str, original: &Resolve, world: WorldId) -> Option<(Resolve, WorldId)> {
+    let mut resolve = Resolve::default();
+    resolve.push_str("reproducer.wit", wit).ok()?;
+    let name = package_name(original, original.worlds[world].package?);
+    let world_name = &original.worlds[world].name;
+    let id = resolve
+        .worlds
+        .iter()
+        .find(|(_, w)| {
+            &w.name == world_name
+                && w.package.map(|p| package_name(&resolve, p)) == Some(name.clone())
+        })?
+        .0;
+    Some((resolve, id))
+}
+
+fn package_name(resolve: &Resolve, pkg: PackageId) -> String {
+    resolve.packages[pkg].name.to_string()
+}
+
+/// Runs `f`, returning the message of the panic it raised, if any.
+///
+/// The panic hook is suppressed while `f` runs so that expected panics aren't
+/// printed.
+fn catch(f: impl FnOnce()) -> Option<String> {
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let result = panic::catch_unwind(AssertUnwindSafe(f));
+    panic::set_hook(hook);
+    let payload = result.err()?;
+    Some(if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else if let Some(s) = payload.downcast_ref::<&str>() {
+        s.to_string()
+    } else {
+        "<non-string panic payload>".to_string()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wit_bindgen_core::wit_parser::WorldItem;
+
+    #[test]
+    fn minimize_removes_unrelated_items() {
+        let mut resolve = Resolve::default();
+        resolve
+            .push_str(
+                "test.wit",
+                r#"
+                    package test:minimize;
+
+                    interface unrelated {
+                        record r { a: u32 }
+                        f: func(r: r);
+                    }
+
+                    interface target {
+                        type t = list<string>;
+                        variant v { a(t), b }
+                        boom: func(v: v);
+                        other: func() -> u32;
+                    }
+
+                    world w {
+                        import unrelated;
+                        import target;
+                        export unrelated;
+                        export g: func();
+                    }
+
+                    world other {
+                        import target;
+                    }
+                "#,
+            )
+            .unwrap();
+        let world = resolve
+            .worlds
+            .iter()
+            .find(|(_, w)| w.name == "w")
+            .unwrap()
+            .0;
+
+        // Pretend a generator panics on any world importing `boom`.
+        let wit = minimize(&resolve, world, |resolve, world| {
+            resolve.worlds[world]
+                .imports
+                .values()
+                .any(|item| match item {
+                    WorldItem::Interface { id, .. } => {
+                        resolve.interfaces[*id].functions.contains_key("boom")
+                    }
+                    _ => false,
+                })
+        });
+        assert_eq!(
+            wit,
+            "\
+package test:minimize {
+  interface target {
+    type t = list<string>;
+
+    variant v {
+      a(t),
+      b,
+    }
+
+    boom: func(v: v);
+  }
+
+  world w {
+    import target;
+  }
+}
+"
+        );
+    }
+}
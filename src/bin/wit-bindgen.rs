@@ -1,9 +1,17 @@
 use anyhow::{bail, Context, Error, Result};
 use clap::Parser;
-use std::path::PathBuf;
+use serde::Deserialize;
+use similar::TextDiff;
+use std::collections::hash_map::{Entry, HashMap};
+use std::collections::{BTreeSet, HashSet};
+use std::ffi::OsString;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
 use std::str;
+use wit_bindgen_core::cargo::{self, Lock};
 use wit_bindgen_core::{wit_parser, Files, WorldGenerator};
-use wit_parser::Resolve;
+use wit_parser::{PackageId, Resolve};
 
 /// Helper for passing VERSION to opt.
 /// If CARGO_VERSION_INFO is set, use it, otherwise use CARGO_PKG_VERSION.
@@ -39,6 +47,15 @@ enum Opt {
         args: Common,
     },
 
+    /// Generates bindings for C++ guest modules.
+    #[cfg(feature = "cpp")]
+    Cpp {
+        #[clap(flatten)]
+        opts: wit_bindgen_cpp::Opts,
+        #[clap(flatten)]
+        args: Common,
+    },
+
     /// Generates bindings for TeaVM-based Java guest modules.
     #[cfg(feature = "teavm-java")]
     TeavmJava {
@@ -64,6 +81,308 @@ enum Opt {
         #[clap(flatten)]
         args: Common,
     },
+
+    /// Generates a file containing only the encoded component type of a world.
+    ///
+    /// This is the same type information that language generators embed in
+    /// their output, for toolchains which don't have a generator of their own
+    /// or which link the type information separately.
+    #[cfg(feature = "c")]
+    ComponentType {
+        #[clap(flatten)]
+        opts: component_type::Opts,
+        #[clap(flatten)]
+        args: Common,
+    },
+
+    /// Generates bindings for every target listed in a `wit-bindgen.toml`
+    /// configuration file.
+    ///
+    /// All targets are generated in one run, sharing the parsed WIT
+    /// documents between targets which use the same WIT path and features.
+    Config {
+        /// Path to the configuration file.
+        #[clap(value_name = "CONFIG", index = 1, default_value = "wit-bindgen.toml")]
+        config: PathBuf,
+
+        /// Indicates that no files are written and instead files of every
+        /// target are checked if they're up-to-date with the source files.
+        ///
+        /// This reports differences and exits the same way as `--check` for a
+        /// single generator.
+        #[clap(long)]
+        check: bool,
+
+        /// Writes the lockfile next to every `Cargo.toml` used by a target,
+        /// the same way as `--update-lockfile` for a single generator.
+        #[clap(long, conflicts_with = "check")]
+        update_lockfile: bool,
+    },
+}
+
+impl Opt {
+    /// Returns the generator and common arguments selected by this
+    /// subcommand, or `None` for `config`.
+    fn generator(self) -> Option<(Box<dyn WorldGenerator>, Common)> {
+        Some(match self {
+            #[cfg(feature = "markdown")]
+            Opt::Markdown { opts, args } => (opts.build(), args),
+            #[cfg(feature = "c")]
+            Opt::C { opts, args } => (opts.build(), args),
+            #[cfg(feature = "cpp")]
+            Opt::Cpp { opts, args } => (opts.build(), args),
+            #[cfg(feature = "rust")]
+            Opt::Rust { opts, args } => (opts.build(), args),
+            #[cfg(feature = "teavm-java")]
+            Opt::TeavmJava { opts, args } => (opts.build(), args),
+            #[cfg(feature = "go")]
+            Opt::TinyGo { opts, args } => (opts.build(), args),
+            #[cfg(feature = "csharp")]
+            Opt::CSharp { opts, args } => (opts.build(), args),
+            #[cfg(feature = "c")]
+            Opt::ComponentType { opts, args } => {
+                (Box::new(component_type::ComponentType { opts }), args)
+            }
+            Opt::Config { .. } => return None,
+        })
+    }
+}
+
+/// The `component-type` subcommand, which reuses the C generator's
+/// component type object.
+#[cfg(feature = "c")]
+mod component_type {
+    use anyhow::Result;
+    use heck::ToSnakeCase;
+    use wasm_encoder::Module;
+    use wit_bindgen_c::component_type_object::{custom_section, object};
+    use wit_bindgen_core::wit_parser::{Function, InterfaceId, Resolve, TypeId, WorldId, WorldKey};
+    use wit_bindgen_core::{Files, WorldGenerator};
+    use wit_component::StringEncoding;
+
+    #[derive(Debug, Clone, clap::Args)]
+    pub struct Opts {
+        /// Format of the generated file.
+        #[clap(long, value_enum, default_value_t = Format::Object)]
+        format: Format,
+
+        /// Set component string encoding
+        #[clap(long, default_value_t = StringEncoding::default())]
+        string_encoding: StringEncoding,
+
+        /// Add the specified suffix to the name of the custom section containing
+        /// the component type.
+        #[clap(long)]
+        type_section_suffix: Option<String>,
+    }
+
+    #[derive(Debug, Clone, Copy, clap::ValueEnum)]
+    pub enum Format {
+        /// A core wasm module containing just the `component-type` custom
+        /// section, written to `<world>_component_type.wasm`.
+        Wasm,
+        /// A relocatable object file containing the `component-type` custom
+        /// section, written to `<world>_component_type.o`.
+        ///
+        /// This is the same object the C generator emits and it defines the
+        /// `__component_type_object_force_link_<world>` symbol to ensure the
+        /// linker keeps the section.
+        Object,
+        /// The encoded WIT world by itself, written to
+        /// `<world>_component_type.wit.wasm`.
+        Wit,
+    }
+
+    /// Generator for the `component-type` subcommand.
+    pub struct ComponentType {
+        pub opts: Opts,
+    }
+
+    impl WorldGenerator for ComponentType {
+        fn import_interface(
+            &mut self,
+            _resolve: &Resolve,
+            _name: &WorldKey,
+            _iface: InterfaceId,
+            _files: &mut Files,
+        ) -> Result<()> {
+            Ok(())
+        }
+
+        fn export_interface(
+            &mut self,
+            _resolve: &Resolve,
+            _name: &WorldKey,
+            _iface: InterfaceId,
+            _files: &mut Files,
+        ) -> Result<()> {
+            Ok(())
+        }
+
+        fn import_funcs(
+            &mut self,
+            _resolve: &Resolve,
+            _world: WorldId,
+            _funcs: &[(&str, &Function)],
+            _files: &mut Files,
+        ) {
+        }
+
+        fn export_funcs(
+            &mut self,
+            _resolve: &Resolve,
+            _world: WorldId,
+            _funcs: &[(&str, &Function)],
+            _files: &mut Files,
+        ) -> Result<()> {
+            Ok(())
+        }
+
+        fn import_types(
+            &mut self,
+            _resolve: &Resolve,
+            _world: WorldId,
+            _types: &[(&str, TypeId)],
+            _files: &mut Files,
+        ) {
+        }
+
+        fn finish(&mut self, resolve: &Resolve, world: WorldId, files: &mut Files) -> Result<()> {
+            let world_name = &resolve.worlds[world].name;
+            let snake = world_name.to_snake_case();
+            let encoding = self.opts.string_encoding;
+            let suffix = self.opts.type_section_suffix.as_deref();
+
+            match self.opts.format {
+                Format::Wit => {
+                    let section = custom_section(resolve, world, world_name, encoding, suffix)?;
+                    files.push(&format!("{snake}_component_type.wit.wasm"), &section.data);
+                }
+                Format::Wasm => {
+                    let mut module = Module::new();
+                    module.section(&custom_section(
+                        resolve, world, world_name, encoding, suffix,
+                    )?);
+                    files.push(&format!("{snake}_component_type.wasm"), &module.finish());
+                }
+                Format::Object => {
+                    let object = object(resolve, world, world_name, encoding, suffix)?;
+                    files.push(&format!("{snake}_component_type.o"), &object);
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Contents of a `wit-bindgen.toml` file.
+///
+/// ```toml
+/// wit = "wit"
+/// features = ["experimental"]
+///
+/// [[target]]
+/// generator = "rust"
+/// out-dir = "src/bindings"
+/// options = { generate-all = true, with = { "wasi:io/poll" = "wasi::io::poll" } }
+///
+/// [[target]]
+/// generator = "c"
+/// world = "my-world"
+/// out-dir = "c"
+/// ```
+///
+/// Relative paths are interpreted relative to the directory containing the
+/// configuration file.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct Config {
+    /// Default location of WIT file(s) for all targets.
+    wit: Option<PathBuf>,
+    /// Features enabled for all targets.
+    #[serde(default)]
+    features: Vec<String>,
+    #[serde(default, rename = "target")]
+    targets: Vec<Target>,
+}
+
+/// A single `[[target]]` entry of a `wit-bindgen.toml` file.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct Target {
+    /// Name of the generator subcommand, such as `rust` or `c-sharp`.
+    generator: String,
+    /// Location of WIT file(s), overriding the top-level `wit`.
+    wit: Option<PathBuf>,
+    world: Option<String>,
+    out_dir: Option<PathBuf>,
+    /// Features enabled in addition to the top-level `features`.
+    #[serde(default)]
+    features: Vec<String>,
+    /// Generator options, keyed by the name of their command line flag.
+    #[serde(default)]
+    options: toml::Table,
+}
+
+impl Target {
+    /// Translates this target into the command line arguments of its
+    /// generator subcommand so options are validated the same way as when
+    /// passed as flags.
+    fn args(&self, config: &Config, dir: &Path) -> Result<Vec<OsString>> {
+        let mut args = vec![OsString::from("wit-bindgen")];
+        args.push(self.generator.clone().into());
+        let wit = self
+            .wit
+            .as_ref()
+            .or(config.wit.as_ref())
+            .with_context(|| format!("no `wit` path specified for `{}`", self.generator))?;
+        args.push(dir.join(wit).into());
+        if let Some(world) = &self.world {
+            args.push("--world".into());
+            args.push(world.into());
+        }
+        if let Some(out_dir) = &self.out_dir {
+            args.push("--out-dir".into());
+            args.push(dir.join(out_dir).into());
+        }
+        for feature in config.features.iter().chain(&self.features) {
+            args.push("--features".into());
+            args.push(feature.into());
+        }
+        for (name, value) in self.options.iter() {
+            let flag = format!("--{name}");
+            let mut push = |value: &toml::Value| -> Result<()> {
+                let value = match value {
+                    toml::Value::String(s) => s.clone(),
+                    toml::Value::Integer(i) => i.to_string(),
+                    _ => bail!("unsupported value for option `{name}`: {value}"),
+                };
+                args.push(flag.clone().into());
+                args.push(value.into());
+                Ok(())
+            };
+            match value {
+                toml::Value::Boolean(true) => args.push(flag.into()),
+                toml::Value::Boolean(false) => {}
+                toml::Value::Array(values) => {
+                    for value in values {
+                        push(value)?;
+                    }
+                }
+                toml::Value::Table(table) => {
+                    for (key, value) in table {
+                        let value = match value {
+                            toml::Value::String(s) => s,
+                            _ => bail!("unsupported value for option `{name}.{key}`: {value}"),
+                        };
+                        push(&toml::Value::String(format!("{key}={value}")))?;
+                    }
+                }
+                value => push(value)?,
+            }
+        }
+        Ok(args)
+    }
 }
 
 #[derive(Debug, Parser)]
@@ -78,6 +397,12 @@ struct Common {
     /// file itself, or a `*.wasm` file which is a wasm-encoded WIT package.
     /// Most of the time it's likely to be a directory containing `*.wit` files
     /// with an optional `deps` folder inside of it.
+    ///
+    /// This can also be a `Cargo.toml` with a
+    /// `[package.metadata.component.target]` table, in which case the WIT
+    /// package and its dependencies are resolved the same way as the
+    /// `generate!` macro does, including checking the `wit-bindgen.lock`
+    /// lockfile next to it if there is one.
     #[clap(value_name = "WIT", index = 1)]
     wit: PathBuf,
 
@@ -89,15 +414,28 @@ struct Common {
     /// must be specified to name the world that bindings are generated for.
     /// This option can also use the fully qualified syntax such as
     /// `wasi:http/proxy` to select a world from a dependency of the main WIT
-    /// package.
+    /// package. Defaults to the world of the target when `WIT` is a
+    /// `Cargo.toml`.
     #[clap(short, long)]
     world: Option<String>,
 
     /// Indicates that no files are written and instead files are checked if
     /// they're up-to-date with the source files.
+    ///
+    /// A unified diff is printed for each out-of-date file, along with the
+    /// files which would be created or deleted. The exit status is 1 if any
+    /// file is out of date and 2 if an error occurred.
     #[clap(long)]
     check: bool,
 
+    /// Writes the `wit-bindgen.lock` lockfile next to a `Cargo.toml` given as
+    /// `WIT`, recording the packages its dependencies resolve to.
+    ///
+    /// Without this flag the lockfile is never written, and if it exists it's
+    /// an error for the packages used not to match it.
+    #[clap(long, conflicts_with = "check")]
+    update_lockfile: bool,
+
     /// Comma-separated list of features that should be enabled when processing
     /// WIT files.
     ///
@@ -106,66 +444,199 @@ struct Common {
     features: Vec<String>,
 }
 
-fn main() -> Result<()> {
-    let mut files = Files::default();
-    let (generator, opt) = match Opt::parse() {
-        #[cfg(feature = "markdown")]
-        Opt::Markdown { opts, args } => (opts.build(), args),
-        #[cfg(feature = "c")]
-        Opt::C { opts, args } => (opts.build(), args),
-        #[cfg(feature = "rust")]
-        Opt::Rust { opts, args } => (opts.build(), args),
-        #[cfg(feature = "teavm-java")]
-        Opt::TeavmJava { opts, args } => (opts.build(), args),
-        #[cfg(feature = "go")]
-        Opt::TinyGo { opts, args } => (opts.build(), args),
-        #[cfg(feature = "csharp")]
-        Opt::CSharp { opts, args } => (opts.build(), args),
-    };
-
-    gen_world(generator, &opt, &mut files).map_err(attach_with_context)?;
-
-    for (name, contents) in files.iter() {
-        let dst = match &opt.out_dir {
-            Some(path) => path.join(name),
-            None => name.into(),
-        };
-        println!("Generating {:?}", dst);
+fn main() -> ExitCode {
+    match run() {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::from(1),
+        Err(e) => {
+            eprintln!("Error: {e:?}");
+            ExitCode::from(2)
+        }
+    }
+}
 
-        if opt.check {
-            let prev = std::fs::read(&dst).with_context(|| format!("failed to read {:?}", dst))?;
-            if prev != contents {
-                // The contents differ. If it looks like textual contents, do a
-                // line-by-line comparison so that we can tell users what the
-                // problem is directly.
-                if let (Ok(utf8_prev), Ok(utf8_contents)) =
-                    (str::from_utf8(&prev), str::from_utf8(contents))
-                {
-                    if !utf8_prev
-                        .chars()
-                        .any(|c| c.is_control() && !matches!(c, '\n' | '\r' | '\t'))
-                        && utf8_prev.lines().eq(utf8_contents.lines())
-                    {
-                        bail!("{} differs only in line endings (CRLF vs. LF). If this is a text file, configure git to mark the file as `text eol=lf`.", dst.display());
-                    }
-                }
-                // The contents are binary or there are other differences; just
-                // issue a generic error.
-                bail!("not up to date: {}", dst.display());
-            }
-            continue;
+/// Runs the CLI, returning whether all files are up-to-date.
+fn run() -> Result<bool> {
+    let opt = Opt::parse();
+    if let Opt::Config {
+        config,
+        check,
+        update_lockfile,
+    } = &opt
+    {
+        return run_config(config, *check, *update_lockfile);
+    }
+    let (generator, opt) = opt.generator().unwrap();
+    let (resolve, pkgs, world) = parse_wit(&opt.wit, &opt.features, lock(opt.update_lockfile))?;
+    let outputs = gen_world(generator, &opt, &resolve, &pkgs, world.as_deref())?;
+    if opt.check {
+        return check_files(&outputs, opt.out_dir.as_slice());
+    }
+    write_files(&outputs)?;
+    Ok(true)
+}
+
+fn run_config(path: &Path, check: bool, update_lockfile: bool) -> Result<bool> {
+    let contents =
+        std::fs::read_to_string(path).with_context(|| format!("failed to read {path:?}"))?;
+    let config: Config =
+        toml::from_str(&contents).with_context(|| format!("failed to parse {path:?}"))?;
+    if config.targets.is_empty() {
+        bail!("no targets listed in {path:?}");
+    }
+    let dir = path.parent().unwrap_or(Path::new(""));
+
+    // Targets are parsed up-front so that invalid options are reported before
+    // anything is generated.
+    let mut targets = Vec::new();
+    for target in config.targets.iter() {
+        let args = target.args(&config, dir)?;
+        let opt = Opt::try_parse_from(args)
+            .with_context(|| format!("invalid target `{}` in {path:?}", target.generator))?;
+        match opt.generator() {
+            Some(target) => targets.push(target),
+            None => bail!("`config` cannot be used as a generator in {path:?}"),
+        }
+    }
+
+    // WIT documents are parsed once for each distinct combination of path,
+    // features and what's done with the lockfile.
+    let mut resolves: HashMap<(PathBuf, BTreeSet<String>, Lock), ParsedWit> = HashMap::new();
+    let mut outputs = Vec::new();
+    let mut out_dirs = Vec::new();
+    for (generator, opt) in targets {
+        let lock = lock(update_lockfile || opt.update_lockfile);
+        let key = (opt.wit.clone(), features(&opt.features), lock);
+        let (resolve, pkgs, world) = match resolves.entry(key) {
+            Entry::Occupied(e) => e.into_mut(),
+            Entry::Vacant(e) => e.insert(parse_wit(&opt.wit, &opt.features, lock)?),
+        };
+        let files = gen_world(generator, &opt, resolve, pkgs, world.as_deref())?;
+        if !check {
+            write_files(&files)?;
         }
+        outputs.extend(files);
+        out_dirs.extend(opt.out_dir);
+    }
 
+    // Files of all targets are checked together since targets may share an
+    // output directory.
+    if check {
+        return check_files(&outputs, &out_dirs);
+    }
+    Ok(true)
+}
+
+fn write_files(outputs: &[(PathBuf, Vec<u8>)]) -> Result<()> {
+    for (dst, contents) in outputs {
+        println!("Generating {:?}", dst);
         if let Some(parent) = dst.parent() {
             std::fs::create_dir_all(parent)
                 .with_context(|| format!("failed to create {:?}", parent))?;
         }
-        std::fs::write(&dst, contents).with_context(|| format!("failed to write {:?}", dst))?;
+        std::fs::write(dst, contents).with_context(|| format!("failed to write {:?}", dst))?;
     }
 
     Ok(())
 }
 
+/// Compares `outputs` with the files on disk, printing a report of every
+/// difference, and returns whether everything is up-to-date.
+///
+/// Besides files which differ or don't exist yet, files which were generated
+/// by `wit-bindgen` but are no longer part of `outputs` are reported as files
+/// which would be deleted. Only the directories of `outputs` within
+/// `out_dirs` are searched for these, as other directories such as the
+/// current one may well contain files generated for something else.
+fn check_files(outputs: &[(PathBuf, Vec<u8>)], out_dirs: &[PathBuf]) -> Result<bool> {
+    let mut up_to_date = true;
+    for (dst, contents) in outputs {
+        let prev = match std::fs::read(dst) {
+            Ok(prev) => prev,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                println!("would create: {}", dst.display());
+                up_to_date = false;
+                continue;
+            }
+            Err(e) => return Err(e).with_context(|| format!("failed to read {:?}", dst)),
+        };
+        if prev == *contents {
+            continue;
+        }
+        up_to_date = false;
+
+        // The contents differ. If it looks like textual contents, do a
+        // line-by-line comparison so that we can tell users what the
+        // problem is directly.
+        match (str::from_utf8(&prev), str::from_utf8(contents)) {
+            (Ok(utf8_prev), Ok(utf8_contents))
+                if !utf8_prev
+                    .chars()
+                    .any(|c| c.is_control() && !matches!(c, '\n' | '\r' | '\t')) =>
+            {
+                if utf8_prev.lines().eq(utf8_contents.lines()) {
+                    println!("{} differs only in line endings (CRLF vs. LF). If this is a text file, configure git to mark the file as `text eol=lf`.", dst.display());
+                    continue;
+                }
+                let name = dst.display().to_string();
+                print!(
+                    "{}",
+                    TextDiff::from_lines(utf8_prev, utf8_contents)
+                        .unified_diff()
+                        .header(&format!("a/{name}"), &format!("b/{name}"))
+                );
+            }
+            // The contents are binary; just issue a generic message.
+            _ => println!("not up to date: {}", dst.display()),
+        }
+    }
+
+    let generated = outputs
+        .iter()
+        .map(|(dst, _)| dst.as_path())
+        .collect::<HashSet<_>>();
+    let dirs = generated
+        .iter()
+        .filter_map(|dst| dst.parent())
+        .filter(|dir| out_dirs.iter().any(|out_dir| dir.starts_with(out_dir)))
+        .collect::<BTreeSet<_>>();
+    for dir in dirs {
+        let entries = match std::fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e).with_context(|| format!("failed to read {:?}", dir)),
+        };
+        let mut stale = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            if generated.contains(path.as_path()) {
+                continue;
+            }
+            if path.is_file() && is_generated(&path)? {
+                stale.push(path);
+            }
+        }
+        stale.sort();
+        for path in stale {
+            println!("would delete: {}", path.display());
+            up_to_date = false;
+        }
+    }
+
+    Ok(up_to_date)
+}
+
+/// Returns whether `path` starts with the preamble which `wit-bindgen`
+/// places at the top of generated source files.
+fn is_generated(path: &Path) -> Result<bool> {
+    let contents = std::fs::read(path).with_context(|| format!("failed to read {:?}", path))?;
+    let head = &contents[..contents.len().min(512)];
+    Ok(String::from_utf8_lossy(head)
+        .lines()
+        .take(5)
+        .any(|line| line.contains("Generated by `wit-bindgen`")))
+}
+
 fn attach_with_context(err: Error) -> Error {
     #[cfg(feature = "rust")]
     if let Some(e) = err.downcast_ref::<wit_bindgen_rust::MissingWith>() {
@@ -180,23 +651,59 @@ fn attach_with_context(err: Error) -> Error {
 fn gen_world(
     mut generator: Box<dyn WorldGenerator>,
     opts: &Common,
-    files: &mut Files,
-) -> Result<()> {
-    let mut resolve = Resolve::default();
-    for features in opts.features.iter() {
-        for feature in features
-            .split(',')
-            .flat_map(|s| s.split_whitespace())
-            .filter(|f| !f.is_empty())
-        {
-            resolve.features.insert(feature.to_string());
-        }
+    resolve: &Resolve,
+    pkgs: &[PackageId],
+    default_world: Option<&str>,
+) -> Result<Vec<(PathBuf, Vec<u8>)>> {
+    let mut files = Files::default();
+    let world = resolve.select_world(pkgs, opts.world.as_deref().or(default_world))?;
+    generator
+        .generate(resolve, world, &mut files)
+        .map_err(attach_with_context)?;
+    Ok(files
+        .iter()
+        .map(|(name, contents)| {
+            let dst = match &opts.out_dir {
+                Some(path) => path.join(name),
+                None => name.into(),
+            };
+            (dst, contents.to_vec())
+        })
+        .collect())
+}
+
+/// Splits the comma- or whitespace-separated `--features` arguments.
+fn features(features: &[String]) -> BTreeSet<String> {
+    features
+        .iter()
+        .flat_map(|s| s.split(','))
+        .flat_map(|s| s.split_whitespace())
+        .filter(|f| !f.is_empty())
+        .map(|f| f.to_string())
+        .collect()
+}
+
+/// A parsed `WIT` argument along with the world it names by default, if any.
+type ParsedWit = (Resolve, Vec<PackageId>, Option<String>);
+
+/// Returns what to do with the lockfile of a `Cargo.toml` given as `WIT`.
+fn lock(update_lockfile: bool) -> Lock {
+    if update_lockfile {
+        Lock::Update
+    } else {
+        Lock::Verify
     }
-    let (pkgs, _files) = resolve.push_path(&opts.wit)?;
-    let world = resolve.select_world(&pkgs, opts.world.as_deref())?;
-    generator.generate(&resolve, world, files)?;
+}
 
-    Ok(())
+fn parse_wit(wit: &Path, features_list: &[String], lock: Lock) -> Result<ParsedWit> {
+    let mut resolve = Resolve::default();
+    resolve.features.extend(features(features_list));
+    if wit.file_name().is_some_and(|name| name == "Cargo.toml") {
+        let target = cargo::push_manifest(&mut resolve, wit, lock)?;
+        return Ok((resolve, target.packages, target.world));
+    }
+    let (pkgs, _files) = resolve.push_path(wit)?;
+    Ok((resolve, pkgs, None))
 }
 
 #[test]
@@ -204,3 +711,102 @@ fn verify_cli() {
     use clap::CommandFactory;
     Opt::command().debug_assert()
 }
+
+#[test]
+fn config_targets() {
+    let config: Config = toml::from_str(
+        r#"
+            wit = "wit"
+            features = ["a"]
+
+            [[target]]
+            generator = "rust"
+            out-dir = "src/bindings"
+            features = ["b"]
+            options = { generate-all = true, stubs = false, with = { "wasi:io/poll" = "wasi::io::poll" }, additional_derive_attribute = ["PartialEq", "Eq"] }
+
+            [[target]]
+            generator = "c"
+            wit = "c.wit"
+            world = "w"
+            options = { string-encoding = "utf16" }
+        "#,
+    )
+    .unwrap();
+    assert_eq!(config.targets.len(), 2);
+
+    let dir = Path::new("dir");
+    let args = config.targets[0].args(&config, dir).unwrap();
+    assert_eq!(
+        args,
+        [
+            "wit-bindgen",
+            "rust",
+            "dir/wit",
+            "--out-dir",
+            "dir/src/bindings",
+            "--features",
+            "a",
+            "--features",
+            "b",
+            "--additional_derive_attribute",
+            "PartialEq",
+            "--additional_derive_attribute",
+            "Eq",
+            "--generate-all",
+            "--with",
+            "wasi:io/poll=wasi::io::poll",
+        ]
+    );
+    let (_, common) = Opt::try_parse_from(args).unwrap().generator().unwrap();
+    assert_eq!(common.wit, Path::new("dir/wit"));
+    assert_eq!(
+        common.out_dir.as_deref(),
+        Some(Path::new("dir/src/bindings"))
+    );
+    assert_eq!(
+        features(&common.features),
+        ["a", "b"].map(String::from).into()
+    );
+    assert!(!common.check);
+
+    let args = config.targets[1].args(&config, dir).unwrap();
+    assert_eq!(
+        args,
+        [
+            "wit-bindgen",
+            "c",
+            "dir/c.wit",
+            "--world",
+            "w",
+            "--features",
+            "a",
+            "--string-encoding",
+            "utf16",
+        ]
+    );
+    let (_, common) = Opt::try_parse_from(args).unwrap().generator().unwrap();
+    assert_eq!(common.world.as_deref(), Some("w"));
+    assert_eq!(common.out_dir, None);
+}
+
+#[test]
+fn config_errors() {
+    let parse = |s: &str| toml::from_str::<Config>(s);
+    assert!(parse("[[target]]\ngenerator = \"rust\"\nunknown = 1").is_err());
+
+    let config = parse("[[target]]\ngenerator = \"rust\"").unwrap();
+    let err = config.targets[0].args(&config, Path::new("")).unwrap_err();
+    assert_eq!(err.to_string(), "no `wit` path specified for `rust`");
+
+    let config =
+        parse("wit = \"wit\"\n[[target]]\ngenerator = \"rust\"\noptions = { x = 1.5 }").unwrap();
+    let err = config.targets[0].args(&config, Path::new("")).unwrap_err();
+    assert_eq!(err.to_string(), "unsupported value for option `x`: 1.5");
+
+    let config =
+        parse("wit = \"wit\"\n[[target]]\ngenerator = \"rust\"\noptions = { no-such-flag = true }")
+            .unwrap();
+    let args = config.targets[0].args(&config, Path::new("")).unwrap();
+    assert!(Opt::try_parse_from(args).is_err());
+}
@@ -0,0 +1,308 @@
+//! Tests running the `wit-bindgen` executable itself.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Creates an empty directory for the test `name`.
+fn test_dir(name: &str) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR"))
+        .join("cli")
+        .join(name);
+    drop(fs::remove_dir_all(&dir));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Runs `wit-bindgen` with `args` in `dir`, returning its exit code and
+/// standard output.
+fn wit_bindgen(dir: &Path, args: &[&str]) -> (i32, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_wit-bindgen"))
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !stderr.is_empty() {
+        eprintln!("{stderr}");
+    }
+    (output.status.code().unwrap(), stdout)
+}
+
+const WIT: &str = "
+    package my:test;
+
+    interface i {
+        f: func(s: string) -> u32;
+    }
+
+    world w {
+        import i;
+        export i;
+    }
+";
+
+#[test]
+fn config() {
+    let dir = test_dir("config");
+    fs::write(dir.join("test.wit"), WIT).unwrap();
+    fs::write(
+        dir.join("wit-bindgen.toml"),
+        r#"
+            wit = "test.wit"
+
+            [[target]]
+            generator = "rust"
+            out-dir = "rust"
+            options = { stubs = true, format = true }
+
+            [[target]]
+            generator = "markdown"
+            out-dir = "docs"
+        "#,
+    )
+    .unwrap();
+
+    let (code, stdout) = wit_bindgen(&dir, &["config", "--check"]);
+    assert_eq!(code, 1);
+    assert!(stdout.contains("would create: rust/w.rs"), "{stdout}");
+    assert!(stdout.contains("would create: docs/w.md"), "{stdout}");
+    assert!(!dir.join("rust").exists());
+
+    let (code, _) = wit_bindgen(&dir, &["config"]);
+    assert_eq!(code, 0);
+    let rust = fs::read_to_string(dir.join("rust/w.rs")).unwrap();
+    assert!(rust.contains("impl exports::my::test::i::Guest for Stub"));
+    assert!(dir.join("docs/w.md").exists());
+
+    let (code, stdout) = wit_bindgen(&dir, &["config", "--check"]);
+    assert_eq!((code, stdout.as_str()), (0, ""));
+
+    // Every target is checked, even when an earlier one is out of date.
+    fs::write(dir.join("rust/w.rs"), "").unwrap();
+    fs::remove_file(dir.join("docs/w.md")).unwrap();
+    let (code, stdout) = wit_bindgen(&dir, &["config", "--check"]);
+    assert_eq!(code, 1);
+    assert!(stdout.contains("+++ b/rust/w.rs"), "{stdout}");
+    assert!(stdout.contains("would create: docs/w.md"), "{stdout}");
+}
+
+#[test]
+fn config_invalid_target() {
+    let dir = test_dir("config-invalid-target");
+    fs::write(dir.join("test.wit"), WIT).unwrap();
+    fs::write(
+        dir.join("wit-bindgen.toml"),
+        r#"
+            wit = "test.wit"
+
+            [[target]]
+            generator = "markdown"
+            out-dir = "docs"
+
+            [[target]]
+            generator = "rust"
+            options = { no-such-option = true }
+        "#,
+    )
+    .unwrap();
+
+    // Nothing is generated if any target is invalid.
+    let (code, _) = wit_bindgen(&dir, &["config"]);
+    assert_eq!(code, 2);
+    assert!(!dir.join("docs").exists());
+}
+
+#[test]
+fn check() {
+    let dir = test_dir("check");
+    fs::write(dir.join("test.wit"), WIT).unwrap();
+    let args = ["rust", "test.wit", "--out-dir", "out", "--check"];
+
+    // Missing files are out of date.
+    let (code, stdout) = wit_bindgen(&dir, &args);
+    assert_eq!((code, stdout.as_str()), (1, "would create: out/w.rs\n"));
+
+    let (code, _) = wit_bindgen(&dir, &args[..4]);
+    assert_eq!(code, 0);
+    let (code, stdout) = wit_bindgen(&dir, &args);
+    assert_eq!((code, stdout.as_str()), (0, ""));
+
+    // Changed files are reported with a unified diff.
+    let rs = fs::read_to_string(dir.join("out/w.rs")).unwrap();
+    fs::write(
+        dir.join("out/w.rs"),
+        rs.replacen("pub mod my {", "pub mod x {", 1),
+    )
+    .unwrap();
+    let (code, stdout) = wit_bindgen(&dir, &args);
+    assert_eq!(code, 1);
+    assert!(
+        stdout.starts_with("--- a/out/w.rs\n+++ b/out/w.rs\n@@ "),
+        "{stdout}"
+    );
+    assert!(
+        stdout.contains("\n-pub mod x {\n+pub mod my {\n"),
+        "{stdout}"
+    );
+
+    // Files which only differ in line endings are reported as such.
+    fs::write(dir.join("out/w.rs"), rs.replace('\n', "\r\n")).unwrap();
+    let (code, stdout) = wit_bindgen(&dir, &args);
+    assert_eq!(code, 1);
+    assert!(
+        stdout.starts_with("out/w.rs differs only in line endings (CRLF vs. LF)."),
+        "{stdout}"
+    );
+    fs::write(dir.join("out/w.rs"), &rs).unwrap();
+
+    // Files generated by `wit-bindgen` which would no longer be generated are
+    // reported, other files aren't.
+    fs::write(dir.join("out/old.rs"), &rs).unwrap();
+    fs::write(dir.join("out/lib.rs"), "mod w;\n").unwrap();
+    let (code, stdout) = wit_bindgen(&dir, &args);
+    assert_eq!((code, stdout.as_str()), (1, "would delete: out/old.rs\n"));
+
+    // Errors are reported with a distinct exit code.
+    let (code, _) = wit_bindgen(&dir, &["rust", "missing.wit", "--check"]);
+    assert_eq!(code, 2);
+}
+
+#[test]
+fn check_without_out_dir() {
+    let dir = test_dir("check-without-out-dir");
+    fs::write(dir.join("test.wit"), WIT).unwrap();
+
+    // Without `--out-dir` files are generated in the current directory, which
+    // isn't searched for stale files as it's not owned by the generator.
+    let (code, _) = wit_bindgen(&dir, &["rust", "test.wit"]);
+    assert_eq!(code, 0);
+    fs::write(dir.join("other.rs"), fs::read(dir.join("w.rs")).unwrap()).unwrap();
+    let (code, stdout) = wit_bindgen(&dir, &["rust", "test.wit", "--check"]);
+    assert_eq!((code, stdout.as_str()), (0, ""));
+}
+
+#[test]
+fn component_type() {
+    let dir = test_dir("component-type");
+    fs::write(dir.join("test.wit"), WIT).unwrap();
+
+    // Objects are the same as the ones the C generator emits.
+    let (code, _) = wit_bindgen(&dir, &["c", "test.wit", "--out-dir", "c"]);
+    assert_eq!(code, 0);
+    let (code, _) = wit_bindgen(&dir, &["component-type", "test.wit", "--out-dir", "o"]);
+    assert_eq!(code, 0);
+    assert_eq!(
+        fs::read(dir.join("o/w_component_type.o")).unwrap(),
+        fs::read(dir.join("c/w_component_type.o")).unwrap(),
+    );
+
+    let args = [
+        "component-type",
+        "test.wit",
+        "--format",
+        "wasm",
+        "--type-section-suffix=-x",
+    ];
+    let (code, _) = wit_bindgen(&dir, &args);
+    assert_eq!(code, 0);
+    let wasm = fs::read(dir.join("w_component_type.wasm")).unwrap();
+    let names = wasmparser::Parser::new(0)
+        .parse_all(&wasm)
+        .filter_map(|payload| match payload.unwrap() {
+            wasmparser::Payload::CustomSection(s) => Some(s.name().to_string()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["component-type:w-x"]);
+    let (_, bindgen) = wit_component::metadata::decode(&wasm).unwrap();
+    let world = &bindgen.resolve.worlds[bindgen.world];
+    for items in [&world.imports, &world.exports] {
+        let names = items
+            .keys()
+            .map(|key| bindgen.resolve.name_world_key(key))
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["my:test/i"]);
+    }
+
+    let (code, _) = wit_bindgen(&dir, &["component-type", "test.wit", "--format", "wit"]);
+    assert_eq!(code, 0);
+    let wit = fs::read(dir.join("w_component_type.wit.wasm")).unwrap();
+    assert!(wit.starts_with(b"\0asm"));
+}
+
+#[test]
+fn cargo_toml() {
+    let dir = test_dir("cargo-toml");
+    fs::write(
+        dir.join("Cargo.toml"),
+        r#"
+            [package]
+            name = "component"
+
+            [package.metadata.component.target]
+            world = "w"
+
+            [package.metadata.component.target.dependencies]
+            "my:dep" = { path = "dep" }
+        "#,
+    )
+    .unwrap();
+    fs::create_dir_all(dir.join("wit")).unwrap();
+    fs::write(
+        dir.join("wit/world.wit"),
+        "package my:test; world w { import my:dep/i; } world other {}",
+    )
+    .unwrap();
+    fs::create_dir_all(dir.join("dep")).unwrap();
+    fs::write(
+        dir.join("dep/i.wit"),
+        "package my:dep; interface i { f: func(); }",
+    )
+    .unwrap();
+    let lockfile = dir.join("wit-bindgen.lock");
+
+    // The world named by the metadata is used, and the lockfile isn't
+    // written unless asked to.
+    let args = ["rust", "Cargo.toml", "--out-dir", "out", "--generate-all"];
+    let (code, _) = wit_bindgen(&dir, &args);
+    assert_eq!(code, 0);
+    let rs = fs::read_to_string(dir.join("out/w.rs")).unwrap();
+    assert!(rs.contains("pub mod dep"), "{rs}");
+    assert!(!lockfile.exists());
+
+    let (code, _) = wit_bindgen(&dir, &[&args[..], &["--update-lockfile"]].concat());
+    assert_eq!(code, 0);
+    let locked = fs::read_to_string(&lockfile).unwrap();
+    assert!(locked.contains("name = \"my:dep\""), "{locked}");
+    assert!(locked.contains("source = \"dep\""), "{locked}");
+
+    // `--check` verifies the lockfile without writing it.
+    let check = [&args[..], &["--check"]].concat();
+    let (code, stdout) = wit_bindgen(&dir, &check);
+    assert_eq!((code, stdout.as_str()), (0, ""));
+    fs::write(
+        dir.join("dep/i.wit"),
+        "package my:dep; interface i { f: func(); g: func(); }",
+    )
+    .unwrap();
+    let (code, _) = wit_bindgen(&dir, &check);
+    assert_eq!(code, 2);
+    let (code, _) = wit_bindgen(&dir, &args);
+    assert_eq!(code, 2);
+    assert_eq!(fs::read_to_string(&lockfile).unwrap(), locked);
+
+    // The lockfile can't be updated while checking.
+    let (code, _) = wit_bindgen(&dir, &[&check[..], &["--update-lockfile"]].concat());
+    assert_eq!(code, 2);
+    assert_eq!(fs::read_to_string(&lockfile).unwrap(), locked);
+
+    let (code, _) = wit_bindgen(&dir, &[&args[..], &["--update-lockfile"]].concat());
+    assert_eq!(code, 0);
+    assert_ne!(fs::read_to_string(&lockfile).unwrap(), locked);
+    let (code, _) = wit_bindgen(&dir, &args);
+    assert_eq!(code, 0);
+    let (code, stdout) = wit_bindgen(&dir, &check);
+    assert_eq!((code, stdout.as_str()), (0, ""));
+}
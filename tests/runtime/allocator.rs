@@ -0,0 +1,75 @@
+use anyhow::Result;
+use wasmtime::Store;
+
+wasmtime::component::bindgen!(in "tests/runtime/allocator");
+
+fn run(name: &str, test: fn(Allocator, &mut Store<crate::Wasi<()>>) -> Result<()>) -> Result<()> {
+    crate::run_test_from_dir(
+        "allocator",
+        name,
+        |_| Ok(()),
+        |store, component, linker| Allocator::instantiate(store, component, linker),
+        test,
+    )
+}
+
+#[test]
+fn bump_arena() -> Result<()> {
+    run("allocator-bump-arena", run_bump_arena)
+}
+
+fn run_bump_arena(exports: Allocator, store: &mut Store<crate::Wasi<()>>) -> Result<()> {
+    // Arguments are allocated in the arena, which is reset once they're freed.
+    assert!(exports.call_allocated(&mut *store, "hello", &[1, 2, 3])?);
+    assert_eq!(exports.call_used(&mut *store)?, 0);
+
+    // Results are freed in post-return, which resets the arena as well.
+    assert_eq!(exports.call_echo(&mut *store, "hello")?, "hello");
+    assert_eq!(exports.call_used(&mut *store)?, 0);
+
+    // Arguments which don't fit in the arena are allocated elsewhere.
+    let big = "x".repeat(1 << 20);
+    assert!(!exports.call_allocated(&mut *store, &big, &[1])?);
+    assert_eq!(exports.call_echo(&mut *store, &big)?, big);
+    assert_eq!(exports.call_used(&mut *store)?, 0);
+    Ok(())
+}
+
+#[test]
+fn pool() -> Result<()> {
+    run("allocator-pool", run_pool)
+}
+
+fn run_pool(exports: Allocator, store: &mut Store<crate::Wasi<()>>) -> Result<()> {
+    assert!(exports.call_allocated(&mut *store, "hello", &[1, 2, 3])?);
+
+    // Freed blocks are reused for later allocations of the same size.
+    assert_eq!(exports.call_echo(&mut *store, "hello")?, "hello");
+    let used = exports.call_used(&mut *store)?;
+    assert_eq!(exports.call_echo(&mut *store, "world")?, "world");
+    assert!(exports.call_allocated(&mut *store, "hello", &[1, 2, 3])?);
+    assert_eq!(exports.call_used(&mut *store)?, used);
+
+    let big = "x".repeat(1 << 20);
+    assert_eq!(exports.call_echo(&mut *store, &big)?, big);
+    let used = exports.call_used(&mut *store)?;
+    assert_eq!(exports.call_echo(&mut *store, &big)?, big);
+    assert_eq!(exports.call_used(&mut *store)?, used);
+    Ok(())
+}
+
+#[test]
+fn realloc_hook() -> Result<()> {
+    run("allocator-realloc-hook", run_realloc_hook)
+}
+
+fn run_realloc_hook(exports: Allocator, store: &mut Store<crate::Wasi<()>>) -> Result<()> {
+    // The hook is registered by the first call.
+    assert_eq!(exports.call_used(&mut *store)?, 0);
+
+    assert!(exports.call_allocated(&mut *store, "hello", &[1, 2, 3])?);
+    assert_eq!(exports.call_used(&mut *store)?, 17);
+    assert_eq!(exports.call_echo(&mut *store, "hello")?, "hello");
+    assert_eq!(exports.call_used(&mut *store)?, 22);
+    Ok(())
+}
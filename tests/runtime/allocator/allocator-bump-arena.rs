@@ -0,0 +1,26 @@
+wit_bindgen::generate!({
+    path: "../../tests/runtime/allocator",
+});
+
+use wit_bindgen::allocator::BumpArena;
+
+#[global_allocator]
+static ALLOC: BumpArena<std::alloc::System, 65536> = BumpArena::new(std::alloc::System);
+
+pub struct Test {}
+
+export!(Test);
+
+impl Guest for Test {
+    fn allocated(s: String, l: Vec<u32>) -> bool {
+        ALLOC.contains(s.as_ptr()) && ALLOC.contains(l.as_ptr().cast())
+    }
+
+    fn echo(s: String) -> String {
+        s
+    }
+
+    fn used() -> u32 {
+        ALLOC.used() as u32
+    }
+}
@@ -0,0 +1,26 @@
+wit_bindgen::generate!({
+    path: "../../tests/runtime/allocator",
+});
+
+use wit_bindgen::allocator::Pool;
+
+#[global_allocator]
+static ALLOC: Pool<{ 8 << 20 }> = Pool::new();
+
+pub struct Test {}
+
+export!(Test);
+
+impl Guest for Test {
+    fn allocated(s: String, l: Vec<u32>) -> bool {
+        ALLOC.contains(s.as_ptr()) && ALLOC.contains(l.as_ptr().cast())
+    }
+
+    fn echo(s: String) -> String {
+        s
+    }
+
+    fn used() -> u32 {
+        ALLOC.used() as u32
+    }
+}
@@ -0,0 +1,60 @@
+wit_bindgen::generate!({
+    path: "../../tests/runtime/allocator",
+});
+
+use std::alloc::{self, Layout};
+use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+use std::sync::{Mutex, Once};
+
+/// Addresses of the allocations made by `hook`.
+static ALLOCATED: Mutex<Vec<usize>> = Mutex::new(Vec::new());
+
+/// The number of bytes allocated by `hook`.
+static USED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe fn hook(old_ptr: *mut u8, old_len: usize, align: usize, new_len: usize) -> *mut u8 {
+    if new_len == 0 {
+        return align as *mut u8;
+    }
+    let ptr = if old_len == 0 {
+        alloc::alloc(Layout::from_size_align_unchecked(new_len, align))
+    } else {
+        alloc::realloc(
+            old_ptr,
+            Layout::from_size_align_unchecked(old_len, align),
+            new_len,
+        )
+    };
+    ALLOCATED.lock().unwrap().push(ptr as usize);
+    USED.fetch_add(new_len.saturating_sub(old_len), Relaxed);
+    ptr
+}
+
+/// Registers `hook`, which only affects allocations made after the first call
+/// to an export.
+fn install() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| unsafe { wit_bindgen::allocator::set_realloc_hook(Some(hook)) });
+}
+
+pub struct Test {}
+
+export!(Test);
+
+impl Guest for Test {
+    fn allocated(s: String, l: Vec<u32>) -> bool {
+        install();
+        let allocated = ALLOCATED.lock().unwrap();
+        allocated.contains(&(s.as_ptr() as usize)) && allocated.contains(&(l.as_ptr() as usize))
+    }
+
+    fn echo(s: String) -> String {
+        install();
+        s
+    }
+
+    fn used() -> u32 {
+        install();
+        USED.load(Relaxed) as u32
+    }
+}
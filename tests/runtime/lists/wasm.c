@@ -102,7 +102,19 @@ void lists_test_imports() {
     assert(a.ptr[1].len == 6);
     assert(memcmp(a.ptr[0].ptr, "hello,", 6) == 0);
     assert(memcmp(a.ptr[1].ptr, "world!", 6) == 0);
+
+    lists_list_string_t b;
+    lists_list_string_clone(&b, &a);
+    assert(b.ptr != a.ptr);
+    assert(lists_list_string_eq(&a, &b));
+    lists_list_string_free(&a);
+    assert(b.len == 2);
+    assert(memcmp(b.ptr[1].ptr, "world!", 6) == 0);
+    b.ptr[1].ptr[0] = 'W';
+    test_lists_test_list_result3(&a);
+    assert(!lists_list_string_eq(&a, &b));
     lists_list_string_free(&a);
+    lists_list_string_free(&b);
   }
 
   {
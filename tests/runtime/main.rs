@@ -13,6 +13,7 @@ use wasmtime_wasi::{WasiCtx, WasiCtxBuilder, WasiView};
 use wit_component::{ComponentEncoder, StringEncoding};
 use wit_parser::{Resolve, WorldId, WorldItem};
 
+mod allocator;
 mod flavorful;
 mod lists;
 mod many_arguments;
@@ -32,6 +33,7 @@ mod resource_floats;
 mod resource_import_and_export;
 mod resource_into_inner;
 mod resource_with_lists;
+mod resource_world_exports;
 mod resources;
 mod results;
 mod rust_xcrate;
@@ -120,6 +122,7 @@ fn tests(name: &str, dir_name: &str) -> Result<Vec<PathBuf>> {
 
     let mut rust = Vec::new();
     let mut c = Vec::new();
+    let mut cpp = Vec::new();
     let mut java = Vec::new();
     let mut go = Vec::new();
     let mut c_sharp: Vec<PathBuf> = Vec::new();
@@ -127,6 +130,7 @@ fn tests(name: &str, dir_name: &str) -> Result<Vec<PathBuf>> {
         let path = file?.path();
         match path.extension().and_then(|s| s.to_str()) {
             Some("c") => c.push(path),
+            Some("cpp") => cpp.push(path),
             Some("java") => java.push(path),
             Some("rs") => rust.push(path),
             Some("go") => go.push(path),
@@ -190,7 +194,9 @@ fn tests(name: &str, dir_name: &str) -> Result<Vec<PathBuf>> {
             let mut files = Default::default();
             let mut opts = wit_bindgen_c::Opts::default();
             if let Some(path) = path.file_name().and_then(|s| s.to_str()) {
-                if path.contains("utf16") {
+                if path.contains("compact_utf16") {
+                    opts.string_encoding = wit_component::StringEncoding::CompactUTF16;
+                } else if path.contains("utf16") {
                     opts.string_encoding = wit_component::StringEncoding::UTF16;
                 }
             }
@@ -265,6 +271,85 @@ fn tests(name: &str, dir_name: &str) -> Result<Vec<PathBuf>> {
         }
     }
 
+    #[cfg(feature = "cpp")]
+    if !cpp.is_empty() {
+        let (resolve, world) = resolve_wit_dir(&dir);
+        for path in cpp.iter() {
+            let world_name = &resolve.worlds[world].name;
+            let out_dir = out_dir.join(format!("cpp-{}", world_name));
+            drop(fs::remove_dir_all(&out_dir));
+            fs::create_dir_all(&out_dir).unwrap();
+
+            let snake = world_name.replace("-", "_");
+            let mut files = Default::default();
+            wit_bindgen_cpp::Opts::default()
+                .build()
+                .generate(&resolve, world, &mut files)
+                .unwrap();
+
+            for (file, contents) in files.iter() {
+                let dst = out_dir.join(file);
+                fs::write(dst, contents).unwrap();
+            }
+
+            let sdk = PathBuf::from(std::env::var_os("WASI_SDK_PATH").expect(
+                "point the `WASI_SDK_PATH` environment variable to the path of your wasi-sdk",
+            ));
+            let mut cmd = Command::new(sdk.join("bin/clang++"));
+            let out_wasm = out_dir.join(format!(
+                "cpp-{}.wasm",
+                path.file_stem().and_then(|s| s.to_str()).unwrap()
+            ));
+            cmd.arg("--sysroot").arg(sdk.join("share/wasi-sysroot"));
+            cmd.arg(path)
+                .arg(out_dir.join(format!("{snake}.cpp")))
+                .arg(out_dir.join(format!("{snake}_component_type.o")))
+                .arg("-I")
+                .arg(&out_dir)
+                .arg("-std=c++23")
+                .arg("-Wall")
+                .arg("-Wextra")
+                .arg("-Werror")
+                .arg("-Wno-unused-parameter")
+                .arg("-mexec-model=reactor")
+                .arg("-g")
+                .arg("-o")
+                .arg(&out_wasm);
+            println!("{:?}", cmd);
+            let output = match cmd.output() {
+                Ok(output) => output,
+                Err(e) => panic!("failed to spawn compiler: {}", e),
+            };
+
+            if !output.status.success() {
+                println!("status: {}", output.status);
+                println!("stdout: ------------------------------------------");
+                println!("{}", String::from_utf8_lossy(&output.stdout));
+                println!("stderr: ------------------------------------------");
+                println!("{}", String::from_utf8_lossy(&output.stderr));
+                panic!("failed to compile");
+            }
+
+            // Translate the canonical ABI module into a component.
+            let module = fs::read(&out_wasm).expect("failed to read wasm file");
+            let component = ComponentEncoder::default()
+                .module(module.as_slice())
+                .expect("pull custom sections from module")
+                .validate(true)
+                .adapter("wasi_snapshot_preview1", &wasi_adapter)
+                .expect("adapter failed to get loaded")
+                .encode()
+                .expect(&format!(
+                    "module {:?} can be translated to a component",
+                    out_wasm
+                ));
+            let component_path = out_wasm.with_extension("component.wasm");
+            fs::write(&component_path, component).expect("write component to disk");
+
+            result.push(component_path);
+        }
+    }
+
     // FIXME: need to fix flaky Go test
     #[cfg(feature = "go")]
     if !go.is_empty() && name != "flavorful" {
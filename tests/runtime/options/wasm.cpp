@@ -0,0 +1,66 @@
+#include <assert.h>
+#include <options_cpp.h>
+
+namespace exports::options {
+
+class Options : public Guest {
+public:
+  void test_imports() override {
+    using namespace ::test::options::test;
+
+    option_none_param(std::nullopt);
+    option_some_param("foo");
+    assert(!option_none_result());
+    assert(option_some_result() == "foo");
+    assert(option_roundtrip("foo") == "foo");
+    assert(double_option_roundtrip(std::optional<uint32_t>(42)) ==
+           std::optional<uint32_t>(42));
+    assert(double_option_roundtrip(std::optional<uint32_t>()) ==
+           std::optional<uint32_t>());
+    assert(!double_option_roundtrip(std::nullopt));
+  }
+};
+
+Guest &guest() {
+  static Options options;
+  return options;
+}
+
+}
+
+namespace exports::test::options::test {
+
+class Test : public Guest {
+public:
+  void option_none_param(std::optional<std::string> a) override {
+    assert(!a);
+  }
+
+  void option_some_param(std::optional<std::string> a) override {
+    assert(a == "foo");
+  }
+
+  std::optional<std::string> option_none_result() override {
+    return std::nullopt;
+  }
+
+  std::optional<std::string> option_some_result() override {
+    return "foo";
+  }
+
+  std::optional<std::string> option_roundtrip(std::optional<std::string> a) override {
+    return a;
+  }
+
+  std::optional<std::optional<uint32_t>> double_option_roundtrip(
+      std::optional<std::optional<uint32_t>> a) override {
+    return a;
+  }
+};
+
+Guest &guest() {
+  static Test test;
+  return test;
+}
+
+}
@@ -0,0 +1,55 @@
+use wasmtime::{component::Resource, Store};
+
+wasmtime::component::bindgen!(in "tests/runtime/resource_world_exports");
+
+#[derive(Default)]
+pub struct MyHostRImpl {
+    values: Vec<u32>,
+    dropped: Vec<u32>,
+}
+
+impl HostR for MyHostRImpl {
+    fn new(&mut self, v: u32) -> Resource<R> {
+        self.values.push(v);
+        Resource::new_own(self.values.len() as u32 - 1)
+    }
+
+    fn value(&mut self, r: Resource<R>) -> u32 {
+        self.values[r.rep() as usize]
+    }
+
+    fn drop(&mut self, r: Resource<R>) -> anyhow::Result<()> {
+        self.dropped.push(r.rep());
+        Ok(())
+    }
+}
+
+impl ResourceWorldExportsImports for MyHostRImpl {}
+
+#[test]
+fn run() -> anyhow::Result<()> {
+    crate::run_test(
+        "resource_world_exports",
+        |linker| ResourceWorldExports::add_to_linker(linker, |x| &mut x.0),
+        |store, component, linker| ResourceWorldExports::instantiate(store, component, linker),
+        run_test,
+    )
+}
+
+fn run_test(
+    instance: ResourceWorldExports,
+    store: &mut Store<crate::Wasi<MyHostRImpl>>,
+) -> anyhow::Result<()> {
+    let r = instance.call_make(&mut *store, 3)?;
+    assert_eq!(store.data().0.values[r.rep() as usize], 3);
+    assert_eq!(instance.call_consume(&mut *store, r)?, 3);
+    assert_eq!(store.data().0.dropped, [0]);
+
+    let rs = [
+        instance.call_make(&mut *store, 4)?,
+        instance.call_make(&mut *store, 5)?,
+    ];
+    assert_eq!(instance.call_consume_all(&mut *store, &rs)?, 9);
+    assert_eq!(store.data().0.dropped, [0, 1, 2]);
+    Ok(())
+}
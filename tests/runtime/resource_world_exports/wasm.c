@@ -0,0 +1,21 @@
+#include <assert.h>
+#include <resource_world_exports.h>
+
+resource_world_exports_own_r_t resource_world_exports_make(uint32_t v) {
+    return resource_world_exports_constructor_r(v);
+}
+
+uint32_t resource_world_exports_consume(resource_world_exports_own_r_t r) {
+    uint32_t ret = resource_world_exports_method_r_value(resource_world_exports_borrow_r(r));
+    resource_world_exports_r_drop_own(r);
+    return ret;
+}
+
+uint32_t resource_world_exports_consume_all(resource_world_exports_list_own_r_t *rs) {
+    uint32_t sum = 0;
+    for (size_t i = 0; i < rs->len; i++) {
+        sum += resource_world_exports_consume(rs->ptr[i]);
+    }
+    resource_world_exports_list_own_r_free(rs);
+    return sum;
+}
@@ -0,0 +1,21 @@
+wit_bindgen::generate!({
+    path: "../../tests/runtime/resource_world_exports",
+});
+
+pub struct Test {}
+
+export!(Test);
+
+impl Guest for Test {
+    fn make(v: u32) -> R {
+        R::new(v)
+    }
+
+    fn consume(r: R) -> u32 {
+        r.value()
+    }
+
+    fn consume_all(rs: Vec<R>) -> u32 {
+        rs.iter().map(|r| r.value()).sum()
+    }
+}
@@ -0,0 +1,117 @@
+#include <assert.h>
+#include <resources_cpp.h>
+
+namespace exports::exports {
+
+class ComponentX : public X {
+public:
+  explicit ComponentX(int32_t a) : val(a) {}
+
+  int32_t get_a() override {
+    return val;
+  }
+
+  void set_a(int32_t a) override {
+    val = a;
+  }
+
+private:
+  int32_t val;
+};
+
+X::Owned X::create(int32_t a) {
+  return X::Owned(new ComponentX(a));
+}
+
+X::Owned X::add(X::Owned x, int32_t a) {
+  x->set_a(x->get_a() + a);
+  return x;
+}
+
+static uint32_t num_dropped_zs = 0;
+
+class ComponentZ : public Z {
+public:
+  explicit ComponentZ(int32_t a) : val(a) {}
+
+  ~ComponentZ() override {
+    num_dropped_zs += 1;
+  }
+
+  int32_t get_a() override {
+    return val;
+  }
+
+private:
+  int32_t val;
+};
+
+Z::Owned Z::create(int32_t a) {
+  return Z::Owned(new ComponentZ(a));
+}
+
+uint32_t Z::num_dropped() {
+  return num_dropped_zs + 1;
+}
+
+class ComponentKebabCase : public KebabCase {
+public:
+  explicit ComponentKebabCase(uint32_t a) : val(a) {}
+
+  uint32_t get_a() override {
+    return val;
+  }
+
+private:
+  uint32_t val;
+};
+
+KebabCase::Owned KebabCase::create(uint32_t a) {
+  return KebabCase::Owned(new ComponentKebabCase(a));
+}
+
+uint32_t KebabCase::take_owned(KebabCase::Owned k) {
+  return k->get_a();
+}
+
+class Exports : public Guest {
+public:
+  Z::Owned add(Z &a, Z &b) override {
+    return Z::create(a.get_a() + b.get_a());
+  }
+
+  void consume(X::Owned x) override {}
+
+  std::expected<void, std::string> test_imports() override {
+    using ::imports::Y;
+
+    Y y(10);
+    assert(y.get_a() == 10);
+    y.set_a(20);
+    assert(y.get_a() == 20);
+    Y y2 = Y::add(std::move(y), 20);
+    assert(y2.get_a() == 40);
+
+    // test multiple instances
+    Y y3(1);
+    Y y4(2);
+    assert(y3.get_a() == 1);
+    assert(y4.get_a() == 2);
+    y3.set_a(10);
+    y4.set_a(20);
+    assert(y3.get_a() == 10);
+    assert(y4.get_a() == 20);
+    Y y5 = Y::add(std::move(y3), 20);
+    Y y6 = Y::add(std::move(y4), 30);
+    assert(y5.get_a() == 30);
+    assert(y6.get_a() == 50);
+    return {};
+  }
+};
+
+Guest &guest() {
+  static Exports exports;
+  return exports;
+}
+
+}
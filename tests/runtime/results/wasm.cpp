@@ -0,0 +1,72 @@
+#include <results_cpp.h>
+
+namespace imports = ::test::results::test;
+
+namespace exports::test::results::test {
+
+static E convert(imports::E e) {
+  switch (e) {
+  case imports::E::kA:
+    return E::kA;
+  case imports::E::kB:
+    return E::kB;
+  case imports::E::kC:
+    return E::kC;
+  }
+  abort();
+}
+
+static E2 convert(imports::E2 e) {
+  return E2{e.line, e.column};
+}
+
+static E3 convert(imports::E3 e) {
+  if (auto *e1 = std::get_if<imports::E3::E1>(&e.variants)) {
+    return E3{E3::E1{convert(e1->value)}};
+  }
+  return E3{E3::E2{convert(std::get<imports::E3::E2>(e.variants).value)}};
+}
+
+template <class T, class Err>
+static std::expected<T, decltype(convert(std::declval<Err>()))>
+convert(std::expected<T, Err> r) {
+  if (r) {
+    return *r;
+  }
+  return std::unexpected(convert(std::move(r.error())));
+}
+
+class Test : public Guest {
+public:
+  std::expected<float, std::string> string_error(float a) override {
+    return imports::string_error(a);
+  }
+
+  std::expected<float, E> enum_error(float a) override {
+    return convert(imports::enum_error(a));
+  }
+
+  std::expected<float, E2> record_error(float a) override {
+    return convert(imports::record_error(a));
+  }
+
+  std::expected<float, E3> variant_error(float a) override {
+    return convert(imports::variant_error(a));
+  }
+
+  std::expected<uint32_t, std::monostate> empty_error(uint32_t a) override {
+    return imports::empty_error(a);
+  }
+
+  std::expected<std::expected<void, std::string>, std::string>
+  double_error(uint32_t a) override {
+    return imports::double_error(a);
+  }
+};
+
+Guest &guest() {
+  static Test test;
+  return test;
+}
+
+}
@@ -0,0 +1,20 @@
+#include <smoke_cpp.h>
+#include <stdio.h>
+
+namespace exports::smoke {
+
+class Smoke : public Guest {
+public:
+  void thunk() override {
+    ::test::smoke::imports::thunk();
+
+    printf("howdy\n");
+  }
+};
+
+Guest &guest() {
+  static Smoke smoke;
+  return smoke;
+}
+
+}
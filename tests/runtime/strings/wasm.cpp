@@ -0,0 +1,30 @@
+#include <assert.h>
+#include <strings_cpp.h>
+
+namespace exports::strings {
+
+class Strings : public Guest {
+public:
+  void test_imports() override {
+    ::test::strings::imports::take_basic("latin utf16");
+
+    std::string str = ::test::strings::imports::return_unicode();
+    assert(str == "🚀🚀🚀 𠈄𓀀");
+  }
+
+  std::string return_empty() override {
+    return "";
+  }
+
+  std::string roundtrip(std::string s) override {
+    assert(!s.empty());
+    return s;
+  }
+};
+
+Guest &guest() {
+  static Strings strings;
+  return strings;
+}
+
+}
@@ -0,0 +1,136 @@
+#include <assert.h>
+#include <variants_cpp.h>
+
+namespace exports::variants {
+
+// Returns the value of the case `T` of the variant `v`, which must hold it.
+template <class T, class V> static auto value(V const &v) {
+  auto *c = std::get_if<T>(&v.variants);
+  assert(c);
+  return c->value;
+}
+
+class Variants : public Guest {
+public:
+  void test_imports() override {
+    using namespace ::test::variants::test;
+
+    assert(roundtrip_option(1.0f) == 1);
+    assert(!roundtrip_option(std::nullopt));
+    assert(roundtrip_option(2.0f) == 2);
+    assert(roundtrip_result(2) == 2.0);
+    assert(roundtrip_result(4) == 4.0);
+    assert(roundtrip_result(std::unexpected(5.3f)).error() == 5);
+
+    assert(roundtrip_enum(E1::kA) == E1::kA);
+    assert(roundtrip_enum(E1::kB) == E1::kB);
+
+    assert(invert_bool(true) == false);
+    assert(invert_bool(false) == true);
+
+    {
+      auto [a1, a2, a3, a4, a5, a6] = variant_casts(
+          {C1{C1::A{1}}, C2{C2::A{2}}, C3{C3::A{3}}, C4{C4::A{4}},
+           C5{C5::A{5}}, C6{C6::A{6}}});
+      assert(value<C1::A>(a1) == 1);
+      assert(value<C2::A>(a2) == 2);
+      assert(value<C3::A>(a3) == 3);
+      assert(value<C4::A>(a4) == 4);
+      assert(value<C5::A>(a5) == 5);
+      assert(value<C6::A>(a6) == 6);
+    }
+
+    {
+      auto [a1, a2, a3, a4, a5, a6] = variant_casts(
+          {C1{C1::B{1}}, C2{C2::B{2}}, C3{C3::B{3}}, C4{C4::B{4}},
+           C5{C5::B{5}}, C6{C6::B{6}}});
+      assert(value<C1::B>(a1) == 1);
+      assert(value<C2::B>(a2) == 2);
+      assert(value<C3::B>(a3) == 3);
+      assert(value<C4::B>(a4) == 4);
+      assert(value<C5::B>(a5) == 5);
+      assert(value<C6::B>(a6) == 6);
+    }
+
+    {
+      auto [a1, a2, a3, a4] = variant_zeros(
+          {Z1{Z1::A{1}}, Z2{Z2::A{2}}, Z3{Z3::A{3}}, Z4{Z4::A{4}}});
+      assert(value<Z1::A>(a1) == 1);
+      assert(value<Z2::A>(a2) == 2);
+      assert(value<Z3::A>(a3) == 3);
+      assert(value<Z4::A>(a4) == 4);
+    }
+
+    {
+      auto [a1, a2, a3, a4] =
+          variant_zeros({Z1{Z1::B{}}, Z2{Z2::B{}}, Z3{Z3::B{}}, Z4{Z4::B{}}});
+      assert(std::holds_alternative<Z1::B>(a1.variants));
+      assert(std::holds_alternative<Z2::B>(a2.variants));
+      assert(std::holds_alternative<Z3::B>(a3.variants));
+      assert(std::holds_alternative<Z4::B>(a4.variants));
+    }
+
+    variant_typedefs(std::nullopt, false, std::unexpected(std::monostate{}));
+
+    auto [a, b, c] = variant_enums(true, {}, MyErrno::kSuccess);
+    assert(a == false);
+    assert(!b);
+    assert(c == MyErrno::kA);
+  }
+};
+
+Guest &guest() {
+  static Variants variants;
+  return variants;
+}
+
+}
+
+namespace exports::test::variants::test {
+
+class Test : public Guest {
+public:
+  std::optional<uint8_t> roundtrip_option(std::optional<float> a) override {
+    if (!a) {
+      return std::nullopt;
+    }
+    return static_cast<uint8_t>(*a);
+  }
+
+  std::expected<double, uint8_t> roundtrip_result(std::expected<uint32_t, float> a) override {
+    if (!a) {
+      return std::unexpected(static_cast<uint8_t>(a.error()));
+    }
+    return *a;
+  }
+
+  E1 roundtrip_enum(E1 a) override {
+    return a;
+  }
+
+  bool invert_bool(bool a) override {
+    return !a;
+  }
+
+  Casts variant_casts(Casts a) override {
+    return a;
+  }
+
+  Zeros variant_zeros(Zeros a) override {
+    return a;
+  }
+
+  void variant_typedefs(OptionTypedef a, BoolTypedef b, ResultTypedef c) override {}
+
+  std::tuple<bool, std::expected<void, std::monostate>, MyErrno>
+  variant_enums(bool a, std::expected<void, std::monostate> b, MyErrno c) override {
+    return {a, b, c};
+  }
+};
+
+Guest &guest() {
+  static Test test;
+  return test;
+}
+
+}